@@ -9,18 +9,25 @@ use axum::{
 };
 use axum_extra::extract::CookieJar;
 use serde::Serialize;
-use tower_http::services::ServeDir;
+use tower_http::{services::ServeDir, trace::TraceLayer};
+
+mod telemetry;
+
+use telemetry::{init_tracing, make_span, trace_context_headers};
 
 #[tokio::main]
 async fn main() {
+    init_tracing().expect("Failed to initialize tracing");
+
     let app = Router::new()
         .nest_service("/assets", ServeDir::new("assets"))
         .route("/", get(root))
-        .route("/protected", get(protected));
+        .route("/protected", get(protected))
+        .layer(TraceLayer::new_for_http().make_span_with(make_span));
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:8000").await.unwrap();
 
-    println!("listening on {}", listener.local_addr().unwrap());
+    tracing::info!("listening on {}", listener.local_addr().unwrap());
     axum::serve(listener, app).await.unwrap();
 }
 
@@ -46,6 +53,7 @@ async fn root() -> impl IntoResponse {
     Html(template.render().unwrap())
 }
 
+#[tracing::instrument(name = "Protected", skip_all)]
 async fn protected(jar: CookieJar) -> impl IntoResponse {
     let jwt_cookie = match jar.get("jwt") {
         Some(cookie) => cookie,
@@ -63,7 +71,14 @@ async fn protected(jar: CookieJar) -> impl IntoResponse {
     let auth_hostname = env::var("AUTH_SERVICE_HOST_NAME").unwrap_or("0.0.0.0".to_owned());
     let url = format!("http://{auth_hostname}:3000/verify-token");
 
-    let response = match api_client.post(&url).json(&verify_token_body).send().await {
+    let mut request = api_client.post(&url).json(&verify_token_body);
+    for (name, value) in trace_context_headers() {
+        if !value.is_empty() {
+            request = request.header(name, value);
+        }
+    }
+
+    let response = match request.send().await {
         Ok(response) => response,
         Err(_) => {
             return StatusCode::INTERNAL_SERVER_ERROR.into_response();
@@ -0,0 +1,75 @@
+use std::{collections::HashMap, env, error::Error};
+
+use axum::{body::Body, extract::Request};
+use opentelemetry::{global, trace::TracerProvider as _, KeyValue};
+use opentelemetry_http::HeaderExtractor;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{
+    propagation::TraceContextPropagator, runtime, trace::TracerProvider, Resource,
+};
+use tracing::{Level, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{fmt, prelude::*, EnvFilter};
+
+const DEFAULT_OTLP_ENDPOINT: &str = "http://localhost:4318";
+const DEFAULT_SERVICE_NAME: &str = "app-service";
+
+/// Installs the global subscriber. Spans always carry a trace context, so it reaches the auth
+/// service, and are exported over OTLP/HTTP when `TELEMETRY_ENABLED=true`.
+pub fn init_tracing() -> Result<(), Box<dyn Error + Send + Sync>> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let service_name =
+        env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| DEFAULT_SERVICE_NAME.to_owned());
+    let mut tracer_provider =
+        TracerProvider::builder().with_resource(Resource::new([KeyValue::new(
+            "service.name",
+            service_name.clone(),
+        )]));
+    if env::var("TELEMETRY_ENABLED").is_ok_and(|enabled| enabled == "true") {
+        let endpoint = env::var("OTEL_EXPORTER_OTLP_ENDPOINT")
+            .unwrap_or_else(|_| DEFAULT_OTLP_ENDPOINT.to_owned());
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+            .build()?;
+        tracer_provider = tracer_provider.with_batch_exporter(exporter, runtime::Tokio);
+    }
+    let tracer_provider = tracer_provider.build();
+
+    let filter_layer = EnvFilter::try_from_default_env().or_else(|_| EnvFilter::try_new("info"))?;
+    tracing_subscriber::registry()
+        .with(filter_layer)
+        .with(fmt::layer().compact())
+        .with(tracing_opentelemetry::layer().with_tracer(tracer_provider.tracer(service_name)))
+        .init();
+
+    Ok(())
+}
+
+/// Opens the request span. A `traceparent` header makes it a child of the caller's span.
+pub fn make_span(request: &Request<Body>) -> Span {
+    let span = tracing::span!(
+        Level::INFO,
+        "[REQUEST]",
+        method = tracing::field::display(request.method()),
+        uri = tracing::field::display(request.uri()),
+    );
+
+    let parent = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(request.headers()))
+    });
+    span.set_parent(parent);
+
+    span
+}
+
+/// W3C trace context headers for an outgoing request, so the callee's spans join the current
+/// trace.
+pub fn trace_context_headers() -> HashMap<String, String> {
+    let mut headers = HashMap::new();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&Span::current().context(), &mut headers)
+    });
+    headers
+}
@@ -81,6 +81,34 @@ loginButton.addEventListener("click", (e) => {
     });
 });
 
+const magicLinkLink = document.getElementById("magic-link-link");
+
+magicLinkLink.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    const email = loginForm.email.value;
+
+    fetch('/magic-link', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ email }),
+    }).then(response => {
+        response.json().then(data => {
+            if (response.status === 200) {
+                // Remembered so the 2FA form can be filled in when the link is opened.
+                localStorage.setItem("magicLinkEmail", email);
+                loginErrAlter.style.display = "none";
+                alert(data.message);
+            } else {
+                loginErrAlter.innerHTML = `<span><strong>Error: </strong>${data.error}</span>`;
+                loginErrAlter.style.display = "block";
+            }
+        });
+    });
+});
+
 const signupForm = document.getElementById("signup-form");
 const signupButton = document.getElementById("signup-form-submit");
 const signupErrAlter = document.getElementById("signup-err-alert");
@@ -132,18 +160,20 @@ TwoFAButton.addEventListener("click", (e) => {
     const email = TwoFAForm.email.value;
     const loginAttemptId = TwoFAForm.login_attempt_id.value;
     const TwoFACode = TwoFAForm.email_code.value;
+    const rememberDevice = TwoFAForm.remember_device.checked;
 
     fetch('/verify-2fa', {
         method: 'POST',
         headers: {
             'Content-Type': 'application/json',
         },
-        body: JSON.stringify({ email, loginAttemptId, "2FACode": TwoFACode }),
+        body: JSON.stringify({ email, loginAttemptId, "2FACode": TwoFACode, rememberDevice }),
     }).then(response => {
         if (response.ok) {
             TwoFAForm.email.value = "";
             TwoFAForm.email_code.value = "";
             TwoFAForm.login_attempt_id.value = "";
+            TwoFAForm.remember_device.checked = false;
             TwoFAErrAlter.style.display = "none";
             alert("You have successfully logged in.");
             loginSection.style.display = "block";
@@ -161,4 +191,74 @@ TwoFAButton.addEventListener("click", (e) => {
             });
         }
     });
-});
\ No newline at end of file
+});
+
+const TwoFAResendLink = document.getElementById("2fa-resend-link");
+
+TwoFAResendLink.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    const email = TwoFAForm.email.value;
+    const loginAttemptId = TwoFAForm.login_attempt_id.value;
+
+    fetch('/resend-2fa', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ email, loginAttemptId }),
+    }).then(response => {
+        if (response.ok) {
+            TwoFAErrAlter.style.display = "none";
+            alert("A new email with your code is on its way.");
+        } else {
+            const retryAfter = response.headers.get("Retry-After");
+            response.json().then(data => {
+                let error_msg = data.error;
+                if (retryAfter !== null) {
+                    error_msg = `${error_msg}, try again in ${retryAfter} seconds`;
+                }
+                if (error_msg !== undefined && error_msg !== null && error_msg !== "") {
+                    TwoFAErrAlter.innerHTML = `<span><strong>Error: </strong>${error_msg}</span>`;
+                    TwoFAErrAlter.style.display = "block";
+                } else {
+                    TwoFAErrAlter.style.display = "none";
+                }
+            });
+        }
+    });
+});
+
+// -----------------------------------------------------
+
+const magicLinkToken = new URLSearchParams(window.location.search).get("magicLinkToken");
+
+if (magicLinkToken !== null) {
+    window.history.replaceState({}, "", window.location.pathname);
+
+    fetch('/verify-magic-link', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ token: magicLinkToken }),
+    }).then(response => {
+        if (response.status === 206) {
+            TwoFAForm.email.value = localStorage.getItem("magicLinkEmail");
+            response.json().then(data => {
+                TwoFAForm.login_attempt_id.value = data.loginAttemptId;
+            });
+
+            loginSection.style.display = "none";
+            twoFASection.style.display = "block";
+            signupSection.style.display = "none";
+        } else if (response.status === 200) {
+            alert("You have successfully logged in.");
+        } else {
+            response.json().then(data => {
+                loginErrAlter.innerHTML = `<span><strong>Error: </strong>${data.error}</span>`;
+                loginErrAlter.style.display = "block";
+            });
+        }
+    });
+}
@@ -0,0 +1,113 @@
+//! Login throughput while signups are running, with and without the store-wide `RwLock` that
+//! `AppState` used to hold around the user store.
+//!
+//! Signups hash the password with Argon2 before inserting, so under the old lock every login
+//! queued behind them.
+
+use std::sync::Arc;
+
+use auth_service::{
+    domain::{Email, NewUser, Password, UserStore},
+    services::SqliteUserStore,
+};
+use criterion::{criterion_group, criterion_main, Criterion};
+use secrecy::Secret;
+use sqlx::sqlite::SqlitePoolOptions;
+use tokio::{runtime::Runtime, sync::RwLock, task::JoinSet};
+use uuid::Uuid;
+
+const CONCURRENT_SIGNUPS: usize = 4;
+const CONCURRENT_LOGINS: usize = 16;
+const LOGIN_EMAIL: &str = "login@example.com";
+const PASSWORD: &str = "password123";
+
+fn password() -> Password {
+    Password::parse(Secret::new(PASSWORD.to_owned())).unwrap()
+}
+
+async fn store_with_login_user() -> Arc<SqliteUserStore> {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    sqlx::migrate!("./migrations_sqlite")
+        .run(&pool)
+        .await
+        .unwrap();
+
+    let store = SqliteUserStore::new(pool);
+    store
+        .add_user(NewUser::new(
+            Email::parse(LOGIN_EMAIL).unwrap(),
+            password(),
+            false,
+        ))
+        .await
+        .unwrap();
+
+    Arc::new(store)
+}
+
+/// Runs the signups and logins concurrently. With `lock`, each call takes it the way the
+/// handlers used to: exclusively for signups, shared for logins.
+async fn signups_and_logins(store: Arc<SqliteUserStore>, lock: Option<Arc<RwLock<()>>>) {
+    let mut tasks = JoinSet::new();
+
+    for _ in 0..CONCURRENT_SIGNUPS {
+        let (store, lock) = (store.clone(), lock.clone());
+        tasks.spawn(async move {
+            let _guard = match &lock {
+                Some(lock) => Some(lock.write().await),
+                None => None,
+            };
+            let email = Email::parse(&format!("{}@example.com", Uuid::new_v4())).unwrap();
+            store
+                .add_user(NewUser::new(email, password(), false))
+                .await
+                .unwrap();
+        });
+    }
+
+    for _ in 0..CONCURRENT_LOGINS {
+        let (store, lock) = (store.clone(), lock.clone());
+        tasks.spawn(async move {
+            let _guard = match &lock {
+                Some(lock) => Some(lock.read().await),
+                None => None,
+            };
+            store
+                .validate_user(Email::parse(LOGIN_EMAIL).unwrap(), password())
+                .await
+                .unwrap();
+        });
+    }
+
+    while let Some(result) = tasks.join_next().await {
+        result.unwrap();
+    }
+}
+
+fn concurrent_login(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let store = runtime.block_on(store_with_login_user());
+
+    let mut group = c.benchmark_group("concurrent_login");
+    group.sample_size(10);
+
+    group.bench_function("store_wide_lock", |b| {
+        let lock = Arc::new(RwLock::new(()));
+        b.to_async(&runtime)
+            .iter(|| signups_and_logins(store.clone(), Some(lock.clone())));
+    });
+
+    group.bench_function("lock_free_store", |b| {
+        b.to_async(&runtime)
+            .iter(|| signups_and_logins(store.clone(), None));
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, concurrent_login);
+criterion_main!(benches);
@@ -0,0 +1,5 @@
+-- Add up migration script here
+ALTER TABLE users
+   ADD COLUMN phone_number TEXT,
+   ADD COLUMN phone_verified BOOLEAN NOT NULL DEFAULT FALSE,
+   ADD COLUMN two_fa_channel TEXT NOT NULL DEFAULT 'email';
@@ -0,0 +1,27 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS banned_tokens(
+   token TEXT NOT NULL PRIMARY KEY,
+   expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS banned_tokens_expires_at_idx ON banned_tokens (expires_at);
+
+CREATE TABLE IF NOT EXISTS two_fa_codes(
+   login_attempt_id TEXT NOT NULL PRIMARY KEY,
+   email TEXT NOT NULL,
+   code TEXT NOT NULL,
+   attempts INTEGER NOT NULL DEFAULT 0,
+   resends INTEGER NOT NULL DEFAULT 0,
+   created_at TIMESTAMPTZ NOT NULL,
+   last_sent_at TIMESTAMPTZ NOT NULL,
+   expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS two_fa_codes_email_idx ON two_fa_codes (email);
+CREATE INDEX IF NOT EXISTS two_fa_codes_expires_at_idx ON two_fa_codes (expires_at);
+
+CREATE TABLE IF NOT EXISTS two_fa_codes_issued(
+   email TEXT NOT NULL PRIMARY KEY,
+   issued INTEGER NOT NULL,
+   expires_at TIMESTAMPTZ NOT NULL
+);
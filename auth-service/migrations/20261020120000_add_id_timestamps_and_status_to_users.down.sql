@@ -0,0 +1,11 @@
+-- Add down migration script here
+ALTER TABLE users DROP CONSTRAINT users_email_key;
+ALTER TABLE users DROP CONSTRAINT users_pkey;
+ALTER TABLE users ADD PRIMARY KEY (email);
+
+ALTER TABLE users
+   DROP COLUMN IF EXISTS last_login_at,
+   DROP COLUMN IF EXISTS updated_at,
+   DROP COLUMN IF EXISTS created_at,
+   DROP COLUMN IF EXISTS status,
+   DROP COLUMN IF EXISTS id;
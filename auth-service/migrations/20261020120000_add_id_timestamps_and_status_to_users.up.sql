@@ -0,0 +1,12 @@
+-- Add up migration script here
+ALTER TABLE users
+   ADD COLUMN id UUID NOT NULL DEFAULT gen_random_uuid(),
+   ADD COLUMN status TEXT NOT NULL DEFAULT 'active'
+      CHECK (status IN ('active', 'disabled', 'pending_verification')),
+   ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+   ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+   ADD COLUMN last_login_at TIMESTAMPTZ;
+
+ALTER TABLE users DROP CONSTRAINT users_pkey;
+ALTER TABLE users ADD PRIMARY KEY (id);
+ALTER TABLE users ADD CONSTRAINT users_email_key UNIQUE (email);
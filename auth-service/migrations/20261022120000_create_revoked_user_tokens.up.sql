@@ -0,0 +1,8 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS revoked_user_tokens(
+   user_id UUID NOT NULL PRIMARY KEY,
+   issued_before TIMESTAMPTZ NOT NULL,
+   expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS revoked_user_tokens_expires_at_idx ON revoked_user_tokens (expires_at);
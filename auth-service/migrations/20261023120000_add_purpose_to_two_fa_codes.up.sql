@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE two_fa_codes
+   ADD COLUMN purpose TEXT NOT NULL DEFAULT 'login';
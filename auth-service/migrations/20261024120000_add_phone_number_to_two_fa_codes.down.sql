@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE two_fa_codes
+   DROP COLUMN IF EXISTS phone_number;
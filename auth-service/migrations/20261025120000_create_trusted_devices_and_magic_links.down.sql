@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS magic_links;
+DROP TABLE IF EXISTS trusted_devices;
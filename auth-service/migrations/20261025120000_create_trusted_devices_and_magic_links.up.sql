@@ -0,0 +1,20 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS trusted_devices(
+   id TEXT NOT NULL PRIMARY KEY,
+   email TEXT NOT NULL,
+   user_agent TEXT,
+   created_at TIMESTAMPTZ NOT NULL,
+   expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS trusted_devices_email_idx ON trusted_devices (email);
+CREATE INDEX IF NOT EXISTS trusted_devices_expires_at_idx ON trusted_devices (expires_at);
+
+CREATE TABLE IF NOT EXISTS magic_links(
+   token_hash TEXT NOT NULL PRIMARY KEY,
+   email TEXT NOT NULL,
+   nonce_hash TEXT NOT NULL,
+   expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS magic_links_expires_at_idx ON magic_links (expires_at);
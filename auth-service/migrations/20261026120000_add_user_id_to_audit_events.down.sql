@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE audit_events
+   DROP COLUMN IF EXISTS user_id;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE audit_events
+   ADD COLUMN user_id TEXT;
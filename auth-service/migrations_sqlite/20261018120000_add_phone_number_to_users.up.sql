@@ -0,0 +1,4 @@
+-- Add up migration script here
+ALTER TABLE users ADD COLUMN phone_number TEXT;
+ALTER TABLE users ADD COLUMN phone_verified BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE users ADD COLUMN two_fa_channel TEXT NOT NULL DEFAULT 'email';
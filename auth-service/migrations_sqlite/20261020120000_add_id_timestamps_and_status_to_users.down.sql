@@ -0,0 +1,16 @@
+-- Add down migration script here
+CREATE TABLE users_old(
+   email TEXT NOT NULL PRIMARY KEY,
+   password_hash TEXT NOT NULL,
+   requires_2fa BOOLEAN NOT NULL DEFAULT FALSE,
+   phone_number TEXT,
+   phone_verified BOOLEAN NOT NULL DEFAULT FALSE,
+   two_fa_channel TEXT NOT NULL DEFAULT 'email'
+);
+
+INSERT INTO users_old (email, password_hash, requires_2fa, phone_number, phone_verified, two_fa_channel)
+   SELECT email, password_hash, requires_2fa, phone_number, phone_verified, two_fa_channel
+   FROM users;
+
+DROP TABLE users;
+ALTER TABLE users_old RENAME TO users;
@@ -0,0 +1,23 @@
+-- Add up migration script here
+-- SQLite cannot change a primary key in place, so the table is rebuilt.
+CREATE TABLE users_new(
+   id BLOB NOT NULL PRIMARY KEY,
+   email TEXT NOT NULL UNIQUE,
+   password_hash TEXT NOT NULL,
+   requires_2fa BOOLEAN NOT NULL DEFAULT FALSE,
+   phone_number TEXT,
+   phone_verified BOOLEAN NOT NULL DEFAULT FALSE,
+   two_fa_channel TEXT NOT NULL DEFAULT 'email',
+   status TEXT NOT NULL DEFAULT 'active'
+      CHECK (status IN ('active', 'disabled', 'pending_verification')),
+   created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+   updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+   last_login_at TEXT
+);
+
+INSERT INTO users_new (id, email, password_hash, requires_2fa, phone_number, phone_verified, two_fa_channel)
+   SELECT randomblob(16), email, password_hash, requires_2fa, phone_number, phone_verified, two_fa_channel
+   FROM users;
+
+DROP TABLE users;
+ALTER TABLE users_new RENAME TO users;
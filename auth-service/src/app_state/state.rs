@@ -1,33 +1,66 @@
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
-use crate::domain::{BannedTokenStore, EmailClient, TwoFACodeStore, UserStore};
+use crate::{
+    domain::{
+        AuditLog, BannedTokenStore, Dependency, EmailClient, MagicLinkStore, SmsClient,
+        TrustedDeviceStore, TwoFACodeStore, UserStore,
+    },
+    utils::{settings::Settings, shutdown::ShutdownHandle},
+};
 
-pub type UserStoreType = Arc<RwLock<Box<dyn UserStore + 'static>>>;
-pub type BannedTokenStoreType = Arc<RwLock<Box<dyn BannedTokenStore + 'static>>>;
-pub type TwoFACodeStoreType = Arc<RwLock<Box<dyn TwoFACodeStore + 'static>>>;
+pub type UserStoreType = Arc<dyn UserStore + 'static>;
+pub type BannedTokenStoreType = Arc<dyn BannedTokenStore + 'static>;
+pub type TwoFACodeStoreType = Arc<dyn TwoFACodeStore + 'static>;
+pub type TrustedDeviceStoreType = Arc<dyn TrustedDeviceStore + 'static>;
+pub type MagicLinkStoreType = Arc<dyn MagicLinkStore + 'static>;
+pub type AuditLogType = Arc<dyn AuditLog + 'static>;
 pub type EmailClientType = Arc<RwLock<Box<dyn EmailClient + 'static>>>;
+pub type SmsClientType = Arc<RwLock<Box<dyn SmsClient + 'static>>>;
+pub type DependenciesType = Arc<Vec<Dependency>>;
 
 #[derive(Clone)]
 pub struct AppState {
+    pub settings: Arc<Settings>,
     pub user_store: UserStoreType,
     pub banned_token_store: BannedTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
+    pub trusted_device_store: TrustedDeviceStoreType,
+    pub magic_link_store: MagicLinkStoreType,
+    pub audit_log: AuditLogType,
     pub email_client: EmailClientType,
+    /// Set only when SMS is enabled.
+    pub sms_client: Option<SmsClientType>,
+    pub dependencies: DependenciesType,
+    pub shutdown: ShutdownHandle,
 }
 
 impl AppState {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
+        settings: Arc<Settings>,
         user_store: UserStoreType,
         banned_token_store: BannedTokenStoreType,
         two_fa_code_store: TwoFACodeStoreType,
+        trusted_device_store: TrustedDeviceStoreType,
+        magic_link_store: MagicLinkStoreType,
+        audit_log: AuditLogType,
         email_client: EmailClientType,
+        sms_client: Option<SmsClientType>,
+        dependencies: DependenciesType,
     ) -> Self {
         Self {
+            settings,
             user_store,
             banned_token_store,
             two_fa_code_store,
+            trusted_device_store,
+            magic_link_store,
+            audit_log,
             email_client,
+            sms_client,
+            dependencies,
+            shutdown: ShutdownHandle::default(),
         }
     }
 }
@@ -0,0 +1,263 @@
+use chrono::{SecondsFormat, Utc};
+use color_eyre::eyre::Result;
+
+use auth_service::{
+    app_state::{BannedTokenStoreType, UserStoreType},
+    domain::{Email, NewUser, Password, UserCursor, UserFilter, UserQuery, UserStatus, UserUpdate},
+};
+
+use crate::output::{Report, UserRow};
+
+/// The stores the user and session commands work on, configured as the service configures them.
+pub struct Stores {
+    pub user_store: UserStoreType,
+    pub banned_token_store: BannedTokenStoreType,
+}
+
+impl Stores {
+    pub async fn create_user(
+        &self,
+        email: Email,
+        password: Password,
+        requires_2fa: bool,
+    ) -> Result<Report> {
+        let user = self
+            .user_store
+            .add_user(NewUser::new(email, password, requires_2fa))
+            .await?;
+
+        Ok(Report::User(UserRow::from(&user)))
+    }
+
+    pub async fn set_password(&self, email: Email, password: Password) -> Result<Report> {
+        let update = UserUpdate {
+            password: Some(password),
+            ..UserUpdate::default()
+        };
+        self.update_user(email, update).await
+    }
+
+    pub async fn set_2fa(&self, email: Email, requires_2fa: bool) -> Result<Report> {
+        let update = UserUpdate {
+            requires_2fa: Some(requires_2fa),
+            ..UserUpdate::default()
+        };
+        self.update_user(email, update).await
+    }
+
+    /// Disabling a user also stops their tokens from validating, see `UserStatus`.
+    pub async fn set_status(&self, email: Email, status: UserStatus) -> Result<Report> {
+        let update = UserUpdate {
+            status: Some(status),
+            ..UserUpdate::default()
+        };
+        self.update_user(email, update).await
+    }
+
+    pub async fn delete_user(&self, email: Email) -> Result<Report> {
+        let user = self.user_store.get_user(email).await?;
+        self.user_store.delete_user(user.id()).await?;
+
+        Ok(Report::Done {
+            message: format!("Deleted user {}", user.email().as_ref()),
+        })
+    }
+
+    pub async fn list_users(
+        &self,
+        filter: UserFilter,
+        after: Option<UserCursor>,
+        limit: u32,
+    ) -> Result<Report> {
+        let page = self
+            .user_store
+            .list_users(&UserQuery {
+                filter,
+                after,
+                limit,
+            })
+            .await?;
+
+        Ok(Report::Users {
+            users: page.users.iter().map(UserRow::from).collect(),
+            next_cursor: page.next_cursor.map(|cursor| cursor.to_string()),
+        })
+    }
+
+    /// Ends every session the user has, by revoking all the tokens issued to them so far.
+    pub async fn revoke_sessions(&self, email: Email) -> Result<Report> {
+        let user = self.user_store.get_user(email).await?;
+        let issued_before = Utc::now();
+        self.banned_token_store
+            .revoke_user_tokens(user.id(), issued_before)
+            .await?;
+
+        Ok(Report::Done {
+            message: format!(
+                "Revoked the sessions of {} started before {}",
+                user.email().as_ref(),
+                issued_before.to_rfc3339_opts(SecondsFormat::Secs, true)
+            ),
+        })
+    }
+
+    async fn update_user(&self, email: Email, update: UserUpdate) -> Result<Report> {
+        let user = self.user_store.get_user(email).await?;
+        let user = self.user_store.update_user(user.id(), update).await?;
+
+        Ok(Report::User(UserRow::from(&user)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use auth_service::{
+        domain::{UserId, UserStoreError},
+        services::{HashmapUserStore, HashsetBannedTokenStore},
+    };
+    use secrecy::Secret;
+
+    use super::*;
+
+    fn stores() -> Stores {
+        Stores {
+            user_store: Arc::new(HashmapUserStore::default()),
+            banned_token_store: Arc::new(HashsetBannedTokenStore::default()),
+        }
+    }
+
+    fn email(email: &str) -> Email {
+        Email::parse(email).unwrap()
+    }
+
+    fn password(password: &str) -> Password {
+        Password::parse(Secret::new(password.to_owned())).unwrap()
+    }
+
+    fn user_row(report: Report) -> UserRow {
+        match report {
+            Report::User(user) => user,
+            _ => panic!("Expected a single user"),
+        }
+    }
+
+    #[tokio::test]
+    async fn create_user_and_update_it() {
+        let stores = stores();
+
+        let created = user_row(
+            stores
+                .create_user(email("ops@example.com"), password("password123"), false)
+                .await
+                .unwrap(),
+        );
+        assert_eq!(created.email, "ops@example.com");
+        assert!(!created.requires_2fa);
+
+        let updated = user_row(
+            stores
+                .set_2fa(email("ops@example.com"), true)
+                .await
+                .unwrap(),
+        );
+        assert!(updated.requires_2fa);
+
+        let disabled = user_row(
+            stores
+                .set_status(email("ops@example.com"), UserStatus::Disabled)
+                .await
+                .unwrap(),
+        );
+        assert_eq!(disabled.status, "disabled");
+
+        stores
+            .set_password(email("ops@example.com"), password("new-password"))
+            .await
+            .unwrap();
+        assert!(stores
+            .user_store
+            .validate_user(email("ops@example.com"), password("new-password"))
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn list_users_pages_through_the_users() {
+        let stores = stores();
+        for address in ["a@example.com", "b@example.com", "c@example.com"] {
+            stores
+                .create_user(email(address), password("password123"), false)
+                .await
+                .unwrap();
+        }
+
+        let Report::Users { users, next_cursor } = stores
+            .list_users(UserFilter::default(), None, 2)
+            .await
+            .unwrap()
+        else {
+            panic!("Expected a list of users");
+        };
+        assert_eq!(users.len(), 2);
+
+        let after = UserCursor::parse(&next_cursor.unwrap()).unwrap();
+        let Report::Users { users, next_cursor } = stores
+            .list_users(UserFilter::default(), Some(after), 2)
+            .await
+            .unwrap()
+        else {
+            panic!("Expected a list of users");
+        };
+        assert_eq!(users.len(), 1);
+        assert!(next_cursor.is_none());
+    }
+
+    #[tokio::test]
+    async fn revoke_sessions_and_delete_user() {
+        let stores = stores();
+        let user = user_row(
+            stores
+                .create_user(email("ops@example.com"), password("password123"), false)
+                .await
+                .unwrap(),
+        );
+
+        stores
+            .revoke_sessions(email("ops@example.com"))
+            .await
+            .unwrap();
+        let user_id = UserId::parse(&user.id).unwrap();
+        assert!(stores
+            .banned_token_store
+            .user_tokens_revoked_before(&user_id)
+            .await
+            .unwrap()
+            .is_some());
+
+        stores.delete_user(email("ops@example.com")).await.unwrap();
+        assert_eq!(
+            stores
+                .user_store
+                .get_user(email("ops@example.com"))
+                .await
+                .unwrap_err(),
+            UserStoreError::UserNotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn commands_on_unknown_users_fail() {
+        let stores = stores();
+
+        assert!(stores
+            .set_2fa(email("nobody@example.com"), true)
+            .await
+            .is_err());
+        assert!(stores
+            .revoke_sessions(email("nobody@example.com"))
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,354 @@
+//! Operator commands run against the database and stores the auth service is configured with,
+//! so users and sessions can be managed without raw SQL.
+
+mod commands;
+mod output;
+
+use std::{
+    io::{self, IsTerminal},
+    sync::Arc,
+};
+
+use auth_service::{
+    app_state::BannedTokenStoreType,
+    domain::{Email, Password, UserCursor, UserFilter, UserStatus},
+    get_postgres_pool, get_redis_client, get_sqlite_pool,
+    services::{
+        PostgresBannedTokenStore, PostgresUserStore, RedisBannedTokenStore, RedisPool,
+        SqliteUserStore,
+    },
+    utils::{
+        constants::StoreBackend,
+        settings::{DatabaseSettings, Settings},
+    },
+    with_user_cache, POSTGRES_MIGRATOR, SQLITE_MIGRATOR,
+};
+use clap::{Parser, Subcommand, ValueEnum};
+use color_eyre::eyre::{eyre, Context, Result};
+use secrecy::Secret;
+use sqlx::{
+    migrate::{Migrate, Migrator},
+    PgPool, Pool, SqlitePool,
+};
+
+use crate::{
+    commands::Stores,
+    output::{Format, MigrationRow, Report},
+};
+
+/// Manage the users, sessions and database of the auth service.
+///
+/// Settings are loaded as the service loads them, from the settings file and the environment.
+#[derive(Parser)]
+#[command(name = "auth-admin")]
+struct Cli {
+    /// How to print the result.
+    #[arg(long, value_enum, default_value_t = Format::Table, global = true)]
+    format: Format,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create a user. The password is read from the first line of stdin.
+    CreateUser {
+        #[arg(value_parser = Email::parse)]
+        email: Email,
+        /// Require a 2FA code at login.
+        #[arg(long)]
+        requires_2fa: bool,
+    },
+    /// Replace a user's password. The new one is read from the first line of stdin.
+    SetPassword {
+        #[arg(value_parser = Email::parse)]
+        email: Email,
+    },
+    /// Turn 2FA on or off for a user.
+    #[command(name = "set-2fa")]
+    Set2fa {
+        #[arg(value_parser = Email::parse)]
+        email: Email,
+        #[arg(value_enum)]
+        state: Toggle,
+    },
+    /// Stop a user from signing in. Their current sessions end too.
+    Disable {
+        #[arg(value_parser = Email::parse)]
+        email: Email,
+    },
+    /// Let a disabled user sign in again.
+    Enable {
+        #[arg(value_parser = Email::parse)]
+        email: Email,
+    },
+    /// Delete a user.
+    Delete {
+        #[arg(value_parser = Email::parse)]
+        email: Email,
+    },
+    /// List users, oldest first.
+    ListUsers {
+        /// Only users whose email starts with this.
+        #[arg(long)]
+        email_prefix: Option<String>,
+        /// Only users with this status: active, disabled or pending_verification.
+        #[arg(long, value_parser = UserStatus::parse)]
+        status: Option<UserStatus>,
+        #[arg(long, default_value_t = 50)]
+        limit: u32,
+        /// Continue from the cursor printed with the previous page.
+        #[arg(long, value_parser = UserCursor::parse)]
+        after: Option<UserCursor>,
+    },
+    /// End all of a user's sessions, revoking every token issued to them so far.
+    RevokeSessions {
+        #[arg(value_parser = Email::parse)]
+        email: Email,
+    },
+    /// Apply or roll back database migrations.
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateAction,
+    },
+    /// Print the effective settings, with secrets redacted.
+    Config,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Toggle {
+    On,
+    Off,
+}
+
+#[derive(Subcommand)]
+enum MigrateAction {
+    /// Apply every pending migration.
+    Run,
+    /// Roll back the latest applied migration, or every migration after `--to`.
+    Revert {
+        /// Version to roll back to. `0` rolls back everything.
+        #[arg(long)]
+        to: Option<i64>,
+    },
+    /// List the migrations and whether each is applied.
+    Status,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    color_eyre::install()?;
+    let cli = Cli::parse();
+    let settings = Settings::load()?;
+
+    let report = run(cli.command, &settings).await?;
+    print!("{}", report.render(cli.format));
+
+    Ok(())
+}
+
+async fn run(command: Command, settings: &Settings) -> Result<Report> {
+    match command {
+        Command::CreateUser {
+            email,
+            requires_2fa,
+        } => {
+            let password = read_password()?;
+            let stores = connect_stores(settings).await?;
+            stores.create_user(email, password, requires_2fa).await
+        }
+        Command::SetPassword { email } => {
+            let password = read_password()?;
+            let stores = connect_stores(settings).await?;
+            stores.set_password(email, password).await
+        }
+        Command::Set2fa { email, state } => {
+            let stores = connect_stores(settings).await?;
+            stores.set_2fa(email, matches!(state, Toggle::On)).await
+        }
+        Command::Disable { email } => {
+            let stores = connect_stores(settings).await?;
+            stores.set_status(email, UserStatus::Disabled).await
+        }
+        Command::Enable { email } => {
+            let stores = connect_stores(settings).await?;
+            stores.set_status(email, UserStatus::Active).await
+        }
+        Command::Delete { email } => connect_stores(settings).await?.delete_user(email).await,
+        Command::ListUsers {
+            email_prefix,
+            status,
+            limit,
+            after,
+        } => {
+            let filter = UserFilter {
+                email_prefix,
+                status,
+                ..UserFilter::default()
+            };
+            let stores = connect_stores(settings).await?;
+            stores.list_users(filter, after, limit).await
+        }
+        Command::RevokeSessions { email } => {
+            connect_stores(settings).await?.revoke_sessions(email).await
+        }
+        Command::Migrate { action } => match Database::connect(&settings.database).await? {
+            Database::Postgres(pool) => migrate(action, &POSTGRES_MIGRATOR, &pool).await,
+            Database::Sqlite(pool) => migrate(action, &SQLITE_MIGRATOR, &pool).await,
+        },
+        Command::Config => Ok(Report::Settings(serde_json::to_value(settings)?)),
+    }
+}
+
+/// The user database, opened without running migrations.
+enum Database {
+    Postgres(PgPool),
+    Sqlite(SqlitePool),
+}
+
+impl Database {
+    async fn connect(settings: &DatabaseSettings) -> Result<Self> {
+        match settings.uses_sqlite() {
+            true => Ok(Self::Sqlite(
+                get_sqlite_pool(settings)
+                    .await
+                    .wrap_err("failed to open the SQLite database")?,
+            )),
+            false => Ok(Self::Postgres(
+                get_postgres_pool(&settings.url, &settings.pool_config())
+                    .await
+                    .wrap_err("failed to connect to Postgres")?,
+            )),
+        }
+    }
+}
+
+/// Builds the user and banned token stores the way the service does, user cache included. Changes
+/// made here drop the user from a Redis cache; a service caching in memory keeps its copy until
+/// it expires, though it never uses that copy for passwords or account status.
+async fn connect_stores(settings: &Settings) -> Result<Stores> {
+    let database = Database::connect(&settings.database).await?;
+    let redis_pool = match settings.uses_redis() {
+        true => {
+            let redis_client = get_redis_client(settings.redis.host_name.to_owned())?;
+            let redis_pool = RedisPool::new(redis_client, settings.redis.pool_config())
+                .await
+                .wrap_err("failed to connect to Redis")?;
+            Some(redis_pool)
+        }
+        false => None,
+    };
+
+    let user_store = match &database {
+        Database::Postgres(pool) => with_user_cache(
+            PostgresUserStore::new(pool.clone()),
+            &settings.user_cache,
+            redis_pool.as_ref(),
+        ),
+        Database::Sqlite(pool) => with_user_cache(
+            SqliteUserStore::new(pool.clone()),
+            &settings.user_cache,
+            redis_pool.as_ref(),
+        ),
+    };
+    let banned_token_store: BannedTokenStoreType =
+        match (settings.stores.banned_token_store, database) {
+            (StoreBackend::Redis, _) => {
+                Arc::new(RedisBannedTokenStore::new(redis_pool.ok_or_else(|| {
+                    eyre!("the Redis banned token store requires Redis")
+                })?))
+            }
+            (StoreBackend::Postgres, Database::Postgres(pool)) => {
+                Arc::new(PostgresBannedTokenStore::new(pool))
+            }
+            (StoreBackend::Postgres, Database::Sqlite(_)) => {
+                return Err(eyre!(
+                    "Postgres store backends require a Postgres DATABASE_URL"
+                ))
+            }
+        };
+
+    Ok(Stores {
+        user_store,
+        banned_token_store,
+    })
+}
+
+async fn migrate<DB>(action: MigrateAction, migrator: &Migrator, pool: &Pool<DB>) -> Result<Report>
+where
+    DB: sqlx::Database,
+    DB::Connection: Migrate,
+{
+    match action {
+        MigrateAction::Run => migrator
+            .run(pool)
+            .await
+            .wrap_err("failed to run migrations")?,
+        MigrateAction::Revert { to } => {
+            let target = match to {
+                Some(version) => version,
+                None => {
+                    let applied = applied_versions(pool).await?;
+                    match applied.as_slice() {
+                        [] => return Err(eyre!("No migrations have been applied")),
+                        [.., previous, _] => *previous,
+                        [_] => 0,
+                    }
+                }
+            };
+            migrator
+                .undo(pool, target)
+                .await
+                .wrap_err("failed to roll back migrations")?;
+        }
+        MigrateAction::Status => {}
+    }
+
+    let applied = applied_versions(pool).await?;
+    let migrations = migrator
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| MigrationRow {
+            version: migration.version,
+            description: migration.description.to_string(),
+            applied: applied.contains(&migration.version),
+        })
+        .collect();
+
+    Ok(Report::Migrations { migrations })
+}
+
+/// Versions of the applied migrations, oldest first.
+async fn applied_versions<DB>(pool: &Pool<DB>) -> Result<Vec<i64>>
+where
+    DB: sqlx::Database,
+    DB::Connection: Migrate,
+{
+    let mut connection = pool.acquire().await?;
+    connection.ensure_migrations_table().await?;
+    let mut versions: Vec<i64> = connection
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect();
+    versions.sort_unstable();
+
+    Ok(versions)
+}
+
+/// Reads a password from the first line of stdin, which keeps it out of the shell history.
+fn read_password() -> Result<Password> {
+    let stdin = io::stdin();
+    if stdin.is_terminal() {
+        eprint!("Password: ");
+    }
+
+    let mut line = String::new();
+    stdin
+        .read_line(&mut line)
+        .wrap_err("failed to read the password")?;
+    let password = line.trim_end_matches(['\r', '\n']).to_owned();
+
+    Password::parse(Secret::new(password)).wrap_err("passwords must be at least 8 characters long")
+}
@@ -0,0 +1,260 @@
+use auth_service::domain::StoredUser;
+use chrono::{DateTime, SecondsFormat, Utc};
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value;
+
+/// How command results are printed.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Format {
+    /// Aligned columns, for people.
+    Table,
+    /// Pretty-printed JSON, for scripts.
+    Json,
+}
+
+/// A user as the commands print it. The password hash is left out.
+#[derive(Debug, Serialize)]
+pub struct UserRow {
+    pub id: String,
+    pub email: String,
+    pub status: String,
+    pub requires_2fa: bool,
+    pub two_fa_channel: String,
+    pub phone_number: Option<String>,
+    pub phone_verified: bool,
+    pub created_at: DateTime<Utc>,
+    pub last_login_at: Option<DateTime<Utc>>,
+}
+
+impl From<&StoredUser> for UserRow {
+    fn from(user: &StoredUser) -> Self {
+        Self {
+            id: user.id().to_string(),
+            email: user.email().as_ref().to_owned(),
+            status: user.status().as_ref().to_owned(),
+            requires_2fa: *user.requires_2fa(),
+            two_fa_channel: user.two_fa_channel().as_ref().to_owned(),
+            phone_number: user
+                .phone_number()
+                .as_ref()
+                .map(|phone_number| phone_number.as_ref().to_owned()),
+            phone_verified: *user.phone_verified(),
+            created_at: *user.created_at(),
+            last_login_at: *user.last_login_at(),
+        }
+    }
+}
+
+impl UserRow {
+    const HEADERS: [&'static str; 6] = ["ID", "EMAIL", "STATUS", "2FA", "CREATED", "LAST LOGIN"];
+
+    fn cells(&self) -> Vec<String> {
+        let two_fa = match self.requires_2fa {
+            true => self.two_fa_channel.clone(),
+            false => "off".to_owned(),
+        };
+
+        vec![
+            self.id.clone(),
+            self.email.clone(),
+            self.status.clone(),
+            two_fa,
+            timestamp(&self.created_at),
+            self.last_login_at
+                .as_ref()
+                .map_or_else(|| "never".to_owned(), timestamp),
+        ]
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct MigrationRow {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+}
+
+/// The result of a command.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum Report {
+    User(UserRow),
+    Users {
+        users: Vec<UserRow>,
+        /// Passed to `--after` for the next page, if there is one.
+        next_cursor: Option<String>,
+    },
+    Migrations {
+        migrations: Vec<MigrationRow>,
+    },
+    Settings(Value),
+    Done {
+        message: String,
+    },
+}
+
+impl Report {
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Json => {
+                let json = serde_json::to_string_pretty(self).expect("reports serialize to JSON");
+                format!("{json}\n")
+            }
+            Format::Table => self.table(),
+        }
+    }
+
+    fn table(&self) -> String {
+        match self {
+            Report::User(user) => table(&UserRow::HEADERS, vec![user.cells()]),
+            Report::Users { users, next_cursor } => {
+                let mut output = table(
+                    &UserRow::HEADERS,
+                    users.iter().map(UserRow::cells).collect(),
+                );
+                if let Some(cursor) = next_cursor {
+                    output.push_str(&format!(
+                        "\nMore users follow, list them with --after {cursor}\n"
+                    ));
+                }
+                output
+            }
+            Report::Migrations { migrations } => table(
+                &["VERSION", "DESCRIPTION", "APPLIED"],
+                migrations
+                    .iter()
+                    .map(|migration| {
+                        vec![
+                            migration.version.to_string(),
+                            migration.description.clone(),
+                            yes_no(migration.applied),
+                        ]
+                    })
+                    .collect(),
+            ),
+            Report::Settings(settings) => {
+                let mut rows = Vec::new();
+                flatten("", settings, &mut rows);
+                table(&["SETTING", "VALUE"], rows)
+            }
+            Report::Done { message } => format!("{message}\n"),
+        }
+    }
+}
+
+/// Lays the rows out under the headers, each column as wide as its widest cell.
+fn table(headers: &[&str], rows: Vec<Vec<String>>) -> String {
+    let mut widths: Vec<usize> = headers
+        .iter()
+        .map(|header| header.chars().count())
+        .collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let line = |cells: Vec<&str>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect();
+        format!("{}\n", padded.join("  ").trim_end())
+    };
+
+    let mut output = line(headers.to_vec());
+    for row in &rows {
+        output.push_str(&line(row.iter().map(String::as_str).collect()));
+    }
+    output
+}
+
+/// Turns nested settings into `section.key` rows.
+fn flatten(prefix: &str, value: &Value, rows: &mut Vec<Vec<String>>) {
+    match value {
+        Value::Object(fields) => {
+            for (key, value) in fields {
+                let key = match prefix.is_empty() {
+                    true => key.clone(),
+                    false => format!("{prefix}.{key}"),
+                };
+                flatten(&key, value, rows);
+            }
+        }
+        Value::String(value) => rows.push(vec![prefix.to_owned(), value.clone()]),
+        Value::Null => rows.push(vec![prefix.to_owned(), "-".to_owned()]),
+        value => rows.push(vec![prefix.to_owned(), value.to_string()]),
+    }
+}
+
+fn timestamp(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn yes_no(value: bool) -> String {
+    match value {
+        true => "yes".to_owned(),
+        false => "no".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn table_aligns_columns() {
+        let report = Report::Migrations {
+            migrations: vec![
+                MigrationRow {
+                    version: 20250816091248,
+                    description: "create users table".to_owned(),
+                    applied: true,
+                },
+                MigrationRow {
+                    version: 20261022120000,
+                    description: "create revoked user tokens".to_owned(),
+                    applied: false,
+                },
+            ],
+        };
+
+        assert_eq!(
+            report.render(Format::Table),
+            "VERSION         DESCRIPTION                 APPLIED\n\
+             20250816091248  create users table          yes\n\
+             20261022120000  create revoked user tokens  no\n"
+        );
+    }
+
+    #[test]
+    fn settings_are_listed_by_key() {
+        let report = Report::Settings(json!({
+            "auth": { "jwt_secret": "[REDACTED]", "admin_api_token": null },
+            "application": { "allowed_origins": ["http://localhost:8000"] },
+        }));
+
+        assert_eq!(
+            report.render(Format::Table),
+            "SETTING                      VALUE\n\
+             application.allowed_origins  [\"http://localhost:8000\"]\n\
+             auth.admin_api_token         -\n\
+             auth.jwt_secret              [REDACTED]\n"
+        );
+    }
+
+    #[test]
+    fn json_output_is_the_report_itself() {
+        let report = Report::Done {
+            message: "Deleted user ops@example.com".to_owned(),
+        };
+
+        let json: Value = serde_json::from_str(&report.render(Format::Json)).unwrap();
+
+        assert_eq!(json, json!({ "message": "Deleted user ops@example.com" }));
+    }
+}
@@ -0,0 +1,172 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
+
+/// Append-only record of authentication events, kept for admins to review.
+#[async_trait::async_trait]
+pub trait AuditLog: Send + Sync {
+    async fn record(&self, event: AuditEvent) -> Result<()>;
+    /// Returns the events matching `query`, newest first.
+    async fn query(&self, query: &AuditQuery) -> Result<Vec<AuditRecord>>;
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    #[serde(rename = "occurredAt")]
+    pub occurred_at: DateTime<Utc>,
+    pub kind: AuditEventKind,
+    pub outcome: AuditOutcome,
+    /// Email of the account the request acted on, when known.
+    pub actor: Option<String>,
+    /// Id of the same account, when the request got as far as finding it.
+    #[serde(rename = "userId")]
+    pub user_id: Option<String>,
+    pub ip: Option<String>,
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    /// Why the request failed or was challenged.
+    pub reason: Option<String>,
+    #[serde(rename = "requestId")]
+    pub request_id: Option<String>,
+}
+
+/// An event as stored, with the id used to page through the log.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub id: i64,
+    #[serde(flatten)]
+    pub event: AuditEvent,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum AuditEventKind {
+    Signup,
+    Login,
+    Verify2FA,
+    Logout,
+    VerifyToken,
+}
+
+impl AuditEventKind {
+    pub fn parse(kind: &str) -> Result<Self, String> {
+        match kind {
+            "signup" => Ok(AuditEventKind::Signup),
+            "login" => Ok(AuditEventKind::Login),
+            "verify_2fa" => Ok(AuditEventKind::Verify2FA),
+            "logout" => Ok(AuditEventKind::Logout),
+            "verify_token" => Ok(AuditEventKind::VerifyToken),
+            _ => Err("Invalid audit event kind".to_owned()),
+        }
+    }
+}
+
+impl AsRef<str> for AuditEventKind {
+    fn as_ref(&self) -> &str {
+        match self {
+            AuditEventKind::Signup => "signup",
+            AuditEventKind::Login => "login",
+            AuditEventKind::Verify2FA => "verify_2fa",
+            AuditEventKind::Logout => "logout",
+            AuditEventKind::VerifyToken => "verify_token",
+        }
+    }
+}
+
+impl TryFrom<String> for AuditEventKind {
+    type Error = String;
+
+    fn try_from(kind: String) -> Result<Self, Self::Error> {
+        Self::parse(&kind)
+    }
+}
+
+impl From<AuditEventKind> for String {
+    fn from(kind: AuditEventKind) -> Self {
+        kind.as_ref().to_owned()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum AuditOutcome {
+    Success,
+    /// The first factor was accepted and a 2FA code was sent.
+    TwoFARequired,
+    Failure,
+}
+
+impl AuditOutcome {
+    pub fn parse(outcome: &str) -> Result<Self, String> {
+        match outcome {
+            "success" => Ok(AuditOutcome::Success),
+            "2fa_required" => Ok(AuditOutcome::TwoFARequired),
+            "failure" => Ok(AuditOutcome::Failure),
+            _ => Err("Invalid audit outcome".to_owned()),
+        }
+    }
+}
+
+impl AsRef<str> for AuditOutcome {
+    fn as_ref(&self) -> &str {
+        match self {
+            AuditOutcome::Success => "success",
+            AuditOutcome::TwoFARequired => "2fa_required",
+            AuditOutcome::Failure => "failure",
+        }
+    }
+}
+
+impl TryFrom<String> for AuditOutcome {
+    type Error = String;
+
+    fn try_from(outcome: String) -> Result<Self, Self::Error> {
+        Self::parse(&outcome)
+    }
+}
+
+impl From<AuditOutcome> for String {
+    fn from(outcome: AuditOutcome) -> Self {
+        outcome.as_ref().to_owned()
+    }
+}
+
+/// Filters for `AuditLog::query`. Unset fields match every event.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuditQuery {
+    pub actor: Option<String>,
+    pub kind: Option<AuditEventKind>,
+    pub outcome: Option<AuditOutcome>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    /// Only events with a smaller id, i.e. the page after the one ending at this id.
+    pub before: Option<i64>,
+    pub limit: u32,
+}
+
+impl AuditQuery {
+    /// A query for the newest `limit` events, with no filters.
+    pub fn with_limit(limit: u32) -> Self {
+        Self {
+            actor: None,
+            kind: None,
+            outcome: None,
+            since: None,
+            until: None,
+            before: None,
+            limit,
+        }
+    }
+
+    pub fn matches(&self, record: &AuditRecord) -> bool {
+        let event = &record.event;
+        self.actor
+            .as_ref()
+            .is_none_or(|actor| event.actor.as_ref() == Some(actor))
+            && self.kind.is_none_or(|kind| event.kind == kind)
+            && self.outcome.is_none_or(|outcome| event.outcome == outcome)
+            && self.since.is_none_or(|since| event.occurred_at >= since)
+            && self.until.is_none_or(|until| event.occurred_at < until)
+            && self.before.is_none_or(|before| record.id < before)
+    }
+}
@@ -1,19 +1,75 @@
+use chrono::{DateTime, Duration, Utc};
 use rand::Rng;
 use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
 use uuid::Uuid;
 
 use color_eyre::eyre::{Report, Result};
 use thiserror::Error;
 
-use crate::domain::{Email, Password};
+use crate::domain::{Email, Password, PhoneNumber};
 
-use super::User;
+use super::{
+    NewUser, StoredUser, TwoFAChannel, UserFilter, UserId, UserPage, UserQuery, UserStatus,
+    UserUpdate,
+};
 
 #[async_trait::async_trait]
 pub trait UserStore: Send + Sync {
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError>;
-    async fn get_user(&self, email: Email) -> Result<User, UserStoreError>;
-    async fn validate_user(&self, email: Email, password: Password) -> Result<(), UserStoreError>;
+    /// Hashes the user's password and stores the user, returning it as stored.
+    async fn add_user(&self, user: NewUser) -> Result<StoredUser, UserStoreError>;
+    async fn get_user(&self, email: Email) -> Result<StoredUser, UserStoreError>;
+    /// Like `get_user`, but may answer with a cached copy that misses recent writes. Only for
+    /// reads that decide neither credentials nor account status.
+    async fn get_cached_user(&self, email: Email) -> Result<StoredUser, UserStoreError> {
+        self.get_user(email).await
+    }
+    async fn get_user_by_id(&self, id: &UserId) -> Result<StoredUser, UserStoreError>;
+    /// Checks `password` against the stored hash and returns the user it belongs to.
+    async fn validate_user(
+        &self,
+        email: Email,
+        password: Password,
+    ) -> Result<StoredUser, UserStoreError>;
+    async fn update_requires_2fa(
+        &self,
+        email: Email,
+        requires_2fa: bool,
+    ) -> Result<(), UserStoreError>;
+    /// Replaces the user's phone number with an unverified one and switches 2FA delivery back to
+    /// email until it is verified.
+    async fn set_phone_number(
+        &self,
+        email: Email,
+        phone_number: PhoneNumber,
+    ) -> Result<(), UserStoreError>;
+    /// Marks the user's phone number as verified. Fails with `InvalidCredentials` if the stored
+    /// number is no longer `phone_number`.
+    async fn verify_phone_number(
+        &self,
+        email: Email,
+        phone_number: PhoneNumber,
+    ) -> Result<(), UserStoreError>;
+    async fn update_two_fa_channel(
+        &self,
+        email: Email,
+        two_fa_channel: TwoFAChannel,
+    ) -> Result<(), UserStoreError>;
+    async fn update_status(&self, email: Email, status: UserStatus) -> Result<(), UserStoreError>;
+    /// Sets the user's last login time to now.
+    async fn record_login(&self, email: Email) -> Result<(), UserStoreError>;
+    /// Applies `update`, hashing any new password, and returns the updated user. Fails with
+    /// `UserAlreadyExists` if the new email belongs to another user.
+    async fn update_user(
+        &self,
+        id: &UserId,
+        update: UserUpdate,
+    ) -> Result<StoredUser, UserStoreError>;
+    async fn delete_user(&self, id: &UserId) -> Result<(), UserStoreError>;
+    async fn count_users(&self, filter: &UserFilter) -> Result<u64, UserStoreError>;
+    /// Returns a page of the users matching `query`, oldest first.
+    async fn list_users(&self, query: &UserQuery) -> Result<UserPage, UserStoreError>;
 }
 
 #[derive(Debug, Error)]
@@ -40,6 +96,19 @@ impl PartialEq for UserStoreError {
     }
 }
 
+/// Short-lived copies of user records, looked up by email, kept in front of a `UserStore`.
+#[async_trait::async_trait]
+pub trait UserCache: Send + Sync {
+    /// Returns the cached user, or `None` if it is missing or has expired.
+    async fn get(&self, email: &Email) -> Result<Option<StoredUser>>;
+    /// Read before looking the user up in the store, and passed on to `insert`.
+    async fn version(&self, email: &Email) -> Result<u64>;
+    /// Caches the user, unless it was invalidated after `version` was read, so a lookup racing
+    /// a write cannot put the old record back.
+    async fn insert(&self, user: &StoredUser, version: u64) -> Result<()>;
+    async fn invalidate(&self, email: &Email) -> Result<()>;
+}
+
 #[derive(Debug, Error)]
 pub enum BannedTokenStoreError {
     #[error("Unexpected error")]
@@ -48,23 +117,69 @@ pub enum BannedTokenStoreError {
 
 #[async_trait::async_trait]
 pub trait BannedTokenStore: Send + Sync {
-    async fn add_token(&mut self, token: Secret<String>) -> Result<()>;
+    async fn add_token(&self, token: Secret<String>) -> Result<()>;
     async fn contains_token(&self, token: Secret<String>) -> Result<bool>;
+    /// Bans every token issued to the user up to `issued_before`, ending all their sessions. An
+    /// earlier cutoff never replaces a later one.
+    async fn revoke_user_tokens(
+        &self,
+        user_id: &UserId,
+        issued_before: DateTime<Utc>,
+    ) -> Result<()>;
+    /// The cutoff of the user's last revocation, if one can still affect an unexpired token.
+    async fn user_tokens_revoked_before(&self, user_id: &UserId) -> Result<Option<DateTime<Utc>>>;
 }
 
 #[async_trait::async_trait]
 pub trait TwoFACodeStore: Send + Sync {
+    /// Stores a pending code for a login attempt, evicting the account's oldest pending attempts
+    /// beyond the configured limit.
     async fn add_code(
-        &mut self,
+        &self,
         email: Email,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
+        purpose: TwoFAPurpose,
+    ) -> Result<(), TwoFACodeStoreError>;
+    /// Removes the pending code. Fails with `LoginAttemptIdNotFound` if it is already gone, so
+    /// only one of several concurrent callers gets to consume a code.
+    async fn remove_code(
+        &self,
+        login_attempt_id: &LoginAttemptId,
     ) -> Result<(), TwoFACodeStoreError>;
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError>;
     async fn get_code(
         &self,
-        email: &Email,
-    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError>;
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(Email, TwoFACode), TwoFACodeStoreError>;
+    async fn get_purpose(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<TwoFAPurpose, TwoFACodeStoreError>;
+    /// Counts an attempt at the pending code and returns the number of attempts so far. Callers
+    /// count an attempt before comparing the code, so concurrent guesses cannot exceed the limit.
+    async fn record_attempt(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<u32, TwoFACodeStoreError>;
+    /// Records a newly issued code and returns how many codes were issued to the account in the current hour.
+    async fn record_code_issued(&self, email: &Email) -> Result<u32, TwoFACodeStoreError>;
+    /// Records that the pending code is sent again, keeping its login attempt id, unless it was
+    /// sent less than `cooldown` ago or already resent `max_resends` times. The check and the
+    /// update are atomic, so concurrent requests cannot resend it more than allowed.
+    async fn record_resend(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+        cooldown: Duration,
+        max_resends: u32,
+    ) -> Result<TwoFAResend, TwoFACodeStoreError>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TwoFAResend {
+    Recorded,
+    /// The code was sent too recently; it can be resent after this many seconds.
+    RetryAfter(i64),
+    LimitReached,
 }
 
 #[derive(Debug, Error)]
@@ -85,7 +200,185 @@ impl PartialEq for TwoFACodeStoreError {
     }
 }
 
+#[async_trait::async_trait]
+pub trait TrustedDeviceStore: Send + Sync {
+    async fn add_device(
+        &self,
+        email: Email,
+        device: TrustedDevice,
+    ) -> Result<(), TrustedDeviceStoreError>;
+    /// Returns the account's trusted devices that have not expired yet.
+    async fn get_devices(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<TrustedDevice>, TrustedDeviceStoreError>;
+    async fn contains_device(
+        &self,
+        email: &Email,
+        device_id: &TrustedDeviceId,
+    ) -> Result<bool, TrustedDeviceStoreError>;
+    async fn remove_device(
+        &self,
+        email: &Email,
+        device_id: &TrustedDeviceId,
+    ) -> Result<(), TrustedDeviceStoreError>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrustedDevice {
+    pub id: TrustedDeviceId,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl TrustedDevice {
+    pub fn new(user_agent: Option<String>, ttl: chrono::Duration) -> Self {
+        let created_at = Utc::now();
+        Self {
+            id: TrustedDeviceId::default(),
+            user_agent,
+            created_at,
+            expires_at: created_at + ttl,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Utc::now()
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum TrustedDeviceStoreError {
+    #[error("Trusted device not found")]
+    DeviceNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for TrustedDeviceStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::DeviceNotFound, Self::DeviceNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TrustedDeviceId(String);
+
+impl TrustedDeviceId {
+    pub fn parse(id: String) -> Result<Self, String> {
+        match Uuid::parse_str(&id) {
+            Ok(uuid) => Ok(Self(uuid.to_string())),
+            Err(_) => Err("Invalid id".to_owned()),
+        }
+    }
+}
+
+impl Default for TrustedDeviceId {
+    fn default() -> Self {
+        Self(Uuid::new_v4().to_string())
+    }
+}
+
+impl AsRef<str> for TrustedDeviceId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[async_trait::async_trait]
+pub trait MagicLinkStore: Send + Sync {
+    /// Stores the link under a hash of its token, so a leaked store does not leak usable links.
+    async fn add_link(
+        &self,
+        token: &MagicLinkToken,
+        link: MagicLink,
+    ) -> Result<(), MagicLinkStoreError>;
+    /// Removes and returns the link, so that every token can be used at most once.
+    async fn take_link(&self, token: &MagicLinkToken) -> Result<MagicLink, MagicLinkStoreError>;
+}
+
 #[derive(Debug, Clone, PartialEq)]
+pub struct MagicLink {
+    pub email: Email,
+    /// Hash of the nonce cookie set on the browser that requested the link.
+    pub nonce_hash: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl MagicLink {
+    pub fn new(email: Email, nonce: &MagicLinkToken, ttl: chrono::Duration) -> Self {
+        Self {
+            email,
+            nonce_hash: nonce.hash(),
+            expires_at: Utc::now() + ttl,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Utc::now()
+    }
+
+    pub fn matches_nonce(&self, nonce: &MagicLinkToken) -> bool {
+        self.nonce_hash
+            .as_bytes()
+            .ct_eq(nonce.hash().as_bytes())
+            .into()
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum MagicLinkStoreError {
+    #[error("Magic link not found")]
+    LinkNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for MagicLinkStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::LinkNotFound, Self::LinkNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+/// A random 256-bit secret, used both for the link token and for the nonce binding it to a browser.
+#[derive(Clone, Debug)]
+pub struct MagicLinkToken(Secret<String>);
+
+impl MagicLinkToken {
+    pub fn parse(token: String) -> Result<Self, String> {
+        match token.len() == 64 && token.chars().all(|c| c.is_ascii_hexdigit()) {
+            true => Ok(Self(Secret::new(token.to_ascii_lowercase()))),
+            false => Err("Invalid token".to_owned()),
+        }
+    }
+
+    pub fn hash(&self) -> String {
+        hex::encode(Sha256::digest(self.0.expose_secret().as_bytes()))
+    }
+}
+
+impl Default for MagicLinkToken {
+    fn default() -> Self {
+        Self(Secret::new(hex::encode(rand::rng().random::<[u8; 32]>())))
+    }
+}
+
+impl AsRef<str> for MagicLinkToken {
+    fn as_ref(&self) -> &str {
+        self.0.expose_secret()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct LoginAttemptId(String);
 
 impl LoginAttemptId {
@@ -114,7 +407,11 @@ pub struct TwoFACode(Secret<String>);
 
 impl PartialEq for TwoFACode {
     fn eq(&self, other: &Self) -> bool {
-        self.0.expose_secret() == other.0.expose_secret()
+        self.0
+            .expose_secret()
+            .as_bytes()
+            .ct_eq(other.0.expose_secret().as_bytes())
+            .into()
     }
 }
 
@@ -129,7 +426,10 @@ impl TwoFACode {
 
 impl Default for TwoFACode {
     fn default() -> Self {
-        Self(Secret::new(format!("{:06}", rand::rng().random_range(0..=999_999))))
+        Self(Secret::new(format!(
+            "{:06}",
+            rand::rng().random_range(0..=999_999)
+        )))
     }
 }
 
@@ -138,3 +438,45 @@ impl AsRef<str> for TwoFACode {
         self.0.expose_secret()
     }
 }
+
+/// What a 2FA code was issued for. Codes are only accepted for their purpose, so a code sent to
+/// complete a login cannot also turn 2FA off.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum TwoFAPurpose {
+    Login,
+    /// Turning 2FA on or off, with a code from `/request-2fa-code`.
+    Update2FA,
+    /// Confirming the phone number a code from `/phone-number` was texted to.
+    VerifyPhoneNumber(PhoneNumber),
+}
+
+impl TwoFAPurpose {
+    /// Rebuilds a stored purpose from its name and, when it verifies one, the phone number.
+    pub fn parse(purpose: &str, phone_number: Option<&str>) -> Result<Self, String> {
+        match (purpose, phone_number) {
+            ("login", _) => Ok(TwoFAPurpose::Login),
+            ("update_2fa", _) => Ok(TwoFAPurpose::Update2FA),
+            ("verify_phone_number", Some(phone_number)) => Ok(TwoFAPurpose::VerifyPhoneNumber(
+                PhoneNumber::parse(phone_number)?,
+            )),
+            _ => Err("Invalid 2FA code purpose".to_owned()),
+        }
+    }
+
+    pub fn phone_number(&self) -> Option<&PhoneNumber> {
+        match self {
+            TwoFAPurpose::VerifyPhoneNumber(phone_number) => Some(phone_number),
+            _ => None,
+        }
+    }
+}
+
+impl AsRef<str> for TwoFAPurpose {
+    fn as_ref(&self) -> &str {
+        match self {
+            TwoFAPurpose::Login => "login",
+            TwoFAPurpose::Update2FA => "update_2fa",
+            TwoFAPurpose::VerifyPhoneNumber(_) => "verify_phone_number",
+        }
+    }
+}
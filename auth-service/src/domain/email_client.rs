@@ -4,10 +4,5 @@ use super::Email;
 
 #[async_trait::async_trait]
 pub trait EmailClient: Send + Sync {
-    async fn send_email(
-        &self,
-        recipient: &Email,
-        subject: &str,
-        content: &str,
-    ) -> Result<()>;
+    async fn send_email(&self, recipient: &Email, subject: &str, content: &str) -> Result<()>;
 }
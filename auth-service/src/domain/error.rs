@@ -9,10 +9,22 @@ pub enum AuthAPIError {
     InvalidCredentials,
     #[error("Incorrect credentials")]
     IncorrectCredentials,
+    #[error("Account disabled")]
+    AccountDisabled,
     #[error("Missing token")]
     MissingToken,
     #[error("Invalid token")]
     InvalidToken,
+    #[error("Too many requests")]
+    TooManyRequests,
+    #[error("Phone number not verified")]
+    PhoneNumberNotVerified,
+    #[error("SMS is not enabled")]
+    SmsNotEnabled,
+    #[error("Trusted device not found")]
+    TrustedDeviceNotFound,
+    #[error("Resend cooldown active")]
+    ResendCooldown(i64),
     #[error("Unexpected error")]
     UnexpectedError(#[source] Report),
 }
@@ -0,0 +1,20 @@
+use std::{sync::Arc, time::Duration};
+
+use color_eyre::eyre::Result;
+
+#[async_trait::async_trait]
+pub trait HealthCheck: Send + Sync {
+    /// Succeeds if the dependency can currently be reached.
+    async fn check(&self) -> Result<()>;
+}
+
+/// A dependency reported on by the readiness endpoint.
+#[derive(Clone)]
+pub struct Dependency {
+    pub name: &'static str,
+    /// Whether the service is not ready while this dependency is down.
+    pub critical: bool,
+    /// How long the check may take before the dependency is reported as down.
+    pub timeout: Duration,
+    pub check: Arc<dyn HealthCheck>,
+}
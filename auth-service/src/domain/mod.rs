@@ -1,13 +1,21 @@
+pub mod audit_log;
 pub(crate) mod data_stores;
 pub mod email;
 pub mod email_client;
 mod error;
+pub mod health_check;
 pub mod password;
+pub mod phone_number;
+pub mod sms_client;
 mod user;
 
+pub use audit_log::*;
 pub use data_stores::*;
 pub use email::*;
 pub use email_client::*;
 pub use error::*;
+pub use health_check::*;
 pub use password::*;
+pub use phone_number::*;
+pub use sms_client::*;
 pub use user::*;
@@ -30,6 +30,28 @@ impl AsRef<Secret<String>> for Password {
     }
 }
 
+/// A password hash in PHC string format, as produced by `PasswordHasher`.
+#[derive(Debug, Clone)]
+pub struct PasswordHash(Secret<String>);
+
+impl PartialEq for PasswordHash {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl PasswordHash {
+    pub fn new(hash: Secret<String>) -> Self {
+        Self(hash)
+    }
+}
+
+impl AsRef<Secret<String>> for PasswordHash {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
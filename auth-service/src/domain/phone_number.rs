@@ -0,0 +1,44 @@
+/// A phone number in E.164 format, e.g. `+40712345678`.
+#[derive(Debug, PartialEq, Clone, Eq, Hash)]
+pub struct PhoneNumber(String);
+
+impl PhoneNumber {
+    pub fn parse(phone_number: &str) -> Result<Self, String> {
+        let digits = phone_number.strip_prefix('+').unwrap_or_default();
+        let is_valid = (8..=15).contains(&digits.len())
+            && !digits.starts_with('0')
+            && digits.chars().all(|c| c.is_ascii_digit());
+
+        match is_valid {
+            true => Ok(PhoneNumber(phone_number.to_owned())),
+            false => Err("Invalid phone number".to_owned()),
+        }
+    }
+}
+
+impl AsRef<str> for PhoneNumber {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::phone_number::PhoneNumber;
+
+    #[test]
+    fn parse_valid_phone_number() {
+        assert_eq!(
+            "+40712345678",
+            PhoneNumber::parse("+40712345678").unwrap().as_ref()
+        )
+    }
+
+    #[test]
+    fn parse_invalid_phone_number() {
+        assert!(PhoneNumber::parse("0712345678").is_err());
+        assert!(PhoneNumber::parse("+0712345678").is_err());
+        assert!(PhoneNumber::parse("+40 712 345 678").is_err());
+        assert!(PhoneNumber::parse("+1234").is_err());
+    }
+}
@@ -0,0 +1,8 @@
+use color_eyre::eyre::Result;
+
+use super::PhoneNumber;
+
+#[async_trait::async_trait]
+pub trait SmsClient: Send + Sync {
+    async fn send_sms(&self, recipient: &PhoneNumber, content: &str) -> Result<()>;
+}
@@ -1,13 +1,18 @@
+use std::fmt;
+
+use chrono::{DateTime, SecondsFormat, SubsecRound, Utc};
 use getset::Getters;
 use secrecy::Secret;
+use uuid::Uuid;
 
 use crate::{
-    domain::{Email, Password, UserStoreError},
+    domain::{Email, Password, PasswordHash, PhoneNumber},
     services::PgUser,
 };
 
+/// A user as submitted at signup, before its password has been hashed.
 #[derive(Clone, Getters, PartialEq, Debug)]
-pub struct User {
+pub struct NewUser {
     #[get = "pub"]
     pub email: Email,
     #[get = "pub"]
@@ -16,29 +21,307 @@ pub struct User {
     pub requires_2fa: bool,
 }
 
-impl User {
+impl NewUser {
     pub fn new(email: Email, password: Password, requires_2fa: bool) -> Self {
-        User {
+        NewUser {
             email,
             password,
             requires_2fa,
         }
     }
+}
+
+/// A user as kept by a `UserStore`. Only the hash of the password is ever stored.
+#[derive(Clone, Getters, PartialEq, Debug)]
+pub struct StoredUser {
+    #[get = "pub"]
+    pub id: UserId,
+    #[get = "pub"]
+    pub email: Email,
+    #[get = "pub"]
+    pub password_hash: PasswordHash,
+    #[get = "pub"]
+    pub requires_2fa: bool,
+    #[get = "pub"]
+    pub phone_number: Option<PhoneNumber>,
+    #[get = "pub"]
+    pub phone_verified: bool,
+    #[get = "pub"]
+    pub two_fa_channel: TwoFAChannel,
+    #[get = "pub"]
+    pub status: UserStatus,
+    #[get = "pub"]
+    pub created_at: DateTime<Utc>,
+    #[get = "pub"]
+    pub updated_at: DateTime<Utc>,
+    #[get = "pub"]
+    pub last_login_at: Option<DateTime<Utc>>,
+}
+
+impl StoredUser {
+    /// Assigns a fresh id and timestamps to a user about to be inserted.
+    pub fn new(user: NewUser, password_hash: PasswordHash) -> Self {
+        // Microseconds is all the databases keep, so a user reads back exactly as it was created.
+        let now = Utc::now().trunc_subsecs(6);
+        StoredUser {
+            id: UserId::default(),
+            email: user.email,
+            password_hash,
+            requires_2fa: user.requires_2fa,
+            phone_number: None,
+            phone_verified: false,
+            two_fa_channel: TwoFAChannel::default(),
+            status: UserStatus::default(),
+            created_at: now,
+            updated_at: now,
+            last_login_at: None,
+        }
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        self.status == UserStatus::Disabled
+    }
+
+    /// The phone number 2FA codes should be sent to, if the user prefers SMS and has verified one.
+    pub fn sms_recipient(&self) -> Option<&PhoneNumber> {
+        match (self.two_fa_channel, self.phone_verified) {
+            (TwoFAChannel::Sms, true) => self.phone_number.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+/// Changes to apply with `UserStore::update_user`. Unset fields are left as they are.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct UserUpdate {
+    pub email: Option<Email>,
+    pub password: Option<Password>,
+    pub requires_2fa: Option<bool>,
+    pub status: Option<UserStatus>,
+}
+
+/// Filters for listing and counting users. Unset fields match every user.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct UserFilter {
+    pub email_prefix: Option<String>,
+    pub requires_2fa: Option<bool>,
+    pub status: Option<UserStatus>,
+    pub created_since: Option<DateTime<Utc>>,
+    pub created_until: Option<DateTime<Utc>>,
+}
+
+impl UserFilter {
+    pub fn matches(&self, user: &StoredUser) -> bool {
+        self.email_prefix
+            .as_ref()
+            .is_none_or(|prefix| user.email.as_ref().starts_with(prefix.as_str()))
+            && self
+                .requires_2fa
+                .is_none_or(|requires_2fa| user.requires_2fa == requires_2fa)
+            && self.status.is_none_or(|status| user.status == status)
+            && self
+                .created_since
+                .is_none_or(|since| user.created_at >= since)
+            && self
+                .created_until
+                .is_none_or(|until| user.created_at < until)
+    }
+}
+
+/// A page request for `UserStore::list_users`. Users are listed oldest first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UserQuery {
+    pub filter: UserFilter,
+    /// Only users after this position, i.e. the page following the one that returned it.
+    pub after: Option<UserCursor>,
+    pub limit: u32,
+}
+
+impl UserQuery {
+    /// A query for the first `limit` users, with no filters.
+    pub fn with_limit(limit: u32) -> Self {
+        Self {
+            filter: UserFilter::default(),
+            after: None,
+            limit,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct UserPage {
+    pub users: Vec<StoredUser>,
+    /// Where the next page starts, if there are more users.
+    pub next_cursor: Option<UserCursor>,
+}
+
+impl UserPage {
+    /// Builds a page from up to `limit + 1` users in listing order. The extra user, if present,
+    /// only signals that another page follows.
+    pub fn from_rows(mut users: Vec<StoredUser>, limit: u32) -> Self {
+        let next_cursor = match users.len() > limit as usize {
+            true => {
+                users.truncate(limit as usize);
+                users.last().map(UserCursor::from)
+            }
+            false => None,
+        };
+
+        Self { users, next_cursor }
+    }
+}
+
+/// Position of a user in a listing, which is ordered by creation time and then id.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct UserCursor {
+    pub created_at: DateTime<Utc>,
+    pub id: UserId,
+}
+
+impl UserCursor {
+    pub fn parse(cursor: &str) -> Result<Self, String> {
+        let invalid = || "Invalid user cursor".to_owned();
+        let (created_at, id) = cursor.split_once('_').ok_or_else(invalid)?;
+
+        Ok(Self {
+            created_at: DateTime::parse_from_rfc3339(created_at)
+                .map_err(|_| invalid())?
+                .with_timezone(&Utc),
+            id: UserId::parse(id).map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl From<&StoredUser> for UserCursor {
+    fn from(user: &StoredUser) -> Self {
+        Self {
+            created_at: user.created_at,
+            id: user.id,
+        }
+    }
+}
+
+impl fmt::Display for UserCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}_{}",
+            self.created_at.to_rfc3339_opts(SecondsFormat::Micros, true),
+            self.id
+        )
+    }
+}
+
+/// Stable identifier of a user, used as the `sub` of auth tokens so they survive email changes.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct UserId(Uuid);
+
+impl UserId {
+    pub fn parse(id: &str) -> Result<Self, String> {
+        Uuid::parse_str(id)
+            .map(Self)
+            .map_err(|_| "Invalid user id".to_owned())
+    }
+}
+
+impl Default for UserId {
+    fn default() -> Self {
+        Self(Uuid::new_v4())
+    }
+}
+
+impl From<Uuid> for UserId {
+    fn from(id: Uuid) -> Self {
+        Self(id)
+    }
+}
+
+impl AsRef<Uuid> for UserId {
+    fn as_ref(&self) -> &Uuid {
+        &self.0
+    }
+}
+
+impl fmt::Display for UserId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Whether a user may sign in. Disabled accounts are refused at login and their tokens stop
+/// validating.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum UserStatus {
+    #[default]
+    Active,
+    Disabled,
+    PendingVerification,
+}
+
+impl UserStatus {
+    pub fn parse(status: &str) -> Result<Self, String> {
+        match status {
+            "active" => Ok(UserStatus::Active),
+            "disabled" => Ok(UserStatus::Disabled),
+            "pending_verification" => Ok(UserStatus::PendingVerification),
+            _ => Err("Invalid user status".to_owned()),
+        }
+    }
+}
+
+impl AsRef<str> for UserStatus {
+    fn as_ref(&self) -> &str {
+        match self {
+            UserStatus::Active => "active",
+            UserStatus::Disabled => "disabled",
+            UserStatus::PendingVerification => "pending_verification",
+        }
+    }
+}
+
+/// How a user prefers to receive 2FA codes.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum TwoFAChannel {
+    #[default]
+    Email,
+    Sms,
+}
+
+impl TwoFAChannel {
+    pub fn parse(channel: &str) -> Result<Self, String> {
+        match channel {
+            "email" => Ok(TwoFAChannel::Email),
+            "sms" => Ok(TwoFAChannel::Sms),
+            _ => Err("Invalid 2FA channel".to_owned()),
+        }
+    }
+}
 
-    pub fn validate_password(&self, password: Password) -> Result<(), UserStoreError> {
-        match self.password == password {
-            true => Ok(()),
-            false => Err(UserStoreError::InvalidCredentials),
+impl AsRef<str> for TwoFAChannel {
+    fn as_ref(&self) -> &str {
+        match self {
+            TwoFAChannel::Email => "email",
+            TwoFAChannel::Sms => "sms",
         }
     }
 }
 
-impl From<PgUser> for User {
+impl From<PgUser> for StoredUser {
     fn from(pg_user: PgUser) -> Self {
-        User {
+        StoredUser {
+            id: UserId::from(pg_user.id),
             email: Email::parse(&pg_user.email).unwrap(),
-            password: Password::parse(Secret::new(pg_user.password_hash)).unwrap(),
+            password_hash: PasswordHash::new(Secret::new(pg_user.password_hash)),
             requires_2fa: pg_user.requires_2fa,
+            phone_number: pg_user
+                .phone_number
+                .map(|phone_number| PhoneNumber::parse(&phone_number).unwrap()),
+            phone_verified: pg_user.phone_verified,
+            two_fa_channel: TwoFAChannel::parse(&pg_user.two_fa_channel).unwrap(),
+            status: UserStatus::parse(&pg_user.status).unwrap(),
+            created_at: pg_user.created_at,
+            updated_at: pg_user.updated_at,
+            last_login_at: pg_user.last_login_at,
         }
     }
 }
@@ -1,29 +1,68 @@
-use std::error::Error;
+use std::{
+    error::Error,
+    future::{Future, IntoFuture},
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
 use axum::{
-    http::Method,
-    http::StatusCode,
+    extract::{connect_info::IntoMakeServiceWithConnectInfo, ConnectInfo},
+    http::{header, HeaderName, HeaderValue, Method, StatusCode},
+    middleware::{self, AddExtension},
     response::{IntoResponse, Response},
-    routing::post,
+    routing::{get, post},
     serve::Serve,
     Json, Router,
 };
+use axum_server::tls_rustls::RustlsAcceptor;
 use domain::AuthAPIError;
+use metrics_exporter_prometheus::PrometheusHandle;
 use redis::{Client, RedisResult};
 use serde::{Deserialize, Serialize};
-use sqlx::{postgres::PgPoolOptions, PgPool};
-use tower_http::{cors::CorsLayer, services::ServeDir, trace::TraceLayer};
-
-use crate::{
-    app_state::AppState,
-    utils::constants::{DATABASE_URL, REDIS_HOST_NAME},
+use sqlx::{
+    migrate::Migrator,
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+    PgPool, SqlitePool,
+};
+use tokio::task::JoinHandle;
+use tower_http::{
+    cors::CorsLayer,
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    services::ServeDir,
+    trace::TraceLayer,
 };
+
 use crate::{
-    domain::Email,
-    routes::{login, logout, signup, verify_2fa, verify_token},
-    services::PostmarkEmailClient,
+    app_state::{AppState, UserStoreType},
+    domain::{Email, PhoneNumber, UserCache, UserStore},
+    routes::{
+        audit_events, disable_2fa, enable_2fa, health_live, health_ready, login, logout,
+        render_metrics, request_2fa_code, request_magic_link, resend_2fa, revoke_trusted_device,
+        set_phone_number, signup, trusted_devices, update_2fa_channel, verify_2fa,
+        verify_magic_link, verify_phone_number, verify_token,
+    },
+    services::{
+        connect_postgres_pool, CachedUserStore, InMemoryUserCache, PostgresBannedTokenStore,
+        PostgresMagicLinkStore, PostgresPoolConfig, PostgresTrustedDeviceStore,
+        PostgresTwoFACodeStore, PostmarkEmailClient, RedisPool, RedisUserCache, TwilioSmsClient,
+    },
     utils::{
-        constants::{prod, POSTMARK_AUTH_TOKEN},
-        tracing::{make_span_with_request_id, on_request, on_response},
+        constants::{UserCacheBackend, REQUEST_ID_HEADER},
+        metrics::track_http_metrics,
+        settings::{
+            DatabaseSettings, EmailClientSettings, RedisSettings, SmsClientSettings,
+            UserCacheSettings,
+        },
+        shutdown::ShutdownHandle,
+        tls::{redirect_to_https, spawn_certificate_reload, Certificates},
+        tracing::{
+            current_request_id, make_span_with_request_id, on_request, on_response,
+            scope_request_id,
+        },
     },
 };
 
@@ -33,85 +72,312 @@ pub mod routes;
 pub mod services;
 pub mod utils;
 
+type AppService = IntoMakeServiceWithConnectInfo<Router, SocketAddr>;
+
+enum AppServer {
+    Http(Serve<AppService, AddExtension<Router, ConnectInfo<SocketAddr>>>),
+    Https(Box<HttpsServer>),
+}
+
+struct HttpsServer {
+    server: axum_server::Server<RustlsAcceptor>,
+    service: AppService,
+    certificates: Certificates,
+    reload_interval: Duration,
+    redirect: Option<Serve<Router, Router>>,
+}
+
+impl HttpsServer {
+    /// Serves over HTTPS, reloading the certificate and redirecting plain HTTP until `shutdown`
+    /// is triggered and `grace` has passed, then drains the connections.
+    async fn serve(self, shutdown: ShutdownHandle, grace: Duration) -> Result<(), io::Error> {
+        spawn_certificate_reload(self.certificates, self.reload_interval, shutdown.clone());
+        if let Some(redirect) = self.redirect {
+            let shutdown = shutdown.clone();
+            tokio::spawn(
+                redirect
+                    .with_graceful_shutdown(async move { stop_listening(&shutdown, grace).await })
+                    .into_future(),
+            );
+        }
+
+        let handle = axum_server::Handle::new();
+        tokio::spawn({
+            let handle = handle.clone();
+            async move {
+                stop_listening(&shutdown, grace).await;
+                handle.graceful_shutdown(None);
+            }
+        });
+
+        self.server.handle(handle).serve(self.service).await
+    }
+}
+
 pub struct Application {
-    server: Serve<Router, Router>,
+    server: AppServer,
     pub address: String,
+    /// Address of the listener redirecting plain HTTP to HTTPS, when there is one.
+    pub redirect_address: Option<String>,
+    shutdown: ShutdownHandle,
+    shutdown_grace: Duration,
+    shutdown_deadline: Duration,
 }
 
 impl Application {
-    pub async fn build(app_state: AppState, address: &str) -> Result<Self, Box<dyn Error>> {
-        let allowed_origins = [
-            "http://localhost:8000".parse()?,
-            "http://161.35.120.222:8000".parse()?,
-        ];
+    /// Builds the app from `app_state`, listening on and serving the routes enabled by its
+    /// settings, over HTTPS when TLS is enabled.
+    pub async fn build(app_state: AppState) -> Result<Self, Box<dyn Error>> {
+        let settings = app_state.settings.clone();
+        let shutdown = app_state.shutdown.clone();
+        let allowed_origins = settings
+            .application
+            .allowed_origins
+            .iter()
+            .map(|origin| origin.parse())
+            .collect::<Result<Vec<HeaderValue>, _>>()?;
 
         let cors = CorsLayer::new()
             .allow_methods([Method::GET, Method::POST])
             .allow_credentials(true)
-            .allow_origin(allowed_origins);
+            .allow_origin(allowed_origins)
+            .expose_headers([HeaderName::from_static(REQUEST_ID_HEADER)]);
 
-        let router = Router::new()
+        let mut router = Router::new()
             .nest_service("/", ServeDir::new("assets"))
             .route("/signup", post(signup))
             .route("/login", post(login))
             .route("/verify-2fa", post(verify_2fa))
+            .route("/resend-2fa", post(resend_2fa))
+            .route("/request-2fa-code", post(request_2fa_code))
+            .route("/enable-2fa", post(enable_2fa))
+            .route("/disable-2fa", post(disable_2fa))
+            .route("/trusted-devices", get(trusted_devices))
+            .route("/revoke-trusted-device", post(revoke_trusted_device))
             .route("/logout", post(logout))
             .route("/verify-token", post(verify_token))
+            .route("/health/live", get(health_live))
+            .route("/health/ready", get(health_ready));
+
+        if settings.sms_client.enabled {
+            router = router
+                .route("/phone-number", post(set_phone_number))
+                .route("/verify-phone-number", post(verify_phone_number))
+                .route("/2fa-channel", post(update_2fa_channel));
+        }
+
+        if settings.magic_link.enabled {
+            router = router
+                .route("/magic-link", post(request_magic_link))
+                .route("/verify-magic-link", post(verify_magic_link));
+        }
+
+        if settings.auth.admin_api_token.is_some() {
+            router = router.route("/admin/audit-events", get(audit_events));
+        }
+
+        let router = router
             .with_state(app_state)
+            .layer(middleware::from_fn(track_http_metrics))
+            .layer(middleware::from_fn(scope_request_id))
             .layer(cors)
             .layer(
                 TraceLayer::new_for_http()
                     .make_span_with(make_span_with_request_id)
                     .on_request(on_request)
                     .on_response(on_response),
-            );
+            )
+            .layer(PropagateRequestIdLayer::x_request_id())
+            .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid));
+
+        let listener = tokio::net::TcpListener::bind(&settings.application.address).await?;
+        let address = listener.local_addr()?;
+        let service = router.into_make_service_with_connect_info::<SocketAddr>();
+
+        let mut redirect_address = None;
+        let server = match settings.tls.enabled {
+            false => AppServer::Http(axum::serve(listener, service)),
+            true => {
+                let certificates = Certificates::load(&settings.tls).await?;
+                let redirect = match &settings.tls.redirect_address {
+                    Some(redirect) => {
+                        let listener = tokio::net::TcpListener::bind(redirect).await?;
+                        redirect_address = Some(listener.local_addr()?.to_string());
+                        Some(axum::serve(listener, redirect_to_https(address.port())))
+                    }
+                    None => None,
+                };
+
+                AppServer::Https(Box::new(HttpsServer {
+                    server: axum_server::from_tcp_rustls(
+                        listener.into_std()?,
+                        certificates.config(),
+                    ),
+                    service,
+                    certificates,
+                    reload_interval: Duration::from_secs(settings.tls.reload_interval_seconds),
+                    redirect,
+                }))
+            }
+        };
+
+        Ok(Application {
+            server,
+            address: address.to_string(),
+            redirect_address,
+            shutdown,
+            shutdown_grace: Duration::from_secs(settings.application.shutdown_grace_seconds),
+            shutdown_deadline: Duration::from_secs(settings.application.shutdown_deadline_seconds),
+        })
+    }
+
+    /// Handle that stops the app: readiness starts failing, new connections are refused once the
+    /// shutdown grace period has passed and the requests in flight then get until the shutdown
+    /// deadline to finish.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Serves until the shutdown handle is triggered and the grace period has passed, then until
+    /// the requests in flight have drained, or the shutdown deadline has passed.
+    pub async fn run(self) -> Result<(), std::io::Error> {
+        let shutdown = self.shutdown.clone();
+        let grace = self.shutdown_grace;
+        let server: Pin<Box<dyn Future<Output = Result<(), io::Error>> + Send>> = match self.server
+        {
+            AppServer::Http(server) => {
+                tracing::info!("listening on {}", &self.address);
+                Box::pin(
+                    server
+                        .with_graceful_shutdown(
+                            async move { stop_listening(&shutdown, grace).await },
+                        )
+                        .into_future(),
+                )
+            }
+            AppServer::Https(server) => {
+                tracing::info!("listening on {} over HTTPS", &self.address);
+                if let Some(redirect_address) = &self.redirect_address {
+                    tracing::info!("redirecting HTTP on {} to HTTPS", redirect_address);
+                }
+                Box::pin(server.serve(shutdown, grace))
+            }
+        };
+
+        tokio::select! {
+            result = server => {
+                tracing::info!("Drained in-flight requests");
+                result
+            }
+            _ = drain_deadline(&self.shutdown, grace, self.shutdown_deadline) => {
+                tracing::warn!("Shutdown deadline passed with requests still in flight");
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Resolves `grace` after shutdown starts. Readiness fails in the meantime while connections are
+/// still accepted, so load balancers stop routing here before the listener closes.
+async fn stop_listening(shutdown: &ShutdownHandle, grace: Duration) {
+    shutdown.triggered().await;
+    tokio::time::sleep(grace).await;
+}
+
+async fn drain_deadline(shutdown: &ShutdownHandle, grace: Duration, deadline: Duration) {
+    shutdown.triggered().await;
+    tokio::time::sleep(grace + deadline).await;
+}
+
+/// The admin listener, serving `/metrics` apart from the public routes.
+pub struct AdminApplication {
+    server: Serve<Router, Router>,
+    pub address: String,
+}
+
+impl AdminApplication {
+    pub async fn build(address: &str, metrics: PrometheusHandle) -> Result<Self, Box<dyn Error>> {
+        let router = Router::new()
+            .route("/metrics", get(render_metrics))
+            .with_state(metrics);
 
         let listener = tokio::net::TcpListener::bind(address).await?;
         let address = listener.local_addr()?.to_string();
         let server = axum::serve(listener, router);
 
-        Ok(Application { server, address })
+        Ok(AdminApplication { server, address })
     }
 
-    pub async fn run(self) -> Result<(), std::io::Error> {
-        tracing::info!("listening on {}", &self.address);
-        self.server.await
+    /// Serves until `shutdown` is triggered.
+    pub async fn run(self, shutdown: ShutdownHandle) -> Result<(), std::io::Error> {
+        tracing::info!("admin listener on {}", &self.address);
+        self.server
+            .with_graceful_shutdown(async move { shutdown.triggered().await })
+            .await
     }
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct ErrorResponse {
     pub error: String,
+    /// Echoes the `x-request-id` header, so a client can quote it when reporting the error.
+    #[serde(rename = "requestId", default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
 }
 
 impl IntoResponse for AuthAPIError {
     fn into_response(self) -> Response {
         log_error_chain(&self);
+        let retry_after = match self {
+            AuthAPIError::ResendCooldown(seconds) => Some(seconds),
+            _ => None,
+        };
         let (status, error_message) = match self {
             AuthAPIError::UserAlreadyExists => (StatusCode::CONFLICT, "User already exists"),
             AuthAPIError::InvalidCredentials => (StatusCode::BAD_REQUEST, "Invalid credentials"),
             AuthAPIError::IncorrectCredentials => {
                 (StatusCode::UNAUTHORIZED, "Incorrect credentials")
             }
+            AuthAPIError::AccountDisabled => (StatusCode::FORBIDDEN, "Account disabled"),
             AuthAPIError::MissingToken => (StatusCode::BAD_REQUEST, "Missing token"),
             AuthAPIError::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid token"),
+            AuthAPIError::TooManyRequests => (StatusCode::TOO_MANY_REQUESTS, "Too many requests"),
+            AuthAPIError::TrustedDeviceNotFound => {
+                (StatusCode::NOT_FOUND, "Trusted device not found")
+            }
+            AuthAPIError::PhoneNumberNotVerified => {
+                (StatusCode::BAD_REQUEST, "Phone number not verified")
+            }
+            AuthAPIError::SmsNotEnabled => (StatusCode::NOT_FOUND, "SMS is not enabled"),
+            AuthAPIError::ResendCooldown(_) => {
+                (StatusCode::TOO_MANY_REQUESTS, "Resend cooldown active")
+            }
             AuthAPIError::UnexpectedError(_) => {
                 (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected error")
             }
         };
         let body = Json(ErrorResponse {
             error: error_message.to_string(),
+            request_id: current_request_id(),
         });
-        (status, body).into_response()
+        match retry_after {
+            Some(seconds) => {
+                (status, [(header::RETRY_AFTER, seconds.to_string())], body).into_response()
+            }
+            None => (status, body).into_response(),
+        }
     }
 }
 
-pub async fn configure_postgresql() -> PgPool {
-    let pg_pool = get_postgres_pool(&DATABASE_URL)
+pub static POSTGRES_MIGRATOR: Migrator = sqlx::migrate!();
+pub static SQLITE_MIGRATOR: Migrator = sqlx::migrate!("./migrations_sqlite");
+
+pub async fn configure_postgresql(settings: &DatabaseSettings) -> PgPool {
+    let pg_pool = get_postgres_pool(&settings.url, &settings.pool_config())
         .await
         .expect("Failed to create Postgres connection pool!");
 
-    sqlx::migrate!()
+    POSTGRES_MIGRATOR
         .run(&pg_pool)
         .await
         .expect("Failed to run migrations");
@@ -119,8 +385,77 @@ pub async fn configure_postgresql() -> PgPool {
     pg_pool
 }
 
-pub async fn get_postgres_pool(url: &str) -> Result<PgPool, sqlx::Error> {
-    PgPoolOptions::new().max_connections(5).connect(url).await
+pub async fn configure_sqlite(settings: &DatabaseSettings) -> SqlitePool {
+    let sqlite_pool = get_sqlite_pool(settings)
+        .await
+        .expect("Failed to create SQLite connection pool!");
+
+    SQLITE_MIGRATOR
+        .run(&sqlite_pool)
+        .await
+        .expect("Failed to run migrations");
+
+    sqlite_pool
+}
+
+/// Opens the SQLite database, creating the file if needed, without running migrations.
+pub async fn get_sqlite_pool(settings: &DatabaseSettings) -> Result<SqlitePool, sqlx::Error> {
+    let options = SqliteConnectOptions::from_str(&settings.url)?.create_if_missing(true);
+
+    SqlitePoolOptions::new()
+        .max_connections(settings.max_connections)
+        .connect_with(options)
+        .await
+}
+
+/// Puts the cache configured in `settings`, if any, in front of `store`.
+pub fn with_user_cache<S: UserStore + 'static>(
+    store: S,
+    settings: &UserCacheSettings,
+    redis_pool: Option<&RedisPool>,
+) -> UserStoreType {
+    let ttl = Duration::from_secs(settings.ttl_seconds);
+    let cache: Arc<dyn UserCache> = match settings.backend {
+        UserCacheBackend::Off => return Arc::new(store),
+        UserCacheBackend::Memory => Arc::new(InMemoryUserCache::new(settings.capacity, ttl)),
+        UserCacheBackend::Redis => Arc::new(RedisUserCache::new(
+            redis_pool
+                .expect("The Redis user cache requires a Redis connection")
+                .clone(),
+            ttl,
+        )),
+    };
+    Arc::new(CachedUserStore::new(store, cache))
+}
+
+/// Periodically deletes expired rows from the Postgres-backed stores.
+pub fn spawn_postgres_cleanup(pg_pool: PgPool, interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+
+            if let Err(e) = PostgresBannedTokenStore::delete_expired(&pg_pool).await {
+                tracing::error!(error = ?e, "Failed to clean up banned tokens");
+            }
+            if let Err(e) = PostgresTwoFACodeStore::delete_expired(&pg_pool).await {
+                tracing::error!(error = ?e, "Failed to clean up 2FA codes");
+            }
+            if let Err(e) = PostgresTrustedDeviceStore::delete_expired(&pg_pool).await {
+                tracing::error!(error = ?e, "Failed to clean up trusted devices");
+            }
+            if let Err(e) = PostgresMagicLinkStore::delete_expired(&pg_pool).await {
+                tracing::error!(error = ?e, "Failed to clean up magic links");
+            }
+        }
+    })
+}
+
+pub async fn get_postgres_pool(
+    url: &str,
+    config: &PostgresPoolConfig,
+) -> Result<PgPool, sqlx::Error> {
+    connect_postgres_pool(url, config).await
 }
 
 pub fn get_redis_client(redis_hostname: String) -> RedisResult<Client> {
@@ -128,11 +463,13 @@ pub fn get_redis_client(redis_hostname: String) -> RedisResult<Client> {
     redis::Client::open(redis_url)
 }
 
-pub fn configure_redis() -> redis::Connection {
-    get_redis_client(REDIS_HOST_NAME.to_owned())
-        .expect("Failed to get Redis client")
-        .get_connection()
-        .expect("Failed to get Redis connection")
+pub async fn configure_redis(settings: &RedisSettings) -> RedisPool {
+    let client =
+        get_redis_client(settings.host_name.to_owned()).expect("Failed to get Redis client");
+
+    RedisPool::new(client, settings.pool_config())
+        .await
+        .expect("Failed to create Redis connection pool")
 }
 
 fn log_error_chain(e: &(dyn Error + 'static)) {
@@ -149,17 +486,33 @@ fn log_error_chain(e: &(dyn Error + 'static)) {
     tracing::error!("{}", report);
 }
 
-pub fn configure_postmark_email_client() -> PostmarkEmailClient {
+pub fn configure_postmark_email_client(settings: &EmailClientSettings) -> PostmarkEmailClient {
     use reqwest::Client;
     let http_client = Client::builder()
-        .timeout(prod::email_client::TIMEOUT)
+        .timeout(Duration::from_millis(settings.timeout_ms))
         .build()
         .expect("Failed to build HTTP client");
 
     PostmarkEmailClient::new(
-        prod::email_client::BASE_URL.to_owned(),
-        Email::parse(prod::email_client::SENDER).unwrap(),
-        POSTMARK_AUTH_TOKEN.to_owned(),
+        settings.base_url.to_owned(),
+        Email::parse(&settings.sender).expect("email_client.sender is validated on load"),
+        settings.auth_token.to_owned(),
+        http_client,
+    )
+}
+
+pub fn configure_twilio_sms_client(settings: &SmsClientSettings) -> TwilioSmsClient {
+    use reqwest::Client;
+    let http_client = Client::builder()
+        .timeout(Duration::from_millis(settings.timeout_ms))
+        .build()
+        .expect("Failed to build HTTP client");
+
+    TwilioSmsClient::new(
+        settings.base_url.to_owned(),
+        PhoneNumber::parse(&settings.sender).expect("sms_client.sender is validated on load"),
+        settings.account_sid.to_owned(),
+        settings.auth_token.to_owned(),
         http_client,
     )
 }
@@ -1,33 +1,233 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use auth_service::{
-    app_state::AppState,
-    configure_postgresql, configure_postmark_email_client, configure_redis,
-    services::{PostgresUserStore, RedisBannedTokenStore, RedisTwoFACodeStore},
-    utils::{constants::prod, tracing::init_tracing},
-    Application,
+    app_state::{
+        AppState, AuditLogType, BannedTokenStoreType, MagicLinkStoreType, SmsClientType,
+        TrustedDeviceStoreType, TwoFACodeStoreType, UserStoreType,
+    },
+    configure_postgresql, configure_postmark_email_client, configure_redis, configure_sqlite,
+    configure_twilio_sms_client,
+    domain::Dependency,
+    services::{
+        DatabaseHealthCheck, HttpHealthCheck, InMemoryAuditLog, PostgresAuditLog,
+        PostgresBannedTokenStore, PostgresMagicLinkStore, PostgresTrustedDeviceStore,
+        PostgresTwoFACodeStore, PostgresUserStore, RedisBannedTokenStore, RedisHealthCheck,
+        RedisMagicLinkStore, RedisTrustedDeviceStore, RedisTwoFACodeStore, SqliteUserStore,
+    },
+    spawn_postgres_cleanup,
+    utils::{
+        constants::{StoreBackend, METRICS_UPKEEP_INTERVAL_SECONDS},
+        metrics::{install_prometheus_recorder, spawn_metrics_upkeep, spawn_pool_metrics},
+        settings::Settings,
+        shutdown::shutdown_signal,
+        tracing::init_tracing,
+    },
+    with_user_cache, AdminApplication, Application,
 };
 use tokio::sync::RwLock;
 
 #[tokio::main]
 async fn main() {
     color_eyre::install().expect("Failed to install color_eyre");
-    init_tracing().expect("Failed to initialize tracing");
 
-    let pg_pool = configure_postgresql().await;
-    let redis_conn = Arc::new(RwLock::new(configure_redis()));
+    let settings = match Settings::load() {
+        Ok(settings) => Arc::new(settings),
+        Err(e) => {
+            eprintln!("Invalid configuration: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    let tracer_provider = init_tracing(&settings.telemetry).expect("Failed to initialize tracing");
+
+    // Installed before anything records a metric, so nothing recorded is lost.
+    let admin = match settings.admin_listener.enabled {
+        true => {
+            let metrics =
+                install_prometheus_recorder().expect("Failed to install metrics recorder");
+            spawn_metrics_upkeep(
+                metrics.clone(),
+                Duration::from_secs(METRICS_UPKEEP_INTERVAL_SECONDS),
+            );
+            let admin = AdminApplication::build(&settings.admin_listener.address, metrics)
+                .await
+                .expect("Failed to build admin listener");
+            Some(admin)
+        }
+        false => None,
+    };
+
+    let (pg_pool, sqlite_pool) = match settings.database.uses_sqlite() {
+        true => (None, Some(configure_sqlite(&settings.database).await)),
+        false => (Some(configure_postgresql(&settings.database).await), None),
+    };
+    // A deployment with every store in Postgres runs without Redis.
+    let redis_pool = match settings.uses_redis() {
+        true => Some(configure_redis(&settings.redis).await),
+        false => None,
+    };
+    let pool_metrics_interval =
+        Duration::from_secs(settings.database.pool_metrics_interval_seconds);
+    let health = &settings.health;
+    let database_timeout = Duration::from_millis(health.database_timeout_ms);
+    let mut dependencies = Vec::new();
+
+    let user_store: UserStoreType = match (&pg_pool, &sqlite_pool) {
+        (Some(pg_pool), _) => {
+            spawn_pool_metrics(pg_pool.clone(), "postgres", pool_metrics_interval);
+            dependencies.push(Dependency {
+                name: "postgres",
+                critical: true,
+                timeout: database_timeout,
+                check: Arc::new(DatabaseHealthCheck::new(pg_pool.clone())),
+            });
+            with_user_cache(
+                PostgresUserStore::new(pg_pool.clone()),
+                &settings.user_cache,
+                redis_pool.as_ref(),
+            )
+        }
+        (None, Some(sqlite_pool)) => {
+            spawn_pool_metrics(sqlite_pool.clone(), "sqlite", pool_metrics_interval);
+            dependencies.push(Dependency {
+                name: "sqlite",
+                critical: true,
+                timeout: database_timeout,
+                check: Arc::new(DatabaseHealthCheck::new(sqlite_pool.clone())),
+            });
+            with_user_cache(
+                SqliteUserStore::new(sqlite_pool.clone()),
+                &settings.user_cache,
+                redis_pool.as_ref(),
+            )
+        }
+        (None, None) => unreachable!("one of the databases is always configured"),
+    };
+    if let Some(redis_pool) = &redis_pool {
+        dependencies.push(Dependency {
+            name: "redis",
+            critical: true,
+            timeout: Duration::from_millis(health.redis_timeout_ms),
+            check: Arc::new(RedisHealthCheck::new(redis_pool.clone())),
+        });
+    }
+    if health.check_email_provider {
+        dependencies.push(Dependency {
+            name: "email_provider",
+            critical: false,
+            timeout: Duration::from_millis(health.email_provider_timeout_ms),
+            check: Arc::new(HttpHealthCheck::new(
+                settings.email_client.base_url.to_owned(),
+                reqwest::Client::new(),
+            )),
+        });
+    }
+
+    // Without Postgres the audit log only lasts as long as the process.
+    let audit_log: AuditLogType = match &pg_pool {
+        Some(pg_pool) => Arc::new(PostgresAuditLog::new(pg_pool.clone())),
+        None => Arc::new(InMemoryAuditLog::default()),
+    };
+    let postgres_backend_pool = || {
+        pg_pool
+            .clone()
+            .expect("Postgres store backends require a Postgres DATABASE_URL")
+    };
+    let redis_backend_pool = || {
+        redis_pool
+            .clone()
+            .expect("Redis store backends require a Redis connection")
+    };
+
+    let stores = &settings.stores;
+    let max_pending_attempts = settings.two_fa.max_pending_attempts;
+
+    let banned_token_store: BannedTokenStoreType = match stores.banned_token_store {
+        StoreBackend::Redis => Arc::new(RedisBannedTokenStore::new(redis_backend_pool())),
+        StoreBackend::Postgres => Arc::new(PostgresBannedTokenStore::new(postgres_backend_pool())),
+    };
+    let two_fa_code_store: TwoFACodeStoreType = match stores.two_fa_code_store {
+        StoreBackend::Redis => Arc::new(RedisTwoFACodeStore::new(
+            redis_backend_pool(),
+            max_pending_attempts,
+        )),
+        StoreBackend::Postgres => Arc::new(PostgresTwoFACodeStore::new(
+            postgres_backend_pool(),
+            max_pending_attempts,
+        )),
+    };
+
+    let trusted_device_store: TrustedDeviceStoreType = match stores.trusted_device_store {
+        StoreBackend::Redis => Arc::new(RedisTrustedDeviceStore::new(redis_backend_pool())),
+        StoreBackend::Postgres => {
+            Arc::new(PostgresTrustedDeviceStore::new(postgres_backend_pool()))
+        }
+    };
+    let magic_link_store: MagicLinkStoreType = match stores.magic_link_store {
+        StoreBackend::Redis => Arc::new(RedisMagicLinkStore::new(redis_backend_pool())),
+        StoreBackend::Postgres => Arc::new(PostgresMagicLinkStore::new(postgres_backend_pool())),
+    };
+
+    if stores.uses_postgres() {
+        spawn_postgres_cleanup(
+            postgres_backend_pool(),
+            Duration::from_secs(stores.postgres_cleanup_interval_seconds),
+        );
+    }
+
     let app_state = AppState::new(
-        Arc::new(RwLock::new(Box::new(PostgresUserStore::new(pg_pool)))),
-        Arc::new(RwLock::new(Box::new(RedisBannedTokenStore::new(
-            redis_conn.clone(),
+        settings.clone(),
+        user_store,
+        banned_token_store,
+        two_fa_code_store,
+        trusted_device_store,
+        magic_link_store,
+        audit_log,
+        Arc::new(RwLock::new(Box::new(configure_postmark_email_client(
+            &settings.email_client,
         )))),
-        Arc::new(RwLock::new(Box::new(RedisTwoFACodeStore::new(redis_conn)))),
-        Arc::new(RwLock::new(Box::new(configure_postmark_email_client()))),
+        settings.sms_client.enabled.then(|| -> SmsClientType {
+            Arc::new(RwLock::new(Box::new(configure_twilio_sms_client(
+                &settings.sms_client,
+            ))))
+        }),
+        Arc::new(dependencies),
     );
 
-    let app = Application::build(app_state, prod::APP_ADDRESS)
+    let app = Application::build(app_state)
         .await
         .expect("Failed to build app");
 
+    let shutdown = app.shutdown_handle();
+    if let Some(admin) = admin {
+        tokio::spawn(admin.run(shutdown.clone()));
+    }
+    let shutdown_grace_seconds = settings.application.shutdown_grace_seconds;
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        tracing::info!(
+            "Shutdown signal received, failing readiness for {shutdown_grace_seconds}s before draining requests"
+        );
+        shutdown.trigger();
+    });
+
     app.run().await.expect("Failed to run app");
+
+    // Background tasks still hold clones of the pools, so they are closed rather than dropped.
+    if let Some(pg_pool) = pg_pool {
+        pg_pool.close().await;
+    }
+    if let Some(sqlite_pool) = sqlite_pool {
+        sqlite_pool.close().await;
+    }
+    if let Some(redis_pool) = redis_pool {
+        redis_pool.close().await;
+    }
+    tracing::info!("Shut down");
+
+    if let Some(tracer_provider) = tracer_provider {
+        if let Err(e) = tracer_provider.shutdown() {
+            eprintln!("Failed to flush traces: {e}");
+        }
+    }
 }
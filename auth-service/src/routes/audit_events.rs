@@ -0,0 +1,74 @@
+use axum::{
+    extract::{Query, State},
+    http::HeaderMap,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuditEventKind, AuditOutcome, AuditQuery, AuditRecord, AuthAPIError},
+    utils::{
+        auth::authorize_admin,
+        constants::{DEFAULT_AUDIT_PAGE_SIZE, MAX_AUDIT_PAGE_SIZE},
+    },
+};
+
+#[tracing::instrument(name = "List audit events", skip_all)]
+pub async fn audit_events(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(request): Query<AuditEventsRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    authorize_admin(&headers, state.settings.auth.admin_api_token.as_ref())?;
+
+    let query = AuditQuery {
+        actor: request.actor,
+        kind: request.kind,
+        outcome: request.outcome,
+        since: request.since,
+        until: request.until,
+        before: request.before,
+        limit: request
+            .limit
+            .unwrap_or(DEFAULT_AUDIT_PAGE_SIZE)
+            .clamp(1, MAX_AUDIT_PAGE_SIZE),
+    };
+
+    let events = state
+        .audit_log
+        .query(&query)
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    // A full page may have more events after it.
+    let next_before = match events.len() == query.limit as usize {
+        true => events.last().map(|record| record.id),
+        false => None,
+    };
+
+    Ok(Json(AuditEventsResponse {
+        events,
+        next_before,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct AuditEventsRequest {
+    actor: Option<String>,
+    kind: Option<AuditEventKind>,
+    outcome: Option<AuditOutcome>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    before: Option<i64>,
+    limit: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditEventsResponse {
+    pub events: Vec<AuditRecord>,
+    #[serde(rename = "nextBefore")]
+    pub next_before: Option<i64>,
+}
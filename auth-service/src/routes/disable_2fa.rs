@@ -0,0 +1,75 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use secrecy::Secret;
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, LoginAttemptId, Password, TwoFACode, TwoFAPurpose},
+    routes::check_2fa_code,
+    utils::auth::authenticate,
+};
+
+#[tracing::instrument(name = "Disable 2FA", skip_all)]
+pub async fn disable_2fa(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<Disable2FARequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticate(&state, &jar).await?;
+
+    let password =
+        Password::parse(request.password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let login_attempt_id = LoginAttemptId::parse(request.login_attempt_id)
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let two_fa_code =
+        TwoFACode::parse(request.two_fa_code).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    state
+        .user_store
+        .validate_user(email.clone(), password)
+        .await
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    check_2fa_code(
+        &state,
+        &email,
+        &login_attempt_id,
+        &two_fa_code,
+        TwoFAPurpose::Update2FA,
+    )
+    .await?;
+
+    state
+        .user_store
+        .update_requires_2fa(email.clone(), false)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    // 2FA is already off at this point, so a failed notification must not fail the request.
+    if let Err(e) = state
+        .email_client
+        .read()
+        .await
+        .send_email(
+            &email,
+            "Auth Service: 2FA disabled",
+            "Two-factor authentication was turned off for your account. \
+            If you did not do this, reset your password and turn it back on.",
+        )
+        .await
+    {
+        tracing::error!(error = ?e, "failed to send 2FA disabled notification");
+    }
+
+    Ok(StatusCode::OK)
+}
+
+#[derive(Deserialize)]
+pub struct Disable2FARequest {
+    password: Secret<String>,
+    #[serde(rename = "loginAttemptId")]
+    login_attempt_id: String,
+    #[serde(rename = "2FACode")]
+    two_fa_code: String,
+}
@@ -0,0 +1,49 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, LoginAttemptId, TwoFACode, TwoFAPurpose},
+    routes::check_2fa_code,
+    utils::auth::authenticate,
+};
+
+#[tracing::instrument(name = "Enable 2FA", skip_all)]
+pub async fn enable_2fa(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<Enable2FARequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticate(&state, &jar).await?;
+
+    let login_attempt_id = LoginAttemptId::parse(request.login_attempt_id)
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let two_fa_code =
+        TwoFACode::parse(request.two_fa_code).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    check_2fa_code(
+        &state,
+        &email,
+        &login_attempt_id,
+        &two_fa_code,
+        TwoFAPurpose::Update2FA,
+    )
+    .await?;
+
+    state
+        .user_store
+        .update_requires_2fa(email, true)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(StatusCode::OK)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Enable2FARequest {
+    #[serde(rename = "loginAttemptId")]
+    login_attempt_id: String,
+    #[serde(rename = "2FACode")]
+    two_fa_code: String,
+}
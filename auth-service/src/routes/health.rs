@@ -0,0 +1,125 @@
+use std::{collections::BTreeMap, time::Instant};
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinSet;
+
+use crate::{app_state::AppState, domain::Dependency};
+
+/// Answers as long as the process is serving requests, without touching any dependency.
+pub async fn health_live() -> impl IntoResponse {
+    Json(LivenessResponse {
+        status: HealthStatus::Up,
+    })
+}
+
+/// Checks every dependency concurrently, each under its own timeout, and answers 503 if a
+/// critical one is down.
+#[tracing::instrument(name = "Check readiness", skip_all)]
+pub async fn health_ready(State(state): State<AppState>) -> impl IntoResponse {
+    let mut checks = JoinSet::new();
+    for dependency in state.dependencies.iter().cloned() {
+        checks.spawn(check_dependency(dependency));
+    }
+
+    let mut dependencies = BTreeMap::new();
+    while let Some(result) = checks.join_next().await {
+        match result {
+            Ok((name, health)) => {
+                dependencies.insert(name.to_owned(), health);
+            }
+            Err(e) => tracing::error!(error = ?e, "Readiness check panicked"),
+        }
+    }
+
+    // Checked last, so a check still running when shutdown starts is answered as not ready.
+    let shutting_down = state.shutdown.is_triggered();
+    // A check that panicked is missing from the breakdown, so it counts as a critical failure.
+    let ready = !shutting_down
+        && dependencies.len() == state.dependencies.len()
+        && dependencies
+            .values()
+            .all(|health| !health.critical || health.status == HealthStatus::Up);
+    let (status_code, status) = match ready {
+        true => (StatusCode::OK, HealthStatus::Up),
+        false => (StatusCode::SERVICE_UNAVAILABLE, HealthStatus::Down),
+    };
+
+    (
+        status_code,
+        Json(ReadinessResponse {
+            status,
+            shutting_down,
+            dependencies,
+        }),
+    )
+}
+
+async fn check_dependency(dependency: Dependency) -> (&'static str, DependencyHealth) {
+    let started = Instant::now();
+    // The endpoint is public, so driver errors, which can name hosts and ports, are only logged.
+    let error = match tokio::time::timeout(dependency.timeout, dependency.check.check()).await {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => {
+            tracing::warn!(
+                dependency = dependency.name,
+                error = format!("{e:#}"),
+                "Dependency is down"
+            );
+            Some(UNREACHABLE)
+        }
+        Err(_) => {
+            tracing::warn!(
+                dependency = dependency.name,
+                timeout_ms = dependency.timeout.as_millis() as u64,
+                "Dependency check timed out"
+            );
+            Some(TIMEOUT)
+        }
+    };
+
+    let health = DependencyHealth {
+        status: match error {
+            None => HealthStatus::Up,
+            Some(_) => HealthStatus::Down,
+        },
+        critical: dependency.critical,
+        latency_ms: started.elapsed().as_millis() as u64,
+        error: error.map(str::to_owned),
+    };
+    (dependency.name, health)
+}
+
+const UNREACHABLE: &str = "unreachable";
+const TIMEOUT: &str = "timeout";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Up,
+    Down,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LivenessResponse {
+    pub status: HealthStatus,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReadinessResponse {
+    pub status: HealthStatus,
+    #[serde(rename = "shuttingDown")]
+    pub shutting_down: bool,
+    pub dependencies: BTreeMap<String, DependencyHealth>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DependencyHealth {
+    pub status: HealthStatus,
+    pub critical: bool,
+    #[serde(rename = "latencyMs")]
+    pub latency_ms: u64,
+    /// `unreachable` or `timeout` when the dependency is down. The details are only logged.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
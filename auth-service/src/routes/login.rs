@@ -4,80 +4,240 @@ use secrecy::Secret;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    app_state::AppState,
-    domain::{AuthAPIError, Email, LoginAttemptId, Password, TwoFACode},
-    utils::auth::generate_auth_cookie,
+    app_state::{AppState, SmsClientType},
+    domain::{
+        AuditEventKind, AuditOutcome, AuthAPIError, Email, LoginAttemptId, Password, PhoneNumber,
+        StoredUser, TwoFACode, TwoFAPurpose, UserId,
+    },
+    utils::{
+        audit::{record_audit_event, RequestMetadata},
+        auth::{generate_auth_cookie, trusted_device_id},
+        metrics::{outcome_label, record_login_attempt},
+    },
 };
 
 #[tracing::instrument(name = "Login", skip_all)]
 pub async fn login(
     State(state): State<AppState>,
+    metadata: RequestMetadata,
     jar: CookieJar,
     Json(request): Json<LoginRequest>,
 ) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
+    let actor = request.email.clone();
+    let result = login_with_password(&state, jar, request).await;
+
+    record_login_audit(&state, &metadata, Some(actor), &result).await;
+    record_login_attempt(match &result {
+        Ok(_) if requires_2fa(&result) => "2fa_required",
+        Err(AuthAPIError::IncorrectCredentials) => "bad_password",
+        _ => outcome_label(&result),
+    });
+
+    result.map(|(_, response)| response)
+}
+
+/// The outcome of a login: the id of the user who logged in along with the response.
+pub(crate) type LoginResult =
+    Result<(UserId, (CookieJar, (StatusCode, Json<LoginResponse>))), AuthAPIError>;
+
+/// Records a login event, telling a login that moved on to 2FA apart from a completed one.
+pub(crate) async fn record_login_audit(
+    state: &AppState,
+    metadata: &RequestMetadata,
+    actor: Option<String>,
+    result: &LoginResult,
+) {
+    let (outcome, reason) = match result {
+        Ok(_) if requires_2fa(result) => (AuditOutcome::TwoFARequired, None),
+        Ok(_) => (AuditOutcome::Success, None),
+        Err(e) => (AuditOutcome::Failure, Some(e.to_string())),
+    };
+    record_audit_event(
+        state,
+        metadata,
+        AuditEventKind::Login,
+        outcome,
+        actor,
+        result.as_ref().ok().map(|(user_id, _)| user_id),
+        reason,
+    )
+    .await;
+}
+
+/// Whether a login answered with a 2FA challenge instead of an auth cookie.
+pub(crate) fn requires_2fa(result: &LoginResult) -> bool {
+    matches!(result, Ok((_, (_, (status, _)))) if *status == StatusCode::PARTIAL_CONTENT)
+}
+
+async fn login_with_password(
+    state: &AppState,
+    jar: CookieJar,
+    request: LoginRequest,
+) -> LoginResult {
     let email = Email::parse(&request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
     let pwd = Password::parse(request.password).map_err(|_| AuthAPIError::InvalidCredentials)?;
 
-    let user_store = &state.user_store.read().await;
-    user_store
-        .validate_user(email.clone(), pwd)
+    let user = state
+        .user_store
+        .validate_user(email, pwd)
         .await
         .map_err(|_| AuthAPIError::IncorrectCredentials)?;
 
-    let user = user_store
-        .get_user(email.clone())
-        .await
-        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    let response = complete_login(&user, state, jar).await?;
+    Ok((*user.id(), response))
+}
 
-    match user.requires_2fa() {
-        true => handle_2fa(user.email(), &state, jar).await,
-        false => handle_no_2fa(user.email(), jar).await,
+/// Finishes a login whose first factor has been checked: refuses disabled accounts, then sets the
+/// auth cookie, or starts 2FA if the user requires it and is not on a trusted device.
+#[tracing::instrument(name = "Complete login", skip_all)]
+pub(crate) async fn complete_login(
+    user: &StoredUser,
+    state: &AppState,
+    jar: CookieJar,
+) -> Result<(CookieJar, (StatusCode, Json<LoginResponse>)), AuthAPIError> {
+    if user.is_disabled() {
+        return Err(AuthAPIError::AccountDisabled);
+    }
+
+    match *user.requires_2fa() && !is_trusted_device(user.email(), state, &jar).await? {
+        true => handle_2fa(user, state, jar).await,
+        false => handle_no_2fa(user, state, jar).await,
     }
 }
 
+#[tracing::instrument(name = "Check trusted device", skip_all)]
+async fn is_trusted_device(
+    email: &Email,
+    state: &AppState,
+    jar: &CookieJar,
+) -> Result<bool, AuthAPIError> {
+    let Some(device_id) = trusted_device_id(jar, email, &state.settings.auth.jwt_secret) else {
+        return Ok(false);
+    };
+
+    state
+        .trusted_device_store
+        .contains_device(email, &device_id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))
+}
+
 #[tracing::instrument(name = "Login handle 2FA", skip_all)]
 async fn handle_2fa(
-    email: &Email,
+    user: &StoredUser,
     state: &AppState,
     jar: CookieJar,
 ) -> Result<(CookieJar, (StatusCode, Json<LoginResponse>)), AuthAPIError> {
+    let login_attempt_id = issue_2fa_code(user, TwoFAPurpose::Login, state).await?;
+
+    let response = Json(LoginResponse::TwoFactorAuth(TwoFactorAuthResponse {
+        message: "2FA required".to_owned(),
+        login_attempt_id: login_attempt_id.as_ref().to_owned(),
+    }));
+
+    Ok((jar, (StatusCode::PARTIAL_CONTENT, response)))
+}
+
+#[tracing::instrument(name = "Issue 2FA code", skip_all)]
+pub(crate) async fn issue_2fa_code(
+    user: &StoredUser,
+    purpose: TwoFAPurpose,
+    state: &AppState,
+) -> Result<LoginAttemptId, AuthAPIError> {
+    let (login_attempt_id, two_fa_code) = store_2fa_code(user.email(), purpose, state).await?;
+
+    deliver_2fa_code(user, &two_fa_code, state).await?;
+
+    Ok(login_attempt_id)
+}
+
+/// Stores a fresh code for `purpose`, enforcing the hourly cap on issued codes.
+#[tracing::instrument(name = "Store 2FA code", skip_all)]
+pub(crate) async fn store_2fa_code(
+    email: &Email,
+    purpose: TwoFAPurpose,
+    state: &AppState,
+) -> Result<(LoginAttemptId, TwoFACode), AuthAPIError> {
     let login_attempt_id = LoginAttemptId::default();
     let two_fa_code = TwoFACode::default();
 
-    state
-        .two_fa_code_store
-        .write()
+    let two_fa_code_store = &state.two_fa_code_store;
+
+    let issued_codes = two_fa_code_store
+        .record_code_issued(email)
         .await
-        .add_code(email.clone(), login_attempt_id.clone(), two_fa_code.clone())
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    if issued_codes > state.settings.two_fa.max_codes_per_hour {
+        return Err(AuthAPIError::TooManyRequests);
+    }
+
+    two_fa_code_store
+        .add_code(
+            email.clone(),
+            login_attempt_id.clone(),
+            two_fa_code.clone(),
+            purpose,
+        )
         .await
         .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
 
-    state
-        .email_client
+    Ok((login_attempt_id, two_fa_code))
+}
+
+/// Sends the code by SMS if SMS is enabled and the user prefers it and has a verified phone
+/// number, by email otherwise.
+#[tracing::instrument(name = "Deliver 2FA code", skip_all)]
+pub(crate) async fn deliver_2fa_code(
+    user: &StoredUser,
+    two_fa_code: &TwoFACode,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    match (&state.sms_client, user.sms_recipient()) {
+        (Some(sms_client), Some(phone_number)) => {
+            send_2fa_sms(sms_client, phone_number, two_fa_code).await
+        }
+        _ => state
+            .email_client
+            .read()
+            .await
+            .send_email(user.email(), "Auth Service: 2FA code", two_fa_code.as_ref())
+            .await
+            .map_err(AuthAPIError::UnexpectedError),
+    }
+}
+
+pub(crate) async fn send_2fa_sms(
+    sms_client: &SmsClientType,
+    phone_number: &PhoneNumber,
+    two_fa_code: &TwoFACode,
+) -> Result<(), AuthAPIError> {
+    sms_client
         .read()
         .await
-        .send_email(
-            email,
-            "Auth Service: 2FA code",
-            two_fa_code.clone().as_ref(),
-        )
+        .send_sms(phone_number, &two_fa_sms_content(two_fa_code))
         .await
-        .map_err(AuthAPIError::UnexpectedError)?;
-
-    let response = Json(LoginResponse::TwoFactorAuth(TwoFactorAuthResponse {
-        message: "2FA required".to_owned(),
-        login_attempt_id: login_attempt_id.as_ref().to_owned(),
-    }));
+        .map_err(AuthAPIError::UnexpectedError)
+}
 
-    Ok((jar, (StatusCode::PARTIAL_CONTENT, response)))
+fn two_fa_sms_content(two_fa_code: &TwoFACode) -> String {
+    format!("Your Auth Service code is {}", two_fa_code.as_ref())
 }
 
 #[tracing::instrument(name = "Login handle non 2FA", skip_all)]
 async fn handle_no_2fa(
-    email: &Email,
+    user: &StoredUser,
+    state: &AppState,
     jar: CookieJar,
 ) -> Result<(CookieJar, (StatusCode, Json<LoginResponse>)), AuthAPIError> {
-    let auth_cookie = generate_auth_cookie(email).map_err(AuthAPIError::UnexpectedError)?;
+    record_login(user.email(), state).await?;
+
+    let auth_cookie = generate_auth_cookie(
+        user.id(),
+        &state.settings.auth.jwt_secret,
+        state.settings.tls.enabled,
+    )
+    .map_err(AuthAPIError::UnexpectedError)?;
     let updated_jar = jar.add(auth_cookie);
     Ok((
         updated_jar,
@@ -85,6 +245,15 @@ async fn handle_no_2fa(
     ))
 }
 
+#[tracing::instrument(name = "Record login", skip_all)]
+pub(crate) async fn record_login(email: &Email, state: &AppState) -> Result<(), AuthAPIError> {
+    state
+        .user_store
+        .record_login(email.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))
+}
+
 #[derive(Deserialize)]
 pub struct LoginRequest {
     pub email: String,
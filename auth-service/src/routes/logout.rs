@@ -4,32 +4,59 @@ use secrecy::Secret;
 
 use crate::{
     app_state::AppState,
-    domain::AuthAPIError,
-    utils::{auth::validate_token, constants::JWT_COOKIE_NAME},
+    domain::{AuditEventKind, AuthAPIError, StoredUser},
+    utils::{
+        audit::{record_audit_result, RequestMetadata},
+        auth::validate_token_for_user,
+        constants::JWT_COOKIE_NAME,
+        metrics::{outcome_label, record_logout},
+    },
 };
 
 #[tracing::instrument(name = "Logout", skip_all)]
 pub async fn logout(
     State(state): State<AppState>,
+    metadata: RequestMetadata,
     jar: CookieJar,
 ) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
+    let result = logout_user(&state, jar).await;
+
+    let user = result.as_ref().ok().map(|(_, user)| user);
+    // Recorded under the email like every other event, so filtering by actor finds logouts too.
+    record_audit_result(
+        &state,
+        &metadata,
+        AuditEventKind::Logout,
+        user.map(|user| user.email().as_ref().to_owned()),
+        user.map(|user| user.id()),
+        &result,
+    )
+    .await;
+    record_logout(outcome_label(&result));
+
+    result.map(|(jar, _)| (jar, StatusCode::OK))
+}
+
+/// Bans the auth token and removes its cookie. Returns the user who logged out.
+async fn logout_user(
+    state: &AppState,
+    jar: CookieJar,
+) -> Result<(CookieJar, StoredUser), AuthAPIError> {
     let cookie = jar.get(JWT_COOKIE_NAME).ok_or(AuthAPIError::MissingToken)?;
 
     let token = Secret::new(cookie.value().to_owned());
 
-    let _claims = validate_token(&state, token.clone())
+    let (_, user) = validate_token_for_user(state, token.clone())
         .await
         .map_err(|_| AuthAPIError::InvalidToken)?;
 
     state
         .banned_token_store
-        .write()
-        .await
         .add_token(token)
         .await
         .map_err(AuthAPIError::UnexpectedError)?;
 
     let jar = jar.remove(Cookie::from(JWT_COOKIE_NAME));
 
-    Ok((jar, StatusCode::OK))
+    Ok((jar, user))
 }
@@ -0,0 +1,10 @@
+use axum::{extract::State, http::header, response::IntoResponse};
+use metrics_exporter_prometheus::PrometheusHandle;
+
+/// Renders every recorded metric in the Prometheus text format. Served on the admin listener.
+pub async fn render_metrics(State(handle): State<PrometheusHandle>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        handle.render(),
+    )
+}
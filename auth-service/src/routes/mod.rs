@@ -1,11 +1,39 @@
+mod audit_events;
+mod disable_2fa;
+mod enable_2fa;
+mod health;
 mod login;
 mod logout;
+mod metrics;
+mod request_2fa_code;
+mod request_magic_link;
+mod resend_2fa;
+mod revoke_trusted_device;
+mod set_phone_number;
 mod signup;
+mod trusted_devices;
+mod update_2fa_channel;
 mod verify_2fa;
+mod verify_magic_link;
+mod verify_phone_number;
 mod verify_token;
 
+pub use audit_events::*;
+pub use disable_2fa::*;
+pub use enable_2fa::*;
+pub use health::*;
 pub use login::*;
 pub use logout::*;
+pub use metrics::*;
+pub use request_2fa_code::*;
+pub use request_magic_link::*;
+pub use resend_2fa::*;
+pub use revoke_trusted_device::*;
+pub use set_phone_number::*;
 pub use signup::*;
+pub use trusted_devices::*;
+pub use update_2fa_channel::*;
 pub use verify_2fa::*;
+pub use verify_magic_link::*;
+pub use verify_phone_number::*;
 pub use verify_token::*;
@@ -0,0 +1,32 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, TwoFAPurpose},
+    routes::{issue_2fa_code, TwoFactorAuthResponse},
+    utils::auth::authenticate,
+};
+
+#[tracing::instrument(name = "Request 2FA code", skip_all)]
+pub async fn request_2fa_code(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticate(&state, &jar).await?;
+
+    let user = state
+        .user_store
+        .get_cached_user(email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let login_attempt_id = issue_2fa_code(&user, TwoFAPurpose::Update2FA, &state).await?;
+
+    let response = Json(TwoFactorAuthResponse {
+        message: "2FA code sent".to_owned(),
+        login_attempt_id: login_attempt_id.as_ref().to_owned(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
@@ -0,0 +1,104 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, MagicLink, MagicLinkToken, UserStoreError},
+    utils::auth::create_magic_link_nonce_cookie,
+};
+
+#[tracing::instrument(name = "Request magic link", skip_all)]
+pub async fn request_magic_link(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<MagicLinkRequest>,
+) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
+    let email = Email::parse(&request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let nonce = MagicLinkToken::default();
+    let jar = jar.add(create_magic_link_nonce_cookie(
+        &nonce,
+        state.settings.magic_link.ttl_seconds,
+        state.settings.tls.enabled,
+    ));
+
+    // Links share the hourly cap on 2FA codes, as both send mail to the account on request.
+    // Requests for unknown emails count too, so neither the response nor the cap can be used to
+    // probe for accounts.
+    let issued = state
+        .two_fa_code_store
+        .record_code_issued(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    if issued > state.settings.two_fa.max_codes_per_hour {
+        return Err(AuthAPIError::TooManyRequests);
+    }
+
+    match state.user_store.get_cached_user(email.clone()).await {
+        Ok(_) => send_magic_link(&email, &nonce, &state).await?,
+        Err(UserStoreError::UserNotFound) => {}
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    let response = Json(MagicLinkResponse {
+        message: "If the account exists, a login link was sent".to_owned(),
+    });
+
+    Ok((jar, (StatusCode::OK, response)))
+}
+
+#[tracing::instrument(name = "Send magic link", skip_all)]
+async fn send_magic_link(
+    email: &Email,
+    nonce: &MagicLinkToken,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    let token = MagicLinkToken::default();
+
+    state
+        .magic_link_store
+        .add_link(
+            &token,
+            MagicLink::new(
+                email.clone(),
+                nonce,
+                chrono::Duration::seconds(state.settings.magic_link.ttl_seconds),
+            ),
+        )
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let link = format!(
+        "{}/?magicLinkToken={}",
+        state.settings.magic_link.base_url.trim_end_matches('/'),
+        token.as_ref()
+    );
+
+    state
+        .email_client
+        .read()
+        .await
+        .send_email(
+            email,
+            "Auth Service: your login link",
+            &format!(
+                "Open this link in the browser you requested it from to log in: {link}\n\
+                It expires in {} minutes and can be used once.",
+                state.settings.magic_link.ttl_seconds / 60
+            ),
+        )
+        .await
+        .map_err(AuthAPIError::UnexpectedError)
+}
+
+#[derive(Deserialize)]
+pub struct MagicLinkRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MagicLinkResponse {
+    pub message: String,
+}
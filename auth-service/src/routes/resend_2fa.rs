@@ -0,0 +1,100 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use chrono::Duration;
+use color_eyre::eyre::eyre;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, LoginAttemptId, TwoFACodeStoreError, TwoFAPurpose, TwoFAResend},
+    routes::{deliver_2fa_code, send_2fa_sms},
+};
+
+#[tracing::instrument(name = "Resend 2FA", skip_all)]
+pub async fn resend_2fa(
+    State(state): State<AppState>,
+    Json(request): Json<Resend2FARequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(&request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let login_attempt_id = LoginAttemptId::parse(request.login_attempt_id)
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let cooldown_seconds = state.settings.two_fa.resend_cooldown_seconds;
+
+    let (two_fa_code, purpose) = {
+        let two_fa_code_store = &state.two_fa_code_store;
+        let (stored_email, stored_two_fa_code) = two_fa_code_store
+            .get_code(&login_attempt_id)
+            .await
+            .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+        if stored_email != email {
+            return Err(AuthAPIError::IncorrectCredentials);
+        }
+
+        let purpose = two_fa_code_store
+            .get_purpose(&login_attempt_id)
+            .await
+            .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+        let resend = two_fa_code_store
+            .record_resend(
+                &login_attempt_id,
+                Duration::seconds(cooldown_seconds),
+                state.settings.two_fa.max_resends,
+            )
+            .await
+            .map_err(|e| match e {
+                TwoFACodeStoreError::LoginAttemptIdNotFound => AuthAPIError::IncorrectCredentials,
+                e => AuthAPIError::UnexpectedError(e.into()),
+            })?;
+
+        match resend {
+            TwoFAResend::Recorded => {}
+            TwoFAResend::RetryAfter(seconds) => return Err(AuthAPIError::ResendCooldown(seconds)),
+            TwoFAResend::LimitReached => return Err(AuthAPIError::TooManyRequests),
+        }
+
+        (stored_two_fa_code, purpose)
+    };
+
+    // A code verifying a phone number goes to that number, not to where the user's codes go.
+    match &purpose {
+        TwoFAPurpose::VerifyPhoneNumber(phone_number) => {
+            let sms_client = state
+                .sms_client
+                .as_ref()
+                .ok_or_else(|| AuthAPIError::UnexpectedError(eyre!("SMS is not enabled")))?;
+
+            send_2fa_sms(sms_client, phone_number, &two_fa_code).await?;
+        }
+        _ => {
+            let user = state
+                .user_store
+                .get_cached_user(email)
+                .await
+                .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+            deliver_2fa_code(&user, &two_fa_code, &state).await?;
+        }
+    }
+
+    let response = Json(Resend2FAResponse {
+        message: "2FA code resent".to_owned(),
+        retry_after: cooldown_seconds,
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Resend2FARequest {
+    email: String,
+    #[serde(rename = "loginAttemptId")]
+    login_attempt_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Resend2FAResponse {
+    pub message: String,
+    #[serde(rename = "retryAfter")]
+    pub retry_after: i64,
+}
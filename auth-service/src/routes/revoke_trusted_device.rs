@@ -0,0 +1,51 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, TrustedDeviceId, TrustedDeviceStoreError},
+    utils::{
+        auth::{authenticate, trusted_device_id},
+        constants::TRUSTED_DEVICE_COOKIE_NAME,
+    },
+};
+
+#[tracing::instrument(name = "Revoke trusted device", skip_all)]
+pub async fn revoke_trusted_device(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<RevokeTrustedDeviceRequest>,
+) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
+    let email = authenticate(&state, &jar).await?;
+    let device_id =
+        TrustedDeviceId::parse(request.device_id).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    match state
+        .trusted_device_store
+        .remove_device(&email, &device_id)
+        .await
+    {
+        Ok(()) => {}
+        Err(TrustedDeviceStoreError::DeviceNotFound) => {
+            return Err(AuthAPIError::TrustedDeviceNotFound)
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    // Revoking the device the request comes from also drops its now useless cookie.
+    let jar = match trusted_device_id(&jar, &email, &state.settings.auth.jwt_secret) {
+        Some(current_device_id) if current_device_id == device_id => {
+            jar.remove(Cookie::from(TRUSTED_DEVICE_COOKIE_NAME))
+        }
+        _ => jar,
+    };
+
+    Ok((jar, StatusCode::OK))
+}
+
+#[derive(Deserialize)]
+pub struct RevokeTrustedDeviceRequest {
+    #[serde(rename = "deviceId")]
+    device_id: String,
+}
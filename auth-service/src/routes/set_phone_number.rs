@@ -0,0 +1,56 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, PhoneNumber, TwoFAPurpose},
+    routes::{send_2fa_sms, store_2fa_code, TwoFactorAuthResponse},
+    utils::auth::authenticate,
+};
+
+#[tracing::instrument(name = "Set phone number", skip_all)]
+pub async fn set_phone_number(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<SetPhoneNumberRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    // Checked before anything is written, as setting a number resets the user's 2FA channel.
+    let sms_client = state
+        .sms_client
+        .as_ref()
+        .ok_or(AuthAPIError::SmsNotEnabled)?;
+
+    let email = authenticate(&state, &jar).await?;
+    let phone_number =
+        PhoneNumber::parse(&request.phone_number).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    state
+        .user_store
+        .set_phone_number(email.clone(), phone_number.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    // Tied to the number, so the code can only verify the number it was texted to.
+    let (login_attempt_id, two_fa_code) = store_2fa_code(
+        &email,
+        TwoFAPurpose::VerifyPhoneNumber(phone_number.clone()),
+        &state,
+    )
+    .await?;
+
+    send_2fa_sms(sms_client, &phone_number, &two_fa_code).await?;
+
+    let response = Json(TwoFactorAuthResponse {
+        message: "Verification code sent".to_owned(),
+        login_attempt_id: login_attempt_id.as_ref().to_owned(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[derive(Deserialize)]
+pub struct SetPhoneNumberRequest {
+    #[serde(rename = "phoneNumber")]
+    phone_number: String,
+}
@@ -4,32 +4,57 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, Email, Password, User, UserStoreError},
+    domain::{AuditEventKind, AuthAPIError, Email, NewUser, Password, UserId, UserStoreError},
+    utils::{
+        audit::{record_audit_result, RequestMetadata},
+        metrics::{outcome_label, record_signup},
+    },
 };
 
 #[tracing::instrument(name = "Signup", skip_all)]
 pub async fn signup(
     State(state): State<AppState>,
+    metadata: RequestMetadata,
     Json(request): Json<SignupRequest>,
 ) -> Result<impl IntoResponse, AuthAPIError> {
+    let actor = request.email.clone();
+    let result = create_user(&state, request).await;
+
+    record_audit_result(
+        &state,
+        &metadata,
+        AuditEventKind::Signup,
+        Some(actor),
+        result.as_ref().ok().map(|(user_id, _)| user_id),
+        &result,
+    )
+    .await;
+    record_signup(outcome_label(&result));
+
+    result.map(|(_, response)| response)
+}
+
+/// Returns the id of the new user along with the response.
+async fn create_user(
+    state: &AppState,
+    request: SignupRequest,
+) -> Result<(UserId, (StatusCode, Json<SignupResponse>)), AuthAPIError> {
     let email = request.email;
     let password = request.password;
 
-    let user = User::new(
+    let user = NewUser::new(
         Email::parse(&email).map_err(|_| AuthAPIError::InvalidCredentials)?,
         Password::parse(password).map_err(|_| AuthAPIError::InvalidCredentials)?,
         request.requires_2fa,
     );
 
-    let mut user_store = state.user_store.write().await;
-
-    match user_store.add_user(user).await {
-        Ok(()) => {
+    match state.user_store.add_user(user).await {
+        Ok(user) => {
             let response = Json(SignupResponse {
                 message: "User created successfully!".to_string(),
             });
 
-            Ok((StatusCode::CREATED, response))
+            Ok((*user.id(), (StatusCode::CREATED, response)))
         }
         Err(UserStoreError::UserAlreadyExists) => Err(AuthAPIError::UserAlreadyExists),
         Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
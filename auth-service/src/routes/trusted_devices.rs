@@ -0,0 +1,49 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+use crate::{app_state::AppState, domain::AuthAPIError, utils::auth::authenticate};
+
+#[tracing::instrument(name = "List trusted devices", skip_all)]
+pub async fn trusted_devices(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticate(&state, &jar).await?;
+
+    let devices = state
+        .trusted_device_store
+        .get_devices(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let response = Json(TrustedDevicesResponse {
+        devices: devices
+            .into_iter()
+            .map(|device| TrustedDeviceResponse {
+                id: device.id.as_ref().to_owned(),
+                user_agent: device.user_agent,
+                created_at: device.created_at.to_rfc3339(),
+                expires_at: device.expires_at.to_rfc3339(),
+            })
+            .collect(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrustedDevicesResponse {
+    pub devices: Vec<TrustedDeviceResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrustedDeviceResponse {
+    pub id: String,
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: String,
+}
@@ -0,0 +1,45 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, TwoFAChannel},
+    utils::auth::authenticate,
+};
+
+#[tracing::instrument(name = "Update 2FA channel", skip_all)]
+pub async fn update_2fa_channel(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<Update2FAChannelRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticate(&state, &jar).await?;
+    let two_fa_channel =
+        TwoFAChannel::parse(&request.channel).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let user_store = &state.user_store;
+
+    if two_fa_channel == TwoFAChannel::Sms {
+        let user = user_store
+            .get_user(email.clone())
+            .await
+            .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+        if !user.phone_verified() {
+            return Err(AuthAPIError::PhoneNumberNotVerified);
+        }
+    }
+
+    user_store
+        .update_two_fa_channel(email, two_fa_channel)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(StatusCode::OK)
+}
+
+#[derive(Deserialize)]
+pub struct Update2FAChannelRequest {
+    channel: String,
+}
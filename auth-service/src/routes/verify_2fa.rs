@@ -1,47 +1,177 @@
-use axum::{extract::State, response::IntoResponse, Json};
+use axum::{
+    extract::State,
+    http::{header, HeaderMap},
+    response::IntoResponse,
+    Json,
+};
 use axum_extra::extract::CookieJar;
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, Email, LoginAttemptId, TwoFACode},
-    utils::auth::generate_auth_cookie,
+    domain::{
+        AuditEventKind, AuthAPIError, Email, LoginAttemptId, TrustedDevice, TwoFACode,
+        TwoFACodeStoreError, TwoFAPurpose, UserId,
+    },
+    routes::record_login,
+    utils::{
+        audit::{record_audit_result, RequestMetadata},
+        auth::{generate_auth_cookie, generate_trusted_device_cookie},
+        metrics::{outcome_label, record_2fa_verification},
+    },
 };
 
 #[tracing::instrument(name = "Verify 2FA", skip_all)]
 pub async fn verify_2fa(
     State(state): State<AppState>,
+    metadata: RequestMetadata,
     jar: CookieJar,
+    headers: HeaderMap,
     Json(request): Json<Verify2FARequest>,
 ) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
+    let actor = request.email.clone();
+    let result = verify_2fa_code(&state, jar, &headers, request).await;
+
+    record_audit_result(
+        &state,
+        &metadata,
+        AuditEventKind::Verify2FA,
+        Some(actor),
+        result.as_ref().ok().map(|(user_id, _)| user_id),
+        &result,
+    )
+    .await;
+    record_2fa_verification(outcome_label(&result));
+
+    result.map(|(_, response)| response)
+}
+
+/// Returns the id of the user who logged in along with the response.
+async fn verify_2fa_code(
+    state: &AppState,
+    jar: CookieJar,
+    headers: &HeaderMap,
+    request: Verify2FARequest,
+) -> Result<(UserId, (CookieJar, StatusCode)), AuthAPIError> {
     let email = Email::parse(&request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
     let login_attempt_id = LoginAttemptId::parse(request.login_attempt_id)
         .map_err(|_| AuthAPIError::InvalidCredentials)?;
     let two_fa_code =
         TwoFACode::parse(request.two_fa_code).map_err(|_| AuthAPIError::InvalidCredentials)?;
 
-    {
-        let mut two_fa_code_store = state.two_fa_code_store.write().await;
-        let (stored_login_attempt_id, stored_two_fa_code) = two_fa_code_store
-            .get_code(&email)
-            .await
-            .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+    check_2fa_code(
+        state,
+        &email,
+        &login_attempt_id,
+        &two_fa_code,
+        TwoFAPurpose::Login,
+    )
+    .await?;
+
+    let user = state
+        .user_store
+        .get_user(email.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
 
-        two_fa_code_store
-            .remove_code(&email)
+    if user.is_disabled() {
+        return Err(AuthAPIError::AccountDisabled);
+    }
+
+    record_login(&email, state).await?;
+
+    let jwt_secret = &state.settings.auth.jwt_secret;
+    let secure = state.settings.tls.enabled;
+    let auth_cookie = generate_auth_cookie(user.id(), jwt_secret, secure)
+        .map_err(AuthAPIError::UnexpectedError)?;
+    let mut updated_jar = jar.add(auth_cookie);
+
+    if request.remember_device {
+        let user_agent = headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+        let device = TrustedDevice::new(
+            user_agent,
+            chrono::Duration::seconds(state.settings.trusted_device.ttl_seconds),
+        );
+
+        let trusted_device_cookie = generate_trusted_device_cookie(
+            &email,
+            &device.id,
+            device.expires_at,
+            jwt_secret,
+            secure,
+        )
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+        state
+            .trusted_device_store
+            .add_device(email, device)
             .await
             .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
 
-        if stored_login_attempt_id != login_attempt_id || stored_two_fa_code != two_fa_code {
-            return Err(AuthAPIError::IncorrectCredentials);
-        }
+        updated_jar = updated_jar.add(trusted_device_cookie);
     }
 
-    let auth_cookie = generate_auth_cookie(&email).map_err(AuthAPIError::UnexpectedError)?;
-    let updated_jar = jar.add(auth_cookie);
+    Ok((*user.id(), (updated_jar, StatusCode::OK)))
+}
+
+#[tracing::instrument(name = "Check 2FA code", skip_all)]
+pub(crate) async fn check_2fa_code(
+    state: &AppState,
+    email: &Email,
+    login_attempt_id: &LoginAttemptId,
+    two_fa_code: &TwoFACode,
+    purpose: TwoFAPurpose,
+) -> Result<(), AuthAPIError> {
+    let two_fa_code_store = &state.two_fa_code_store;
+    let (stored_email, stored_two_fa_code) = two_fa_code_store
+        .get_code(login_attempt_id)
+        .await
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    if &stored_email != email {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+
+    let stored_purpose = two_fa_code_store
+        .get_purpose(login_attempt_id)
+        .await
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    if stored_purpose != purpose {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
 
-    Ok((updated_jar, StatusCode::OK.into_response()))
+    // Counted before comparing, so concurrent guesses beyond the limit are never compared.
+    let attempts = match two_fa_code_store.record_attempt(login_attempt_id).await {
+        Ok(attempts) => attempts,
+        Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => {
+            return Err(AuthAPIError::IncorrectCredentials)
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+    let max_attempts = state.settings.two_fa.max_attempts;
+
+    if attempts > max_attempts || &stored_two_fa_code != two_fa_code {
+        if attempts >= max_attempts {
+            match two_fa_code_store.remove_code(login_attempt_id).await {
+                Ok(()) | Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => {}
+                Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+            }
+        }
+
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+
+    // Only the request that removes the code logs in, even if several presented it at once.
+    match two_fa_code_store.remove_code(login_attempt_id).await {
+        Ok(()) => Ok(()),
+        Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => Err(AuthAPIError::IncorrectCredentials),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -51,4 +181,6 @@ pub struct Verify2FARequest {
     login_attempt_id: String,
     #[serde(rename = "2FACode")]
     two_fa_code: String,
+    #[serde(rename = "rememberDevice", default)]
+    remember_device: bool,
 }
@@ -0,0 +1,93 @@
+use axum::{extract::State, response::IntoResponse, Json};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, MagicLink, MagicLinkStoreError, MagicLinkToken, UserStoreError},
+    routes::{complete_login, record_login_audit, requires_2fa, LoginResult},
+    utils::{
+        audit::RequestMetadata,
+        constants::MAGIC_LINK_NONCE_COOKIE_NAME,
+        metrics::{outcome_label, record_login_attempt},
+    },
+};
+
+#[tracing::instrument(name = "Verify magic link", skip_all)]
+pub async fn verify_magic_link(
+    State(state): State<AppState>,
+    metadata: RequestMetadata,
+    jar: CookieJar,
+    Json(request): Json<VerifyMagicLinkRequest>,
+) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
+    let link = take_magic_link(&state, &jar, request).await;
+
+    // Known once the link is taken, so a link burnt by another browser is recorded against it.
+    let actor = link
+        .as_ref()
+        .ok()
+        .map(|(link, _)| link.email.as_ref().to_owned());
+    let result = match link {
+        Ok((link, nonce)) => login_with_magic_link(&state, jar, link, &nonce).await,
+        Err(e) => Err(e),
+    };
+
+    record_login_audit(&state, &metadata, actor, &result).await;
+    record_login_attempt(match &result {
+        Ok(_) if requires_2fa(&result) => "2fa_required",
+        _ => outcome_label(&result),
+    });
+
+    result.map(|(_, response)| response)
+}
+
+/// Takes the link, along with the nonce of the browser opening it.
+async fn take_magic_link(
+    state: &AppState,
+    jar: &CookieJar,
+    request: VerifyMagicLinkRequest,
+) -> Result<(MagicLink, MagicLinkToken), AuthAPIError> {
+    let token =
+        MagicLinkToken::parse(request.token).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // Checked before taking the link, so opening it in a browser without a nonce does not burn it.
+    let nonce = jar
+        .get(MAGIC_LINK_NONCE_COOKIE_NAME)
+        .and_then(|cookie| MagicLinkToken::parse(cookie.value().to_owned()).ok())
+        .ok_or(AuthAPIError::IncorrectCredentials)?;
+
+    match state.magic_link_store.take_link(&token).await {
+        Ok(link) => Ok((link, nonce)),
+        Err(MagicLinkStoreError::LinkNotFound) => Err(AuthAPIError::IncorrectCredentials),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+async fn login_with_magic_link(
+    state: &AppState,
+    jar: CookieJar,
+    link: MagicLink,
+    nonce: &MagicLinkToken,
+) -> LoginResult {
+    // The link is already taken, so a browser holding another request's nonce burns it: a link
+    // that leaked to someone else's browser gets a single failed try.
+    if !link.matches_nonce(nonce) {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+
+    let user = match state.user_store.get_user(link.email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::IncorrectCredentials),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    let jar = jar.remove(Cookie::from(MAGIC_LINK_NONCE_COOKIE_NAME));
+
+    let response = complete_login(&user, state, jar).await?;
+    Ok((*user.id(), response))
+}
+
+#[derive(Deserialize)]
+pub struct VerifyMagicLinkRequest {
+    pub token: String,
+}
@@ -0,0 +1,55 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, LoginAttemptId, PhoneNumber, TwoFACode, TwoFAPurpose, UserStoreError},
+    routes::check_2fa_code,
+    utils::auth::authenticate,
+};
+
+#[tracing::instrument(name = "Verify phone number", skip_all)]
+pub async fn verify_phone_number(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<VerifyPhoneNumberRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticate(&state, &jar).await?;
+
+    let phone_number =
+        PhoneNumber::parse(&request.phone_number).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let login_attempt_id = LoginAttemptId::parse(request.login_attempt_id)
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let two_fa_code =
+        TwoFACode::parse(request.two_fa_code).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    check_2fa_code(
+        &state,
+        &email,
+        &login_attempt_id,
+        &two_fa_code,
+        TwoFAPurpose::VerifyPhoneNumber(phone_number.clone()),
+    )
+    .await?;
+
+    match state
+        .user_store
+        .verify_phone_number(email, phone_number)
+        .await
+    {
+        Ok(()) => Ok(StatusCode::OK),
+        Err(UserStoreError::InvalidCredentials) => Err(AuthAPIError::IncorrectCredentials),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct VerifyPhoneNumberRequest {
+    #[serde(rename = "phoneNumber")]
+    phone_number: String,
+    #[serde(rename = "loginAttemptId")]
+    login_attempt_id: String,
+    #[serde(rename = "2FACode")]
+    two_fa_code: String,
+}
@@ -3,16 +3,37 @@ use reqwest::StatusCode;
 use secrecy::Secret;
 use serde::Deserialize;
 
-use crate::{app_state::AppState, domain::AuthAPIError, utils::auth::validate_token};
+use crate::{
+    app_state::AppState,
+    domain::{AuditEventKind, AuditOutcome, AuthAPIError},
+    utils::{
+        audit::{record_audit_event, RequestMetadata},
+        auth::validate_token,
+    },
+};
 
 #[tracing::instrument(name = "Verify token", skip_all)]
 pub async fn verify_token(
     State(state): State<AppState>,
+    metadata: RequestMetadata,
     Json(request): Json<VerifyTokenRequest>,
 ) -> Result<impl IntoResponse, AuthAPIError> {
-    validate_token(&state, Secret::new(request.token))
-        .await
-        .map_err(|_| AuthAPIError::InvalidToken)?;
+    // Only failures are audited; app-service verifies a token on every request it serves.
+    if let Err(e) = validate_token(&state, Secret::new(request.token)).await {
+        record_audit_event(
+            &state,
+            &metadata,
+            AuditEventKind::VerifyToken,
+            AuditOutcome::Failure,
+            None,
+            None,
+            Some(e.to_string()),
+        )
+        .await;
+
+        return Err(AuthAPIError::InvalidToken);
+    }
+
     Ok(StatusCode::OK)
 }
 
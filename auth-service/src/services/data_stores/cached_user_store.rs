@@ -0,0 +1,339 @@
+use std::sync::Arc;
+
+use crate::domain::{
+    Email, NewUser, Password, PhoneNumber, StoredUser, TwoFAChannel, UserCache, UserFilter, UserId,
+    UserPage, UserQuery, UserStatus, UserStore, UserStoreError, UserUpdate,
+};
+
+/// Serves `get_cached_user` from `cache`, falling back to `inner` and caching what it returns.
+/// Every other read, `get_user` and `validate_user` included, goes to `inner`, so passwords and
+/// account status are always current.
+///
+/// Every write goes to `inner` first and then drops the user from the cache. Writes made around
+/// the wrapper, or by another instance sharing an in-process cache, are only seen by cached reads
+/// once the cached entry expires. A failing cache is logged and treated as a miss, never as an
+/// error.
+///
+/// Logins are the exception: they only move `last_login_at`, which nothing reads through the
+/// cache, so they leave the cached copy in place rather than emptying the cache on every login.
+pub struct CachedUserStore<S: UserStore> {
+    inner: S,
+    cache: Arc<dyn UserCache>,
+}
+
+impl<S: UserStore> CachedUserStore<S> {
+    pub fn new(inner: S, cache: Arc<dyn UserCache>) -> Self {
+        Self { inner, cache }
+    }
+
+    async fn invalidate(&self, email: &Email) {
+        if let Err(e) = self.cache.invalidate(email).await {
+            tracing::error!(error = ?e, "Failed to invalidate cached user");
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: UserStore> UserStore for CachedUserStore<S> {
+    async fn add_user(&self, user: NewUser) -> Result<StoredUser, UserStoreError> {
+        self.inner.add_user(user).await
+    }
+
+    async fn get_user(&self, email: Email) -> Result<StoredUser, UserStoreError> {
+        self.inner.get_user(email).await
+    }
+
+    #[tracing::instrument(name = "Retrieving user through cache", skip(self))]
+    async fn get_cached_user(&self, email: Email) -> Result<StoredUser, UserStoreError> {
+        match self.cache.get(&email).await {
+            Ok(Some(user)) => return Ok(user),
+            Ok(None) => {}
+            Err(e) => tracing::warn!(error = ?e, "Failed to read cached user"),
+        }
+
+        // Read before the store, so a write landing in between makes the insert a no-op.
+        let version = match self.cache.version(&email).await {
+            Ok(version) => Some(version),
+            Err(e) => {
+                tracing::warn!(error = ?e, "Failed to read cached user version");
+                None
+            }
+        };
+        let user = self.inner.get_user(email).await?;
+        if let Some(version) = version {
+            if let Err(e) = self.cache.insert(&user, version).await {
+                tracing::warn!(error = ?e, "Failed to cache user");
+            }
+        }
+
+        Ok(user)
+    }
+
+    async fn get_user_by_id(&self, id: &UserId) -> Result<StoredUser, UserStoreError> {
+        self.inner.get_user_by_id(id).await
+    }
+
+    async fn validate_user(
+        &self,
+        email: Email,
+        password: Password,
+    ) -> Result<StoredUser, UserStoreError> {
+        self.inner.validate_user(email, password).await
+    }
+
+    async fn update_requires_2fa(
+        &self,
+        email: Email,
+        requires_2fa: bool,
+    ) -> Result<(), UserStoreError> {
+        let result = self
+            .inner
+            .update_requires_2fa(email.clone(), requires_2fa)
+            .await;
+        self.invalidate(&email).await;
+        result
+    }
+
+    async fn set_phone_number(
+        &self,
+        email: Email,
+        phone_number: PhoneNumber,
+    ) -> Result<(), UserStoreError> {
+        let result = self
+            .inner
+            .set_phone_number(email.clone(), phone_number)
+            .await;
+        self.invalidate(&email).await;
+        result
+    }
+
+    async fn verify_phone_number(
+        &self,
+        email: Email,
+        phone_number: PhoneNumber,
+    ) -> Result<(), UserStoreError> {
+        let result = self
+            .inner
+            .verify_phone_number(email.clone(), phone_number)
+            .await;
+        self.invalidate(&email).await;
+        result
+    }
+
+    async fn update_two_fa_channel(
+        &self,
+        email: Email,
+        two_fa_channel: TwoFAChannel,
+    ) -> Result<(), UserStoreError> {
+        let result = self
+            .inner
+            .update_two_fa_channel(email.clone(), two_fa_channel)
+            .await;
+        self.invalidate(&email).await;
+        result
+    }
+
+    async fn update_status(&self, email: Email, status: UserStatus) -> Result<(), UserStoreError> {
+        let result = self.inner.update_status(email.clone(), status).await;
+        self.invalidate(&email).await;
+        result
+    }
+
+    async fn record_login(&self, email: Email) -> Result<(), UserStoreError> {
+        self.inner.record_login(email).await
+    }
+
+    async fn update_user(
+        &self,
+        id: &UserId,
+        update: UserUpdate,
+    ) -> Result<StoredUser, UserStoreError> {
+        // The cache is keyed by email, so the old one is needed in case the update changes it.
+        let old_email = self.inner.get_user_by_id(id).await?.email;
+        let result = self.inner.update_user(id, update).await;
+        self.invalidate(&old_email).await;
+        if let Ok(user) = &result {
+            self.invalidate(user.email()).await;
+        }
+        result
+    }
+
+    async fn delete_user(&self, id: &UserId) -> Result<(), UserStoreError> {
+        let email = self.inner.get_user_by_id(id).await?.email;
+        let result = self.inner.delete_user(id).await;
+        self.invalidate(&email).await;
+        result
+    }
+
+    async fn count_users(&self, filter: &UserFilter) -> Result<u64, UserStoreError> {
+        self.inner.count_users(filter).await
+    }
+
+    async fn list_users(&self, query: &UserQuery) -> Result<UserPage, UserStoreError> {
+        self.inner.list_users(query).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicBool, Ordering},
+        time::Duration,
+    };
+
+    use color_eyre::eyre::Result;
+    use secrecy::Secret;
+
+    use super::*;
+    use crate::services::{HashmapUserStore, InMemoryUserCache};
+
+    fn password() -> Password {
+        Password::parse(Secret::new("password123".to_owned())).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_serves_cached_user_until_written_through() {
+        let store = CachedUserStore::new(
+            HashmapUserStore::default(),
+            Arc::new(InMemoryUserCache::new(10, Duration::from_secs(60))),
+        );
+        let email = Email::parse("test@email.com").unwrap();
+        store
+            .add_user(NewUser::new(email.clone(), password(), false))
+            .await
+            .unwrap();
+
+        let cached = store.get_cached_user(email.clone()).await.unwrap();
+        assert!(!cached.requires_2fa());
+
+        // A write that bypasses the wrapper is not seen while the entry is cached.
+        store
+            .inner
+            .update_requires_2fa(email.clone(), true)
+            .await
+            .unwrap();
+        assert_eq!(store.get_cached_user(email.clone()).await, Ok(cached));
+
+        store
+            .update_two_fa_channel(email.clone(), TwoFAChannel::Email)
+            .await
+            .unwrap();
+        assert!(*store.get_cached_user(email).await.unwrap().requires_2fa());
+    }
+
+    #[tokio::test]
+    async fn test_reads_credentials_and_status_from_store() {
+        let store = CachedUserStore::new(
+            HashmapUserStore::default(),
+            Arc::new(InMemoryUserCache::new(10, Duration::from_secs(60))),
+        );
+        let email = Email::parse("test@email.com").unwrap();
+        let user = store
+            .add_user(NewUser::new(email.clone(), password(), false))
+            .await
+            .unwrap();
+        store.get_cached_user(email.clone()).await.unwrap();
+
+        // Changes made around the wrapper, as by another process, are seen at once.
+        let new_password = Password::parse(Secret::new("newpassword123".to_owned())).unwrap();
+        store
+            .inner
+            .update_user(
+                user.id(),
+                UserUpdate {
+                    password: Some(new_password.clone()),
+                    ..UserUpdate::default()
+                },
+            )
+            .await
+            .unwrap();
+        store
+            .inner
+            .update_status(email.clone(), UserStatus::Disabled)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.validate_user(email.clone(), password()).await,
+            Err(UserStoreError::InvalidCredentials)
+        );
+        assert!(store
+            .validate_user(email.clone(), new_password)
+            .await
+            .unwrap()
+            .is_disabled());
+        assert!(store.get_user(email).await.unwrap().is_disabled());
+    }
+
+    /// Invalidates the user between the store read and the first insert, as a concurrent
+    /// write would.
+    struct RacingCache {
+        inner: InMemoryUserCache,
+        raced: AtomicBool,
+    }
+
+    #[async_trait::async_trait]
+    impl UserCache for RacingCache {
+        async fn get(&self, email: &Email) -> Result<Option<StoredUser>> {
+            self.inner.get(email).await
+        }
+        async fn version(&self, email: &Email) -> Result<u64> {
+            self.inner.version(email).await
+        }
+        async fn insert(&self, user: &StoredUser, version: u64) -> Result<()> {
+            if !self.raced.swap(true, Ordering::SeqCst) {
+                self.inner.invalidate(user.email()).await?;
+            }
+            self.inner.insert(user, version).await
+        }
+        async fn invalidate(&self, email: &Email) -> Result<()> {
+            self.inner.invalidate(email).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_does_not_cache_user_read_before_concurrent_write() {
+        let store = CachedUserStore::new(
+            HashmapUserStore::default(),
+            Arc::new(RacingCache {
+                inner: InMemoryUserCache::new(10, Duration::from_secs(60)),
+                raced: AtomicBool::new(false),
+            }),
+        );
+        let email = Email::parse("test@email.com").unwrap();
+        store
+            .add_user(NewUser::new(email.clone(), password(), false))
+            .await
+            .unwrap();
+
+        assert!(!store
+            .get_cached_user(email.clone())
+            .await
+            .unwrap()
+            .requires_2fa());
+        // The write the invalidation belongs to.
+        store
+            .inner
+            .update_requires_2fa(email.clone(), true)
+            .await
+            .unwrap();
+
+        assert!(*store.get_cached_user(email).await.unwrap().requires_2fa());
+    }
+
+    #[tokio::test]
+    async fn test_keeps_cached_user_on_login() {
+        let cache = Arc::new(InMemoryUserCache::new(10, Duration::from_secs(60)));
+        let store = CachedUserStore::new(HashmapUserStore::default(), cache.clone());
+        let email = Email::parse("test@email.com").unwrap();
+        store
+            .add_user(NewUser::new(email.clone(), password(), false))
+            .await
+            .unwrap();
+        store.get_cached_user(email.clone()).await.unwrap();
+
+        store.record_login(email.clone()).await.unwrap();
+
+        assert!(cache.get(&email).await.unwrap().is_some());
+    }
+}
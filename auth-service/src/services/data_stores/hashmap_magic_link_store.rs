@@ -0,0 +1,91 @@
+use dashmap::DashMap;
+
+use crate::domain::{MagicLink, MagicLinkStore, MagicLinkStoreError, MagicLinkToken};
+
+#[derive(Default)]
+pub struct HashmapMagicLinkStore {
+    links: DashMap<String, MagicLink>,
+}
+
+#[async_trait::async_trait]
+impl MagicLinkStore for HashmapMagicLinkStore {
+    async fn add_link(
+        &self,
+        token: &MagicLinkToken,
+        link: MagicLink,
+    ) -> Result<(), MagicLinkStoreError> {
+        self.links.retain(|_, link| !link.is_expired());
+        self.links.insert(token.hash(), link);
+        Ok(())
+    }
+    async fn take_link(&self, token: &MagicLinkToken) -> Result<MagicLink, MagicLinkStoreError> {
+        self.links
+            .remove(&token.hash())
+            .map(|(_, link)| link)
+            .filter(|link| !link.is_expired())
+            .ok_or(MagicLinkStoreError::LinkNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use crate::{
+        domain::{Email, MagicLink, MagicLinkStore, MagicLinkStoreError, MagicLinkToken},
+        services::HashmapMagicLinkStore,
+    };
+
+    fn link(ttl: Duration) -> MagicLink {
+        MagicLink::new(
+            Email::parse("test@email.com").unwrap(),
+            &MagicLinkToken::default(),
+            ttl,
+        )
+    }
+
+    #[tokio::test]
+    async fn add_link_stores_token_hash() {
+        let store = HashmapMagicLinkStore::default();
+        let token = MagicLinkToken::default();
+
+        store
+            .add_link(&token, link(Duration::minutes(15)))
+            .await
+            .unwrap();
+
+        assert!(store.links.contains_key(&token.hash()));
+        assert!(!store.links.contains_key(token.as_ref()));
+    }
+
+    #[tokio::test]
+    async fn take_link_is_single_use() {
+        let store = HashmapMagicLinkStore::default();
+        let token = MagicLinkToken::default();
+        let magic_link = link(Duration::minutes(15));
+
+        store.add_link(&token, magic_link.clone()).await.unwrap();
+
+        assert_eq!(store.take_link(&token).await, Ok(magic_link));
+        assert_eq!(
+            store.take_link(&token).await,
+            Err(MagicLinkStoreError::LinkNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn take_link_rejects_expired_link() {
+        let store = HashmapMagicLinkStore::default();
+        let token = MagicLinkToken::default();
+
+        store
+            .add_link(&token, link(Duration::seconds(-1)))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.take_link(&token).await,
+            Err(MagicLinkStoreError::LinkNotFound)
+        );
+    }
+}
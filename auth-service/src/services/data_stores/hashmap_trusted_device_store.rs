@@ -0,0 +1,134 @@
+use dashmap::DashMap;
+
+use crate::domain::{
+    Email, TrustedDevice, TrustedDeviceId, TrustedDeviceStore, TrustedDeviceStoreError,
+};
+
+#[derive(Default)]
+pub struct HashmapTrustedDeviceStore {
+    devices: DashMap<Email, Vec<TrustedDevice>>,
+}
+
+#[async_trait::async_trait]
+impl TrustedDeviceStore for HashmapTrustedDeviceStore {
+    async fn add_device(
+        &self,
+        email: Email,
+        device: TrustedDevice,
+    ) -> Result<(), TrustedDeviceStoreError> {
+        let mut devices = self.devices.entry(email).or_default();
+        devices.retain(|device| !device.is_expired());
+        devices.push(device);
+        Ok(())
+    }
+    async fn get_devices(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<TrustedDevice>, TrustedDeviceStoreError> {
+        Ok(self
+            .devices
+            .get(email)
+            .map(|devices| {
+                devices
+                    .iter()
+                    .filter(|device| !device.is_expired())
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+    async fn contains_device(
+        &self,
+        email: &Email,
+        device_id: &TrustedDeviceId,
+    ) -> Result<bool, TrustedDeviceStoreError> {
+        Ok(self.devices.get(email).is_some_and(|devices| {
+            devices
+                .iter()
+                .any(|device| &device.id == device_id && !device.is_expired())
+        }))
+    }
+    async fn remove_device(
+        &self,
+        email: &Email,
+        device_id: &TrustedDeviceId,
+    ) -> Result<(), TrustedDeviceStoreError> {
+        let mut devices = self
+            .devices
+            .get_mut(email)
+            .ok_or(TrustedDeviceStoreError::DeviceNotFound)?;
+        let position = devices
+            .iter()
+            .position(|device| &device.id == device_id)
+            .ok_or(TrustedDeviceStoreError::DeviceNotFound)?;
+        devices.remove(position);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use crate::{
+        domain::{
+            Email, TrustedDevice, TrustedDeviceId, TrustedDeviceStore, TrustedDeviceStoreError,
+        },
+        services::HashmapTrustedDeviceStore,
+    };
+
+    fn email() -> Email {
+        Email::parse("test@email.com").unwrap()
+    }
+
+    #[tokio::test]
+    async fn add_and_get_devices() {
+        let store = HashmapTrustedDeviceStore::default();
+        let device = TrustedDevice::new(Some("Firefox".to_owned()), Duration::days(30));
+
+        store.add_device(email(), device.clone()).await.unwrap();
+
+        assert_eq!(
+            store.get_devices(&email()).await.unwrap(),
+            vec![device.clone()]
+        );
+        assert!(store.contains_device(&email(), &device.id).await.unwrap());
+        assert!(store
+            .get_devices(&Email::parse("other@email.com").unwrap())
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn expired_devices_are_not_trusted() {
+        let store = HashmapTrustedDeviceStore::default();
+        let device = TrustedDevice::new(None, Duration::seconds(-1));
+
+        store.add_device(email(), device.clone()).await.unwrap();
+
+        assert!(store.get_devices(&email()).await.unwrap().is_empty());
+        assert!(!store.contains_device(&email(), &device.id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn remove_device() {
+        let store = HashmapTrustedDeviceStore::default();
+        let device = TrustedDevice::new(None, Duration::days(30));
+
+        store.add_device(email(), device.clone()).await.unwrap();
+        store.remove_device(&email(), &device.id).await.unwrap();
+
+        assert!(!store.contains_device(&email(), &device.id).await.unwrap());
+        assert_eq!(
+            store.remove_device(&email(), &device.id).await,
+            Err(TrustedDeviceStoreError::DeviceNotFound)
+        );
+        assert_eq!(
+            store
+                .remove_device(&email(), &TrustedDeviceId::default())
+                .await,
+            Err(TrustedDeviceStoreError::DeviceNotFound)
+        );
+    }
+}
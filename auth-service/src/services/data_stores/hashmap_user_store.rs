@@ -1,33 +1,211 @@
-use std::collections::{hash_map::Entry, HashMap};
+use chrono::Utc;
+use dashmap::{mapref::entry::Entry, mapref::one::RefMut, DashMap};
 
-use crate::domain::{Email, Password, User, UserStore, UserStoreError};
+use crate::{
+    domain::{
+        Email, NewUser, Password, PhoneNumber, StoredUser, TwoFAChannel, UserCursor, UserFilter,
+        UserId, UserPage, UserQuery, UserStatus, UserStore, UserStoreError, UserUpdate,
+    },
+    services::PasswordHasher,
+};
 
-#[derive(Default, PartialEq, Debug)]
+#[derive(Default, Debug)]
 pub struct HashmapUserStore {
-    users: HashMap<Email, User>,
+    users: DashMap<Email, StoredUser>,
+    hasher: PasswordHasher,
 }
 
 #[async_trait::async_trait]
 impl UserStore for HashmapUserStore {
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
+    async fn add_user(&self, user: NewUser) -> Result<StoredUser, UserStoreError> {
+        // Hash before taking the entry so the map shard isn't locked while Argon2 runs.
+        let password_hash = self
+            .hasher
+            .hash(user.password())
+            .await
+            .map_err(UserStoreError::UnexpectedError)?;
+
         match self.users.entry(user.email().to_owned()) {
-            Entry::Vacant(entry) => {
-                entry.insert(user);
-                Ok(())
-            }
+            Entry::Vacant(entry) => Ok(entry.insert(StoredUser::new(user, password_hash)).clone()),
             Entry::Occupied(_) => Err(UserStoreError::UserAlreadyExists),
         }
     }
 
-    async fn get_user(&self, email: Email) -> Result<User, UserStoreError> {
+    async fn get_user(&self, email: Email) -> Result<StoredUser, UserStoreError> {
         self.users
             .get(&email)
-            .cloned()
+            .map(|user| user.clone())
+            .ok_or(UserStoreError::UserNotFound)
+    }
+
+    async fn get_user_by_id(&self, id: &UserId) -> Result<StoredUser, UserStoreError> {
+        self.users
+            .iter()
+            .find(|user| user.id() == id)
+            .map(|user| user.clone())
             .ok_or(UserStoreError::UserNotFound)
     }
 
-    async fn validate_user(&self, email: Email, password: Password) -> Result<(), UserStoreError> {
-        self.get_user(email).await?.validate_password(password)
+    async fn validate_user(
+        &self,
+        email: Email,
+        password: Password,
+    ) -> Result<StoredUser, UserStoreError> {
+        let user = self.get_user(email).await?;
+
+        self.hasher
+            .verify(user.password_hash(), &password)
+            .await
+            .map_err(|_| UserStoreError::InvalidCredentials)?;
+
+        Ok(user)
+    }
+
+    async fn update_requires_2fa(
+        &self,
+        email: Email,
+        requires_2fa: bool,
+    ) -> Result<(), UserStoreError> {
+        let mut user = self.get_user_mut(&email)?;
+        user.requires_2fa = requires_2fa;
+        user.updated_at = Utc::now();
+        Ok(())
+    }
+
+    async fn set_phone_number(
+        &self,
+        email: Email,
+        phone_number: PhoneNumber,
+    ) -> Result<(), UserStoreError> {
+        let mut user = self.get_user_mut(&email)?;
+        user.phone_number = Some(phone_number);
+        user.phone_verified = false;
+        user.two_fa_channel = TwoFAChannel::Email;
+        user.updated_at = Utc::now();
+        Ok(())
+    }
+
+    async fn verify_phone_number(
+        &self,
+        email: Email,
+        phone_number: PhoneNumber,
+    ) -> Result<(), UserStoreError> {
+        let mut user = self.get_user_mut(&email)?;
+        if user.phone_number.as_ref() != Some(&phone_number) {
+            return Err(UserStoreError::InvalidCredentials);
+        }
+        user.phone_verified = true;
+        user.updated_at = Utc::now();
+        Ok(())
+    }
+
+    async fn update_two_fa_channel(
+        &self,
+        email: Email,
+        two_fa_channel: TwoFAChannel,
+    ) -> Result<(), UserStoreError> {
+        let mut user = self.get_user_mut(&email)?;
+        user.two_fa_channel = two_fa_channel;
+        user.updated_at = Utc::now();
+        Ok(())
+    }
+
+    async fn update_status(&self, email: Email, status: UserStatus) -> Result<(), UserStoreError> {
+        let mut user = self.get_user_mut(&email)?;
+        user.status = status;
+        user.updated_at = Utc::now();
+        Ok(())
+    }
+
+    async fn record_login(&self, email: Email) -> Result<(), UserStoreError> {
+        self.get_user_mut(&email)?.last_login_at = Some(Utc::now());
+        Ok(())
+    }
+
+    async fn update_user(
+        &self,
+        id: &UserId,
+        update: UserUpdate,
+    ) -> Result<StoredUser, UserStoreError> {
+        let password_hash = match &update.password {
+            Some(password) => Some(
+                self.hasher
+                    .hash(password)
+                    .await
+                    .map_err(UserStoreError::UnexpectedError)?,
+            ),
+            None => None,
+        };
+
+        let mut user = self.get_user_by_id(id).await?;
+        let old_email = user.email.clone();
+        if let Some(email) = update.email {
+            user.email = email;
+        }
+        if let Some(password_hash) = password_hash {
+            user.password_hash = password_hash;
+        }
+        if let Some(requires_2fa) = update.requires_2fa {
+            user.requires_2fa = requires_2fa;
+        }
+        if let Some(status) = update.status {
+            user.status = status;
+        }
+        user.updated_at = Utc::now();
+
+        match user.email == old_email {
+            true => {
+                self.users.insert(old_email, user.clone());
+            }
+            false => {
+                match self.users.entry(user.email.clone()) {
+                    Entry::Vacant(entry) => entry.insert(user.clone()),
+                    Entry::Occupied(_) => return Err(UserStoreError::UserAlreadyExists),
+                };
+                self.users.remove(&old_email);
+            }
+        }
+
+        Ok(user)
+    }
+
+    async fn delete_user(&self, id: &UserId) -> Result<(), UserStoreError> {
+        let user = self.get_user_by_id(id).await?;
+        self.users.remove(user.email());
+        Ok(())
+    }
+
+    async fn count_users(&self, filter: &UserFilter) -> Result<u64, UserStoreError> {
+        Ok(self
+            .users
+            .iter()
+            .filter(|user| filter.matches(user))
+            .count() as u64)
+    }
+
+    async fn list_users(&self, query: &UserQuery) -> Result<UserPage, UserStoreError> {
+        let mut users = self
+            .users
+            .iter()
+            .filter(|user| {
+                query.filter.matches(user)
+                    && query
+                        .after
+                        .is_none_or(|after| UserCursor::from(user.value()) > after)
+            })
+            .map(|user| user.clone())
+            .collect::<Vec<_>>();
+        users.sort_by_key(|user| UserCursor::from(user));
+
+        Ok(UserPage::from_rows(users, query.limit))
+    }
+}
+
+impl HashmapUserStore {
+    fn get_user_mut(&self, email: &Email) -> Result<RefMut<'_, Email, StoredUser>, UserStoreError> {
+        self.users
+            .get_mut(email)
+            .ok_or(UserStoreError::UserNotFound)
     }
 }
 
@@ -35,10 +213,10 @@ impl UserStore for HashmapUserStore {
 mod tests {
     use super::*;
     use once_cell::sync::Lazy;
-    use secrecy::Secret;
+    use secrecy::{ExposeSecret, Secret};
 
-    static TEST_USER: Lazy<User> = Lazy::new(|| {
-        User::new(
+    static TEST_USER: Lazy<NewUser> = Lazy::new(|| {
+        NewUser::new(
             Email::parse("abc@test.com").unwrap(),
             Password::parse(Secret::new("MySecretPassword".to_owned())).unwrap(),
             false,
@@ -47,25 +225,40 @@ mod tests {
 
     #[tokio::test]
     async fn test_add_user() {
-        let mut hashmap_user_store = HashmapUserStore::default();
+        let hashmap_user_store = HashmapUserStore::default();
 
-        assert_eq!(hashmap_user_store.add_user(TEST_USER.clone()).await, Ok(()));
+        let stored = hashmap_user_store
+            .add_user(TEST_USER.clone())
+            .await
+            .unwrap();
 
+        assert_eq!(hashmap_user_store.users.len(), 1);
+        assert_eq!(
+            Some(stored.clone()),
+            hashmap_user_store
+                .users
+                .get(TEST_USER.email())
+                .map(|user| user.clone())
+        );
+        assert_ne!(
+            stored.password_hash().as_ref().expose_secret(),
+            TEST_USER.password().as_ref().expose_secret()
+        );
         assert_eq!(
-            HashMap::from([(TEST_USER.email().to_owned(), TEST_USER.clone())]),
-            hashmap_user_store.users
+            Err(UserStoreError::UserAlreadyExists),
+            hashmap_user_store.add_user(TEST_USER.clone()).await
         );
     }
 
     #[tokio::test]
     async fn test_get_user() {
-        let mut hashmap_user_store = HashmapUserStore::default();
-        hashmap_user_store
+        let hashmap_user_store = HashmapUserStore::default();
+        let stored = hashmap_user_store
             .add_user(TEST_USER.clone())
             .await
             .unwrap();
         assert_eq!(
-            TEST_USER.clone(),
+            stored,
             hashmap_user_store
                 .get_user(TEST_USER.email().to_owned())
                 .await
@@ -81,14 +274,14 @@ mod tests {
 
     #[tokio::test]
     async fn test_validate_user() {
-        let mut hashmap_user_store = HashmapUserStore::default();
-        hashmap_user_store
+        let hashmap_user_store = HashmapUserStore::default();
+        let stored = hashmap_user_store
             .add_user(TEST_USER.clone())
             .await
             .unwrap();
 
         assert_eq!(
-            Ok(()),
+            Ok(stored),
             hashmap_user_store
                 .validate_user(
                     TEST_USER.email().to_owned(),
@@ -106,4 +299,116 @@ mod tests {
                 .await
         );
     }
+
+    #[tokio::test]
+    async fn test_update_requires_2fa() {
+        let hashmap_user_store = HashmapUserStore::default();
+        hashmap_user_store
+            .add_user(TEST_USER.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            Ok(()),
+            hashmap_user_store
+                .update_requires_2fa(TEST_USER.email().to_owned(), true)
+                .await
+        );
+        assert!(hashmap_user_store
+            .get_user(TEST_USER.email().to_owned())
+            .await
+            .unwrap()
+            .requires_2fa());
+        assert_eq!(
+            Err(UserStoreError::UserNotFound),
+            hashmap_user_store
+                .update_requires_2fa(Email::parse("not_found@test.com").unwrap(), true)
+                .await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_and_verify_phone_number() {
+        let hashmap_user_store = HashmapUserStore::default();
+        hashmap_user_store
+            .add_user(TEST_USER.clone())
+            .await
+            .unwrap();
+        let phone_number = PhoneNumber::parse("+40712345678").unwrap();
+
+        hashmap_user_store
+            .set_phone_number(TEST_USER.email().to_owned(), phone_number.clone())
+            .await
+            .unwrap();
+        hashmap_user_store
+            .update_two_fa_channel(TEST_USER.email().to_owned(), TwoFAChannel::Sms)
+            .await
+            .unwrap();
+        let user = hashmap_user_store
+            .get_user(TEST_USER.email().to_owned())
+            .await
+            .unwrap();
+        assert_eq!(user.phone_number(), &Some(phone_number.clone()));
+        assert!(!user.phone_verified());
+        assert_eq!(user.sms_recipient(), None);
+
+        assert_eq!(
+            Err(UserStoreError::InvalidCredentials),
+            hashmap_user_store
+                .verify_phone_number(
+                    TEST_USER.email().to_owned(),
+                    PhoneNumber::parse("+40787654321").unwrap()
+                )
+                .await
+        );
+        assert_eq!(
+            Ok(()),
+            hashmap_user_store
+                .verify_phone_number(TEST_USER.email().to_owned(), phone_number.clone())
+                .await
+        );
+        let user = hashmap_user_store
+            .get_user(TEST_USER.email().to_owned())
+            .await
+            .unwrap();
+        assert_eq!(user.sms_recipient(), Some(&phone_number));
+
+        hashmap_user_store
+            .set_phone_number(
+                TEST_USER.email().to_owned(),
+                PhoneNumber::parse("+40787654321").unwrap(),
+            )
+            .await
+            .unwrap();
+        let user = hashmap_user_store
+            .get_user(TEST_USER.email().to_owned())
+            .await
+            .unwrap();
+        assert!(!user.phone_verified());
+        assert_eq!(user.two_fa_channel(), &TwoFAChannel::Email);
+    }
+
+    #[tokio::test]
+    async fn test_get_user_by_id_and_update_status() {
+        let hashmap_user_store = HashmapUserStore::default();
+        let stored = hashmap_user_store
+            .add_user(TEST_USER.clone())
+            .await
+            .unwrap();
+
+        hashmap_user_store
+            .update_status(TEST_USER.email().to_owned(), UserStatus::Disabled)
+            .await
+            .unwrap();
+        let user = hashmap_user_store
+            .get_user_by_id(stored.id())
+            .await
+            .unwrap();
+        assert!(user.is_disabled());
+        assert!(user.updated_at() > stored.updated_at());
+        assert_eq!(
+            Err(UserStoreError::UserNotFound),
+            hashmap_user_store.get_user_by_id(&UserId::default()).await
+        );
+    }
 }
@@ -1,18 +1,19 @@
-use std::collections::HashSet;
-
+use chrono::{DateTime, Utc};
 use color_eyre::eyre::Result;
+use dashmap::{DashMap, DashSet};
 use secrecy::{ExposeSecret, Secret};
 
-use crate::domain::BannedTokenStore;
+use crate::domain::{BannedTokenStore, UserId};
 
 #[derive(Clone, Default)]
 pub struct HashsetBannedTokenStore {
-    store: HashSet<String>,
+    store: DashSet<String>,
+    revoked_users: DashMap<UserId, DateTime<Utc>>,
 }
 
 #[async_trait::async_trait]
 impl BannedTokenStore for HashsetBannedTokenStore {
-    async fn add_token(&mut self, token: Secret<String>) -> Result<()> {
+    async fn add_token(&self, token: Secret<String>) -> Result<()> {
         self.store.insert(token.expose_secret().to_owned());
         Ok(())
     }
@@ -20,6 +21,22 @@ impl BannedTokenStore for HashsetBannedTokenStore {
     async fn contains_token(&self, token: Secret<String>) -> Result<bool> {
         Ok(self.store.contains(token.expose_secret()))
     }
+
+    async fn revoke_user_tokens(
+        &self,
+        user_id: &UserId,
+        issued_before: DateTime<Utc>,
+    ) -> Result<()> {
+        self.revoked_users
+            .entry(*user_id)
+            .and_modify(|cutoff| *cutoff = (*cutoff).max(issued_before))
+            .or_insert(issued_before);
+        Ok(())
+    }
+
+    async fn user_tokens_revoked_before(&self, user_id: &UserId) -> Result<Option<DateTime<Utc>>> {
+        Ok(self.revoked_users.get(user_id).map(|cutoff| *cutoff))
+    }
 }
 
 #[cfg(test)]
@@ -30,25 +47,55 @@ mod tests {
 
     #[tokio::test]
     async fn test_ban_token() {
-        let mut hashset_banned_token_store = HashsetBannedTokenStore::default();
+        let hashset_banned_token_store = HashsetBannedTokenStore::default();
 
-        let _ = hashset_banned_token_store.add_token(Secret::new(JWT.to_owned())).await;
+        let _ = hashset_banned_token_store
+            .add_token(Secret::new(JWT.to_owned()))
+            .await;
 
-        assert_eq!(
-            HashSet::from([JWT.to_owned()]),
-            hashset_banned_token_store.store
-        );
+        assert_eq!(hashset_banned_token_store.store.len(), 1);
+        assert!(hashset_banned_token_store.store.contains(JWT));
     }
 
     #[tokio::test]
     async fn test_is_banned() {
-        let mut hashset_banned_token_store = HashsetBannedTokenStore::default();
+        let hashset_banned_token_store = HashsetBannedTokenStore::default();
 
-        let _ = hashset_banned_token_store.add_token(Secret::new(JWT.to_owned())).await;
+        let _ = hashset_banned_token_store
+            .add_token(Secret::new(JWT.to_owned()))
+            .await;
 
         assert!(hashset_banned_token_store
             .contains_token(Secret::new(JWT.to_owned()))
             .await
             .unwrap());
     }
+
+    #[tokio::test]
+    async fn test_revoke_user_tokens_keeps_the_latest_cutoff() {
+        let store = HashsetBannedTokenStore::default();
+        let user_id = UserId::default();
+        let earlier = Utc::now() - chrono::Duration::minutes(5);
+        let later = Utc::now();
+
+        assert_eq!(
+            store.user_tokens_revoked_before(&user_id).await.unwrap(),
+            None
+        );
+
+        store.revoke_user_tokens(&user_id, later).await.unwrap();
+        store.revoke_user_tokens(&user_id, earlier).await.unwrap();
+
+        assert_eq!(
+            store.user_tokens_revoked_before(&user_id).await.unwrap(),
+            Some(later)
+        );
+        assert_eq!(
+            store
+                .user_tokens_revoked_before(&UserId::default())
+                .await
+                .unwrap(),
+            None
+        );
+    }
 }
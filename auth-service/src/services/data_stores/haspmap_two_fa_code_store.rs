@@ -1,50 +1,182 @@
-use std::collections::HashMap;
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
 
-use crate::domain::{email::Email, LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError};
+use chrono::{DateTime, Utc};
+use dashmap::{mapref::one::RefMut, DashMap};
+
+use crate::{
+    domain::{
+        email::Email, LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError, TwoFAPurpose,
+        TwoFAResend,
+    },
+    utils::constants::DEFAULT_TWO_FA_MAX_PENDING_ATTEMPTS,
+};
 
-#[derive(Default)]
 pub struct HashmapTwoFACodeStore {
-    codes: HashMap<Email, (LoginAttemptId, TwoFACode)>,
+    codes: DashMap<LoginAttemptId, PendingCode>,
+    // Always locked before `codes` when both are needed, so the two maps cannot deadlock.
+    pending: DashMap<Email, VecDeque<LoginAttemptId>>,
+    issued: DashMap<Email, (u32, Instant)>,
+    max_pending_attempts: usize,
+}
+
+impl Default for HashmapTwoFACodeStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_TWO_FA_MAX_PENDING_ATTEMPTS)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct PendingCode {
+    email: Email,
+    code: TwoFACode,
+    purpose: TwoFAPurpose,
+    attempts: u32,
+    resends: u32,
+    last_sent_at: DateTime<Utc>,
+}
+
+impl HashmapTwoFACodeStore {
+    /// Keeps at most `max_pending_attempts` codes per email, dropping the oldest first.
+    pub fn new(max_pending_attempts: usize) -> Self {
+        Self {
+            codes: DashMap::new(),
+            pending: DashMap::new(),
+            issued: DashMap::new(),
+            max_pending_attempts,
+        }
+    }
+
+    fn get_pending_code(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<RefMut<'_, LoginAttemptId, PendingCode>, TwoFACodeStoreError> {
+        self.codes
+            .get_mut(login_attempt_id)
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    }
 }
 
 #[async_trait::async_trait]
 impl TwoFACodeStore for HashmapTwoFACodeStore {
     async fn add_code(
-        &mut self,
+        &self,
         email: Email,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
+        purpose: TwoFAPurpose,
     ) -> Result<(), TwoFACodeStoreError> {
-        self.codes.insert(email, (login_attempt_id, code));
+        let mut pending = self.pending.entry(email.clone()).or_default();
+        while pending.len() >= self.max_pending_attempts {
+            if let Some(oldest) = pending.pop_front() {
+                self.codes.remove(&oldest);
+            }
+        }
+        pending.push_back(login_attempt_id.clone());
+
+        self.codes.insert(
+            login_attempt_id,
+            PendingCode {
+                email,
+                code,
+                purpose,
+                attempts: 0,
+                resends: 0,
+                last_sent_at: Utc::now(),
+            },
+        );
         Ok(())
     }
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
-        self.codes.remove(email);
+    async fn remove_code(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let (_, removed) = self
+            .codes
+            .remove(login_attempt_id)
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+        if let Some(mut pending) = self.pending.get_mut(&removed.email) {
+            pending.retain(|id| id != login_attempt_id);
+        }
         Ok(())
     }
     async fn get_code(
         &self,
-        email: &Email,
-    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(Email, TwoFACode), TwoFACodeStoreError> {
+        self.codes
+            .get(login_attempt_id)
+            .map(|pending| (pending.email.clone(), pending.code.clone()))
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    }
+    async fn get_purpose(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<TwoFAPurpose, TwoFACodeStoreError> {
         self.codes
-            .get(email)
+            .get(login_attempt_id)
+            .map(|pending| pending.purpose.clone())
             .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)
-            .cloned()
+    }
+    async fn record_attempt(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<u32, TwoFACodeStoreError> {
+        let mut pending = self.get_pending_code(login_attempt_id)?;
+        pending.attempts += 1;
+        Ok(pending.attempts)
+    }
+    async fn record_code_issued(&self, email: &Email) -> Result<u32, TwoFACodeStoreError> {
+        let now = Instant::now();
+        let mut issued = self.issued.entry(email.clone()).or_insert((0, now));
+        let (count, window_start) = &mut *issued;
+        if now.duration_since(*window_start) >= ISSUE_WINDOW {
+            *count = 0;
+            *window_start = now;
+        }
+        *count += 1;
+        Ok(*count)
+    }
+    async fn record_resend(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+        cooldown: chrono::Duration,
+        max_resends: u32,
+    ) -> Result<TwoFAResend, TwoFACodeStoreError> {
+        // The entry stays locked from the check to the update.
+        let mut pending = self.get_pending_code(login_attempt_id)?;
+        let now = Utc::now();
+        let retry_after = (pending.last_sent_at + cooldown - now).num_seconds();
+        if retry_after > 0 {
+            return Ok(TwoFAResend::RetryAfter(retry_after));
+        }
+        if pending.resends >= max_resends {
+            return Ok(TwoFAResend::LimitReached);
+        }
+
+        pending.resends += 1;
+        pending.last_sent_at = now;
+        Ok(TwoFAResend::Recorded)
     }
 }
 
+const ISSUE_WINDOW: Duration = Duration::from_secs(60 * 60);
+
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use chrono::Duration;
 
     use crate::{
-        domain::{Email, LoginAttemptId, TwoFACode, TwoFACodeStore},
+        domain::{Email, LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFAPurpose, TwoFAResend},
         services::HashmapTwoFACodeStore,
+        utils::constants::DEFAULT_TWO_FA_MAX_PENDING_ATTEMPTS,
     };
 
     #[tokio::test]
     async fn add_code() {
-        let mut store = HashmapTwoFACodeStore::default();
+        let store = HashmapTwoFACodeStore::default();
         let login_attempt_id = LoginAttemptId::default();
         let two_facode = TwoFACode::default();
         let _ = store
@@ -52,80 +184,274 @@ mod tests {
                 Email::parse("test@email.com").unwrap(),
                 login_attempt_id.clone(),
                 two_facode.clone(),
+                TwoFAPurpose::Login,
+            )
+            .await;
+
+        let pending = store.codes.get(&login_attempt_id).unwrap();
+
+        assert_eq!(store.codes.len(), 1);
+        assert_eq!(pending.email, Email::parse("test@email.com").unwrap());
+        assert_eq!(pending.code, two_facode);
+        assert_eq!(pending.purpose, TwoFAPurpose::Login);
+        assert_eq!(pending.attempts, 0);
+        assert_eq!(pending.resends, 0);
+    }
+
+    #[tokio::test]
+    async fn add_code_keeps_concurrent_attempts() {
+        let store = HashmapTwoFACodeStore::default();
+        let email = Email::parse("test@email.com").unwrap();
+        let first_attempt_id = LoginAttemptId::default();
+        let first_code = TwoFACode::default();
+        let second_attempt_id = LoginAttemptId::default();
+        let second_code = TwoFACode::default();
+
+        let _ = store
+            .add_code(
+                email.clone(),
+                first_attempt_id.clone(),
+                first_code.clone(),
+                TwoFAPurpose::Login,
+            )
+            .await;
+        let _ = store
+            .add_code(
+                email.clone(),
+                second_attempt_id.clone(),
+                second_code.clone(),
+                TwoFAPurpose::Login,
             )
             .await;
 
         assert_eq!(
-            store.codes,
-            HashMap::from([(
-                Email::parse("test@email.com").unwrap(),
-                (login_attempt_id, two_facode)
-            )])
+            store.get_code(&first_attempt_id).await.unwrap(),
+            (email.clone(), first_code)
+        );
+        assert_eq!(
+            store.get_code(&second_attempt_id).await.unwrap(),
+            (email, second_code)
+        );
+    }
+
+    #[tokio::test]
+    async fn add_code_evicts_oldest_attempt_over_limit() {
+        let store = HashmapTwoFACodeStore::default();
+        let email = Email::parse("test@email.com").unwrap();
+        let attempt_ids: Vec<LoginAttemptId> = (0..=DEFAULT_TWO_FA_MAX_PENDING_ATTEMPTS)
+            .map(|_| LoginAttemptId::default())
+            .collect();
+
+        for login_attempt_id in attempt_ids.iter() {
+            let _ = store
+                .add_code(
+                    email.clone(),
+                    login_attempt_id.clone(),
+                    TwoFACode::default(),
+                    TwoFAPurpose::Login,
+                )
+                .await;
+        }
+
+        assert_eq!(store.codes.len(), DEFAULT_TWO_FA_MAX_PENDING_ATTEMPTS);
+        assert_eq!(
+            store.get_code(&attempt_ids[0]).await,
+            Err(crate::domain::TwoFACodeStoreError::LoginAttemptIdNotFound)
         );
+        assert!(store.get_code(&attempt_ids[1]).await.is_ok());
     }
 
     #[tokio::test]
     async fn get_code_found() {
-        let mut store = HashmapTwoFACodeStore::default();
+        let store = HashmapTwoFACodeStore::default();
         let login_attempt_id = LoginAttemptId::default();
         let two_facode = TwoFACode::default();
         let email = Email::parse("test@email.com").unwrap();
 
         let _ = store
-            .add_code(email.clone(), login_attempt_id.clone(), two_facode.clone())
+            .add_code(
+                email.clone(),
+                login_attempt_id.clone(),
+                two_facode.clone(),
+                TwoFAPurpose::Login,
+            )
             .await;
 
         assert_eq!(
-            store.get_code(&email).await.unwrap(),
-            (login_attempt_id, two_facode)
+            store.get_code(&login_attempt_id).await.unwrap(),
+            (email, two_facode)
         );
     }
 
     #[tokio::test]
     async fn get_code_not_found() {
-        let mut store = HashmapTwoFACodeStore::default();
+        let store = HashmapTwoFACodeStore::default();
         let login_attempt_id = LoginAttemptId::default();
         let two_facode = TwoFACode::default();
         let email = Email::parse("test@email.com").unwrap();
 
         let _ = store
-            .add_code(email.clone(), login_attempt_id.clone(), two_facode.clone())
+            .add_code(
+                email.clone(),
+                login_attempt_id.clone(),
+                two_facode.clone(),
+                TwoFAPurpose::Login,
+            )
             .await;
 
         assert_eq!(
-            store
-                .get_code(&Email::parse("not_test@email.com").unwrap())
-                .await,
+            store.get_code(&LoginAttemptId::default()).await,
             Err(crate::domain::TwoFACodeStoreError::LoginAttemptIdNotFound)
         );
     }
 
     #[tokio::test]
     async fn remove_code() {
-        let mut store = HashmapTwoFACodeStore::default();
+        let store = HashmapTwoFACodeStore::default();
         let login_attempt_id = LoginAttemptId::default();
         let two_facode = TwoFACode::default();
         let email = Email::parse("test@email.com").unwrap();
 
         let _ = store
-            .add_code(email.clone(), login_attempt_id.clone(), two_facode.clone())
+            .add_code(
+                email.clone(),
+                login_attempt_id.clone(),
+                two_facode.clone(),
+                TwoFAPurpose::Login,
+            )
             .await;
 
+        assert_eq!(
+            store.get_code(&login_attempt_id).await.unwrap(),
+            (email.clone(), two_facode)
+        );
+
+        assert_eq!(store.remove_code(&login_attempt_id).await, Ok(()));
+        assert_eq!(
+            store.remove_code(&login_attempt_id).await,
+            Err(crate::domain::TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+
+        assert_eq!(
+            store.get_code(&login_attempt_id).await,
+            Err(crate::domain::TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+        assert!(store.pending.get(&email).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn record_attempt() {
+        let store = HashmapTwoFACodeStore::default();
+        let email = Email::parse("test@email.com").unwrap();
+        let login_attempt_id = LoginAttemptId::default();
+
+        assert_eq!(
+            store.record_attempt(&login_attempt_id).await,
+            Err(crate::domain::TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+
+        let _ = store
+            .add_code(
+                email.clone(),
+                login_attempt_id.clone(),
+                TwoFACode::default(),
+                TwoFAPurpose::Login,
+            )
+            .await;
+
+        assert_eq!(store.record_attempt(&login_attempt_id).await, Ok(1));
+        assert_eq!(store.record_attempt(&login_attempt_id).await, Ok(2));
+
+        let other_attempt_id = LoginAttemptId::default();
+        let _ = store
+            .add_code(
+                email.clone(),
+                other_attempt_id.clone(),
+                TwoFACode::default(),
+                TwoFAPurpose::Login,
+            )
+            .await;
+
+        assert_eq!(store.record_attempt(&other_attempt_id).await, Ok(1));
+    }
+
+    #[tokio::test]
+    async fn record_code_issued() {
+        let store = HashmapTwoFACodeStore::default();
+        let email = Email::parse("test@email.com").unwrap();
+
+        assert_eq!(store.record_code_issued(&email).await, Ok(1));
+        assert_eq!(store.record_code_issued(&email).await, Ok(2));
         assert_eq!(
             store
-                .get_code(&Email::parse("test@email.com").unwrap())
-                .await
-                .unwrap(),
-            (login_attempt_id, two_facode)
+                .record_code_issued(&Email::parse("other@email.com").unwrap())
+                .await,
+            Ok(1)
         );
+    }
 
-        let _ = store.remove_code(&email).await;
+    #[tokio::test]
+    async fn record_resend() {
+        let store = HashmapTwoFACodeStore::default();
+        let email = Email::parse("test@email.com").unwrap();
+        let login_attempt_id = LoginAttemptId::default();
 
         assert_eq!(
             store
-                .get_code(&Email::parse("test@email.com").unwrap())
+                .record_resend(&login_attempt_id, Duration::zero(), 1)
                 .await,
             Err(crate::domain::TwoFACodeStoreError::LoginAttemptIdNotFound)
         );
+
+        let _ = store
+            .add_code(
+                email.clone(),
+                login_attempt_id.clone(),
+                TwoFACode::default(),
+                TwoFAPurpose::Login,
+            )
+            .await;
+
+        let first_sent_at = store.codes.get(&login_attempt_id).unwrap().last_sent_at;
+
+        assert_eq!(
+            store
+                .record_resend(&login_attempt_id, Duration::zero(), 1)
+                .await,
+            Ok(TwoFAResend::Recorded)
+        );
+
+        let pending = store.codes.get(&login_attempt_id).unwrap().clone();
+        assert_eq!(pending.resends, 1);
+        assert!(pending.last_sent_at >= first_sent_at);
+
+        assert_eq!(
+            store
+                .record_resend(&login_attempt_id, Duration::zero(), 1)
+                .await,
+            Ok(TwoFAResend::LimitReached)
+        );
+    }
+
+    #[tokio::test]
+    async fn record_resend_waits_for_cooldown() {
+        let store = HashmapTwoFACodeStore::default();
+        let login_attempt_id = LoginAttemptId::default();
+        let _ = store
+            .add_code(
+                Email::parse("test@email.com").unwrap(),
+                login_attempt_id.clone(),
+                TwoFACode::default(),
+                TwoFAPurpose::Login,
+            )
+            .await;
+
+        let resend = store
+            .record_resend(&login_attempt_id, Duration::seconds(30), 3)
+            .await
+            .unwrap();
+
+        assert!(matches!(resend, TwoFAResend::RetryAfter(1..=30)));
+        assert_eq!(store.codes.get(&login_attempt_id).unwrap().resends, 0);
     }
 }
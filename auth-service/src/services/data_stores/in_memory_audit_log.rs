@@ -0,0 +1,99 @@
+use color_eyre::eyre::Result;
+use tokio::sync::RwLock;
+
+use crate::domain::{AuditEvent, AuditLog, AuditQuery, AuditRecord};
+
+#[derive(Default)]
+pub struct InMemoryAuditLog {
+    records: RwLock<Vec<AuditRecord>>,
+}
+
+#[async_trait::async_trait]
+impl AuditLog for InMemoryAuditLog {
+    async fn record(&self, event: AuditEvent) -> Result<()> {
+        let mut records = self.records.write().await;
+        let id = records.len() as i64 + 1;
+        records.push(AuditRecord { id, event });
+        Ok(())
+    }
+
+    async fn query(&self, query: &AuditQuery) -> Result<Vec<AuditRecord>> {
+        Ok(self
+            .records
+            .read()
+            .await
+            .iter()
+            .rev()
+            .filter(|record| query.matches(record))
+            .take(query.limit as usize)
+            .cloned()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::domain::{AuditEventKind, AuditOutcome};
+
+    fn event(actor: &str, outcome: AuditOutcome) -> AuditEvent {
+        AuditEvent {
+            occurred_at: Utc::now(),
+            kind: AuditEventKind::Login,
+            outcome,
+            actor: Some(actor.to_owned()),
+            user_id: None,
+            ip: None,
+            user_agent: None,
+            reason: None,
+            request_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_query_filters_and_pages_newest_first() {
+        let log = InMemoryAuditLog::default();
+        for _ in 0..3 {
+            log.record(event("a@test.com", AuditOutcome::Failure))
+                .await
+                .unwrap();
+        }
+        log.record(event("b@test.com", AuditOutcome::Success))
+            .await
+            .unwrap();
+
+        let query = AuditQuery {
+            actor: Some("a@test.com".to_owned()),
+            ..AuditQuery::with_limit(2)
+        };
+        let page = log.query(&query).await.unwrap();
+        assert_eq!(
+            page.iter().map(|record| record.id).collect::<Vec<_>>(),
+            vec![3, 2]
+        );
+
+        let next_page = log
+            .query(&AuditQuery {
+                before: Some(2),
+                ..query
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            next_page.iter().map(|record| record.id).collect::<Vec<_>>(),
+            vec![1]
+        );
+
+        let successes = log
+            .query(&AuditQuery {
+                outcome: Some(AuditOutcome::Success),
+                ..AuditQuery::with_limit(10)
+            })
+            .await
+            .unwrap();
+        assert_eq!(successes.len(), 1);
+        assert_eq!(successes[0].event.actor.as_deref(), Some("b@test.com"));
+    }
+}
@@ -0,0 +1,149 @@
+use std::{
+    num::NonZeroUsize,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use color_eyre::eyre::Result;
+use lru::LruCache;
+
+use crate::domain::{Email, StoredUser, UserCache};
+
+/// A per-process user cache holding at most `capacity` users for `ttl` each, dropping the least
+/// recently used one when full.
+pub struct InMemoryUserCache {
+    state: Option<Mutex<CacheState>>,
+    ttl: Duration,
+}
+
+struct CacheState {
+    users: LruCache<Email, (StoredUser, Instant)>,
+    /// Bumped by every invalidation. Versions are not kept per user, so an insert is dropped if
+    /// any user was invalidated since its version was read, which only costs a cache miss.
+    invalidations: u64,
+}
+
+impl InMemoryUserCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            state: NonZeroUsize::new(capacity).map(|capacity| {
+                Mutex::new(CacheState {
+                    users: LruCache::new(capacity),
+                    invalidations: 0,
+                })
+            }),
+            ttl,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl UserCache for InMemoryUserCache {
+    async fn get(&self, email: &Email) -> Result<Option<StoredUser>> {
+        let Some(state) = &self.state else {
+            return Ok(None);
+        };
+        let mut state = state.lock().unwrap();
+
+        match state.users.get(email) {
+            Some((user, cached_at)) if cached_at.elapsed() < self.ttl => Ok(Some(user.clone())),
+            Some(_) => {
+                state.users.pop(email);
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn version(&self, _email: &Email) -> Result<u64> {
+        Ok(self
+            .state
+            .as_ref()
+            .map_or(0, |state| state.lock().unwrap().invalidations))
+    }
+
+    async fn insert(&self, user: &StoredUser, version: u64) -> Result<()> {
+        let Some(state) = &self.state else {
+            return Ok(());
+        };
+        let mut state = state.lock().unwrap();
+
+        if state.invalidations == version {
+            state
+                .users
+                .put(user.email().clone(), (user.clone(), Instant::now()));
+        }
+        Ok(())
+    }
+
+    async fn invalidate(&self, email: &Email) -> Result<()> {
+        if let Some(state) = &self.state {
+            let mut state = state.lock().unwrap();
+            state.users.pop(email);
+            state.invalidations += 1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::*;
+    use crate::domain::{NewUser, Password, PasswordHash};
+
+    fn user(email: &str) -> StoredUser {
+        StoredUser::new(
+            NewUser::new(
+                Email::parse(email).unwrap(),
+                Password::parse(Secret::new("password123".to_owned())).unwrap(),
+                false,
+            ),
+            PasswordHash::new(Secret::new("hash".to_owned())),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_entries_expire() {
+        let cache = InMemoryUserCache::new(10, Duration::from_millis(50));
+        let user = user("a@test.com");
+        cache.insert(&user, 0).await.unwrap();
+
+        assert_eq!(cache.get(user.email()).await.unwrap(), Some(user.clone()));
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(cache.get(user.email()).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_evicts_least_recently_used_when_full() {
+        let cache = InMemoryUserCache::new(2, Duration::from_secs(60));
+        let users = [user("a@test.com"), user("b@test.com"), user("c@test.com")];
+        cache.insert(&users[0], 0).await.unwrap();
+        cache.insert(&users[1], 0).await.unwrap();
+        cache.get(users[0].email()).await.unwrap();
+        cache.insert(&users[2], 0).await.unwrap();
+
+        assert!(cache.get(users[0].email()).await.unwrap().is_some());
+        assert_eq!(cache.get(users[1].email()).await.unwrap(), None);
+        assert!(cache.get(users[2].email()).await.unwrap().is_some());
+
+        cache.invalidate(users[2].email()).await.unwrap();
+        assert_eq!(cache.get(users[2].email()).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_drops_insert_read_before_invalidation() {
+        let cache = InMemoryUserCache::new(10, Duration::from_secs(60));
+        let user = user("a@test.com");
+
+        let version = cache.version(user.email()).await.unwrap();
+        cache.invalidate(user.email()).await.unwrap();
+        cache.insert(&user, version).await.unwrap();
+        assert_eq!(cache.get(user.email()).await.unwrap(), None);
+
+        let version = cache.version(user.email()).await.unwrap();
+        cache.insert(&user, version).await.unwrap();
+        assert_eq!(cache.get(user.email()).await.unwrap(), Some(user));
+    }
+}
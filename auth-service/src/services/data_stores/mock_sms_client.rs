@@ -0,0 +1,21 @@
+use color_eyre::eyre::Result;
+
+use crate::domain::{PhoneNumber, SmsClient};
+
+#[derive(Default)]
+pub struct MockSmsClient;
+
+#[async_trait::async_trait]
+impl SmsClient for MockSmsClient {
+    #[tracing::instrument(name = "Sending 2FA SMS", skip(self))]
+    async fn send_sms(&self, recipient: &PhoneNumber, content: &str) -> Result<()> {
+        // Like the mock email client, this only logs the message to standard output
+        println!(
+            "Sending SMS to {} with content: {}",
+            recipient.as_ref(),
+            content
+        );
+
+        Ok(())
+    }
+}
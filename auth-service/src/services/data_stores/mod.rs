@@ -1,17 +1,53 @@
+pub(crate) mod cached_user_store;
+pub(crate) mod hashmap_magic_link_store;
+pub(crate) mod hashmap_trusted_device_store;
 pub(crate) mod hashmap_user_store;
 pub(crate) mod hashset_banned_token_store;
 pub(crate) mod haspmap_two_fa_code_store;
+pub(crate) mod in_memory_audit_log;
+pub(crate) mod in_memory_user_cache;
 pub(crate) mod mock_email_client;
+pub(crate) mod mock_sms_client;
+pub(crate) mod postgres_audit_log;
+pub(crate) mod postgres_banned_token_store;
+pub(crate) mod postgres_magic_link_store;
+pub(crate) mod postgres_pool;
+pub(crate) mod postgres_trusted_device_store;
+pub(crate) mod postgres_two_fa_code_store;
 pub(crate) mod postgresuser_store;
+pub(crate) mod postmark_email_client;
 pub(crate) mod redis_banned_token_store;
+pub(crate) mod redis_magic_link_store;
+pub(crate) mod redis_pool;
+pub(crate) mod redis_trusted_device_store;
 pub(crate) mod redis_two_fa_code_store;
-pub(crate) mod postmark_email_client;
+pub(crate) mod redis_user_cache;
+pub(crate) mod sqlite_user_store;
+pub(crate) mod twilio_sms_client;
 
+pub use cached_user_store::*;
+pub use hashmap_magic_link_store::*;
+pub use hashmap_trusted_device_store::*;
 pub use hashmap_user_store::*;
 pub use hashset_banned_token_store::*;
 pub use haspmap_two_fa_code_store::*;
+pub use in_memory_audit_log::*;
+pub use in_memory_user_cache::*;
 pub use mock_email_client::*;
+pub use mock_sms_client::*;
+pub use postgres_audit_log::*;
+pub use postgres_banned_token_store::*;
+pub use postgres_magic_link_store::*;
+pub use postgres_pool::*;
+pub use postgres_trusted_device_store::*;
+pub use postgres_two_fa_code_store::*;
 pub use postgresuser_store::*;
+pub use postmark_email_client::*;
 pub use redis_banned_token_store::*;
+pub use redis_magic_link_store::*;
+pub use redis_pool::*;
+pub use redis_trusted_device_store::*;
 pub use redis_two_fa_code_store::*;
-pub use postmark_email_client::*;
+pub use redis_user_cache::*;
+pub use sqlite_user_store::*;
+pub use twilio_sms_client::*;
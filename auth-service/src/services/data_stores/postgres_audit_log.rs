@@ -0,0 +1,119 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Context, Result};
+use sqlx::{prelude::FromRow, PgPool, Postgres, QueryBuilder};
+
+use crate::domain::{AuditEvent, AuditEventKind, AuditLog, AuditOutcome, AuditQuery, AuditRecord};
+
+pub struct PostgresAuditLog {
+    pool: PgPool,
+}
+
+impl PostgresAuditLog {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[derive(FromRow)]
+struct PgAuditRecord {
+    id: i64,
+    occurred_at: DateTime<Utc>,
+    kind: String,
+    outcome: String,
+    actor: Option<String>,
+    user_id: Option<String>,
+    ip: Option<String>,
+    user_agent: Option<String>,
+    reason: Option<String>,
+    request_id: Option<String>,
+}
+
+impl TryFrom<PgAuditRecord> for AuditRecord {
+    type Error = color_eyre::eyre::Report;
+
+    fn try_from(record: PgAuditRecord) -> Result<Self> {
+        Ok(AuditRecord {
+            id: record.id,
+            event: AuditEvent {
+                occurred_at: record.occurred_at,
+                kind: AuditEventKind::parse(&record.kind).map_err(|e| eyre!(e))?,
+                outcome: AuditOutcome::parse(&record.outcome).map_err(|e| eyre!(e))?,
+                actor: record.actor,
+                user_id: record.user_id,
+                ip: record.ip,
+                user_agent: record.user_agent,
+                reason: record.reason,
+                request_id: record.request_id,
+            },
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl AuditLog for PostgresAuditLog {
+    #[tracing::instrument(name = "Recording audit event in PostgreSQL", skip_all)]
+    async fn record(&self, event: AuditEvent) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO audit_events \
+            (occurred_at, kind, outcome, actor, user_id, ip, user_agent, reason, request_id) \
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+        )
+        .bind(event.occurred_at)
+        .bind(event.kind.as_ref())
+        .bind(event.outcome.as_ref())
+        .bind(event.actor)
+        .bind(event.user_id)
+        .bind(event.ip)
+        .bind(event.user_agent)
+        .bind(event.reason)
+        .bind(event.request_id)
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to record audit event in PostgreSQL")?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Querying audit events in PostgreSQL", skip(self))]
+    async fn query(&self, query: &AuditQuery) -> Result<Vec<AuditRecord>> {
+        let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(
+            "SELECT id, occurred_at, kind, outcome, actor, user_id, ip, user_agent, reason, \
+            request_id FROM audit_events WHERE TRUE",
+        );
+
+        if let Some(actor) = &query.actor {
+            builder.push(" AND actor = ").push_bind(actor.clone());
+        }
+        if let Some(kind) = query.kind {
+            builder
+                .push(" AND kind = ")
+                .push_bind(kind.as_ref().to_owned());
+        }
+        if let Some(outcome) = query.outcome {
+            builder
+                .push(" AND outcome = ")
+                .push_bind(outcome.as_ref().to_owned());
+        }
+        if let Some(since) = query.since {
+            builder.push(" AND occurred_at >= ").push_bind(since);
+        }
+        if let Some(until) = query.until {
+            builder.push(" AND occurred_at < ").push_bind(until);
+        }
+        if let Some(before) = query.before {
+            builder.push(" AND id < ").push_bind(before);
+        }
+        builder
+            .push(" ORDER BY id DESC LIMIT ")
+            .push_bind(i64::from(query.limit));
+
+        builder
+            .build_query_as::<PgAuditRecord>()
+            .fetch_all(&self.pool)
+            .await
+            .wrap_err("failed to query audit events in PostgreSQL")?
+            .into_iter()
+            .map(AuditRecord::try_from)
+            .collect()
+    }
+}
@@ -0,0 +1,113 @@
+use chrono::{DateTime, Duration, Utc};
+use color_eyre::eyre::{Context, Result};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+
+use crate::{
+    domain::{
+        data_stores::{BannedTokenStore, BannedTokenStoreError},
+        UserId,
+    },
+    utils::auth::TOKEN_TTL_SECONDS,
+};
+
+pub struct PostgresBannedTokenStore {
+    pool: PgPool,
+}
+
+impl PostgresBannedTokenStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Deletes tokens and revocations that have outlived the JWTs they ban and returns how many
+    /// were removed.
+    #[tracing::instrument(name = "Deleting expired banned tokens from PostgreSQL", skip_all)]
+    pub async fn delete_expired(pool: &PgPool) -> Result<u64> {
+        let tokens = sqlx::query("DELETE FROM banned_tokens WHERE expires_at <= now()")
+            .execute(pool)
+            .await
+            .wrap_err("failed to delete expired banned tokens from PostgreSQL")?;
+        let revocations = sqlx::query("DELETE FROM revoked_user_tokens WHERE expires_at <= now()")
+            .execute(pool)
+            .await
+            .wrap_err("failed to delete expired user token revocations from PostgreSQL")?;
+
+        Ok(tokens.rows_affected() + revocations.rows_affected())
+    }
+}
+
+#[async_trait::async_trait]
+impl BannedTokenStore for PostgresBannedTokenStore {
+    #[tracing::instrument(name = "Adding token to PostgreSQL banned token store", skip_all)]
+    async fn add_token(&self, token: Secret<String>) -> Result<()> {
+        let expires_at = Utc::now() + Duration::seconds(TOKEN_TTL_SECONDS);
+
+        sqlx::query(
+            "INSERT INTO banned_tokens (token, expires_at) VALUES ($1, $2) \
+            ON CONFLICT (token) DO UPDATE SET expires_at = EXCLUDED.expires_at",
+        )
+        .bind(token.expose_secret())
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to add banned token in PostgreSQL")
+        .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Checking PostgreSQL banned token store for token", skip_all)]
+    async fn contains_token(&self, token: Secret<String>) -> Result<bool> {
+        let exists: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM banned_tokens WHERE token = $1 AND expires_at > now())",
+        )
+        .bind(token.expose_secret())
+        .fetch_one(&self.pool)
+        .await
+        .wrap_err("failed to check if token exists in PostgreSQL")
+        .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(exists)
+    }
+
+    #[tracing::instrument(name = "Revoking user tokens in PostgreSQL", skip_all)]
+    async fn revoke_user_tokens(
+        &self,
+        user_id: &UserId,
+        issued_before: DateTime<Utc>,
+    ) -> Result<()> {
+        let expires_at = issued_before + Duration::seconds(TOKEN_TTL_SECONDS);
+
+        sqlx::query(
+            "INSERT INTO revoked_user_tokens (user_id, issued_before, expires_at) \
+            VALUES ($1, $2, $3) \
+            ON CONFLICT (user_id) DO UPDATE SET \
+                issued_before = GREATEST(revoked_user_tokens.issued_before, EXCLUDED.issued_before), \
+                expires_at = GREATEST(revoked_user_tokens.expires_at, EXCLUDED.expires_at)",
+        )
+        .bind(user_id.as_ref())
+        .bind(issued_before)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to revoke user tokens in PostgreSQL")
+        .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Getting user tokens revocation from PostgreSQL", skip_all)]
+    async fn user_tokens_revoked_before(&self, user_id: &UserId) -> Result<Option<DateTime<Utc>>> {
+        let issued_before = sqlx::query_scalar(
+            "SELECT issued_before FROM revoked_user_tokens WHERE user_id = $1 AND expires_at > now()",
+        )
+        .bind(user_id.as_ref())
+        .fetch_optional(&self.pool)
+        .await
+        .wrap_err("failed to get user tokens revocation from PostgreSQL")
+        .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(issued_before)
+    }
+}
@@ -0,0 +1,84 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Context, Result};
+use sqlx::{prelude::FromRow, PgPool};
+
+use crate::domain::{Email, MagicLink, MagicLinkStore, MagicLinkStoreError, MagicLinkToken};
+
+pub struct PostgresMagicLinkStore {
+    pool: PgPool,
+}
+
+impl PostgresMagicLinkStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Deletes expired magic links and returns how many were removed.
+    #[tracing::instrument(name = "Deleting expired magic links from PostgreSQL", skip_all)]
+    pub async fn delete_expired(pool: &PgPool) -> Result<u64> {
+        let links = sqlx::query("DELETE FROM magic_links WHERE expires_at <= now()")
+            .execute(pool)
+            .await
+            .wrap_err("failed to delete expired magic links from PostgreSQL")?;
+
+        Ok(links.rows_affected())
+    }
+}
+
+#[async_trait::async_trait]
+impl MagicLinkStore for PostgresMagicLinkStore {
+    #[tracing::instrument(name = "Adding magic link to PostgreSQL", skip_all)]
+    async fn add_link(
+        &self,
+        token: &MagicLinkToken,
+        link: MagicLink,
+    ) -> Result<(), MagicLinkStoreError> {
+        sqlx::query(
+            "INSERT INTO magic_links (token_hash, email, nonce_hash, expires_at) \
+            VALUES ($1, $2, $3, $4)",
+        )
+        .bind(token.hash())
+        .bind(link.email.as_ref())
+        .bind(link.nonce_hash)
+        .bind(link.expires_at)
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to add magic link in PostgreSQL")
+        .map_err(MagicLinkStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Taking magic link from PostgreSQL", skip_all)]
+    async fn take_link(&self, token: &MagicLinkToken) -> Result<MagicLink, MagicLinkStoreError> {
+        let record: PgMagicLink = sqlx::query_as(
+            "DELETE FROM magic_links WHERE token_hash = $1 \
+            RETURNING email, nonce_hash, expires_at",
+        )
+        .bind(token.hash())
+        .fetch_optional(&self.pool)
+        .await
+        .wrap_err("failed to take magic link from PostgreSQL")
+        .map_err(MagicLinkStoreError::UnexpectedError)?
+        .ok_or(MagicLinkStoreError::LinkNotFound)?;
+
+        let link = MagicLink {
+            email: Email::parse(&record.email)
+                .map_err(|e| MagicLinkStoreError::UnexpectedError(eyre!(e)))?,
+            nonce_hash: record.nonce_hash,
+            expires_at: record.expires_at,
+        };
+
+        match link.is_expired() {
+            true => Err(MagicLinkStoreError::LinkNotFound),
+            false => Ok(link),
+        }
+    }
+}
+
+#[derive(FromRow)]
+struct PgMagicLink {
+    email: String,
+    nonce_hash: String,
+    expires_at: DateTime<Utc>,
+}
@@ -0,0 +1,134 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Context, Result};
+use sqlx::{prelude::FromRow, PgPool};
+
+use crate::domain::{
+    Email, TrustedDevice, TrustedDeviceId, TrustedDeviceStore, TrustedDeviceStoreError,
+};
+
+pub struct PostgresTrustedDeviceStore {
+    pool: PgPool,
+}
+
+impl PostgresTrustedDeviceStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Deletes expired trusted devices and returns how many were removed.
+    #[tracing::instrument(name = "Deleting expired trusted devices from PostgreSQL", skip_all)]
+    pub async fn delete_expired(pool: &PgPool) -> Result<u64> {
+        let devices = sqlx::query("DELETE FROM trusted_devices WHERE expires_at <= now()")
+            .execute(pool)
+            .await
+            .wrap_err("failed to delete expired trusted devices from PostgreSQL")?;
+
+        Ok(devices.rows_affected())
+    }
+}
+
+#[async_trait::async_trait]
+impl TrustedDeviceStore for PostgresTrustedDeviceStore {
+    #[tracing::instrument(name = "Adding trusted device to PostgreSQL", skip(self))]
+    async fn add_device(
+        &self,
+        email: Email,
+        device: TrustedDevice,
+    ) -> Result<(), TrustedDeviceStoreError> {
+        sqlx::query(
+            "INSERT INTO trusted_devices (id, email, user_agent, created_at, expires_at) \
+            VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(device.id.as_ref())
+        .bind(email.as_ref())
+        .bind(device.user_agent)
+        .bind(device.created_at)
+        .bind(device.expires_at)
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to add trusted device in PostgreSQL")
+        .map_err(TrustedDeviceStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Getting trusted devices from PostgreSQL", skip(self))]
+    async fn get_devices(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<TrustedDevice>, TrustedDeviceStoreError> {
+        let records: Vec<PgTrustedDevice> = sqlx::query_as(
+            "SELECT id, user_agent, created_at, expires_at FROM trusted_devices \
+            WHERE email = $1 AND expires_at > now() ORDER BY created_at",
+        )
+        .bind(email.as_ref())
+        .fetch_all(&self.pool)
+        .await
+        .wrap_err("failed to get trusted devices from PostgreSQL")
+        .map_err(TrustedDeviceStoreError::UnexpectedError)?;
+
+        records.into_iter().map(TrustedDevice::try_from).collect()
+    }
+
+    #[tracing::instrument(name = "Checking trusted device in PostgreSQL", skip(self))]
+    async fn contains_device(
+        &self,
+        email: &Email,
+        device_id: &TrustedDeviceId,
+    ) -> Result<bool, TrustedDeviceStoreError> {
+        let exists: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM trusted_devices \
+            WHERE id = $1 AND email = $2 AND expires_at > now())",
+        )
+        .bind(device_id.as_ref())
+        .bind(email.as_ref())
+        .fetch_one(&self.pool)
+        .await
+        .wrap_err("failed to check trusted device in PostgreSQL")
+        .map_err(TrustedDeviceStoreError::UnexpectedError)?;
+
+        Ok(exists)
+    }
+
+    #[tracing::instrument(name = "Removing trusted device from PostgreSQL", skip(self))]
+    async fn remove_device(
+        &self,
+        email: &Email,
+        device_id: &TrustedDeviceId,
+    ) -> Result<(), TrustedDeviceStoreError> {
+        let result = sqlx::query("DELETE FROM trusted_devices WHERE id = $1 AND email = $2")
+            .bind(device_id.as_ref())
+            .bind(email.as_ref())
+            .execute(&self.pool)
+            .await
+            .wrap_err("failed to remove trusted device from PostgreSQL")
+            .map_err(TrustedDeviceStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(TrustedDeviceStoreError::DeviceNotFound),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(FromRow)]
+struct PgTrustedDevice {
+    id: String,
+    user_agent: Option<String>,
+    created_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+}
+
+impl TryFrom<PgTrustedDevice> for TrustedDevice {
+    type Error = TrustedDeviceStoreError;
+
+    fn try_from(record: PgTrustedDevice) -> Result<Self, Self::Error> {
+        Ok(TrustedDevice {
+            id: TrustedDeviceId::parse(record.id)
+                .map_err(|e| TrustedDeviceStoreError::UnexpectedError(eyre!(e)))?,
+            user_agent: record.user_agent,
+            created_at: record.created_at,
+            expires_at: record.expires_at,
+        })
+    }
+}
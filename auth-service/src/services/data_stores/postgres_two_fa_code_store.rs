@@ -0,0 +1,278 @@
+use chrono::{DateTime, Duration, Utc};
+use color_eyre::eyre::{eyre, Context, Result};
+use sqlx::{prelude::FromRow, PgPool, Postgres, Transaction};
+
+use crate::domain::{
+    data_stores::{
+        LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError, TwoFAPurpose, TwoFAResend,
+    },
+    Email,
+};
+
+pub struct PostgresTwoFACodeStore {
+    pool: PgPool,
+    max_pending_attempts: usize,
+}
+
+impl PostgresTwoFACodeStore {
+    /// Keeps at most `max_pending_attempts` codes per email, dropping the oldest first.
+    pub fn new(pool: PgPool, max_pending_attempts: usize) -> Self {
+        Self {
+            pool,
+            max_pending_attempts,
+        }
+    }
+
+    /// Deletes expired codes and issue counters and returns how many rows were removed.
+    #[tracing::instrument(name = "Deleting expired 2FA codes from PostgreSQL", skip_all)]
+    pub async fn delete_expired(pool: &PgPool) -> Result<u64> {
+        let codes = sqlx::query("DELETE FROM two_fa_codes WHERE expires_at <= now()")
+            .execute(pool)
+            .await
+            .wrap_err("failed to delete expired 2FA codes from PostgreSQL")?;
+
+        let issued = sqlx::query("DELETE FROM two_fa_codes_issued WHERE expires_at <= now()")
+            .execute(pool)
+            .await
+            .wrap_err("failed to delete expired 2FA issue counters from PostgreSQL")?;
+
+        Ok(codes.rows_affected() + issued.rows_affected())
+    }
+
+    async fn get_record(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<PgTwoFACode, TwoFACodeStoreError> {
+        sqlx::query_as(
+            "SELECT email, code, purpose, phone_number FROM two_fa_codes \
+            WHERE login_attempt_id = $1 AND expires_at > now()",
+        )
+        .bind(login_attempt_id.as_ref())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| TwoFACodeStoreError::UnexpectedError(e.into()))?
+        .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    }
+
+    async fn evict_oldest_pending(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        email: &Email,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM two_fa_codes WHERE email = $1 AND expires_at <= now()")
+            .bind(email.as_ref())
+            .execute(&mut **tx)
+            .await?;
+
+        // Keeps the newest attempts, leaving room for the one being added.
+        sqlx::query(
+            "DELETE FROM two_fa_codes WHERE login_attempt_id IN ( \
+                SELECT login_attempt_id FROM two_fa_codes WHERE email = $1 \
+                ORDER BY created_at DESC OFFSET $2 \
+            )",
+        )
+        .bind(email.as_ref())
+        .bind(self.max_pending_attempts.saturating_sub(1) as i64)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(FromRow)]
+struct PgTwoFACode {
+    email: String,
+    code: String,
+    purpose: String,
+    phone_number: Option<String>,
+}
+
+#[derive(FromRow)]
+struct PgTwoFACodeDelivery {
+    resends: i32,
+    last_sent_at: DateTime<Utc>,
+}
+
+#[async_trait::async_trait]
+impl TwoFACodeStore for PostgresTwoFACodeStore {
+    #[tracing::instrument(name = "Adding 2FA code to PostgreSQL", skip(self))]
+    async fn add_code(
+        &self,
+        email: Email,
+        login_attempt_id: LoginAttemptId,
+        code: TwoFACode,
+        purpose: TwoFAPurpose,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let now = Utc::now();
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| TwoFACodeStoreError::UnexpectedError(e.into()))?;
+
+        self.evict_oldest_pending(&mut tx, &email)
+            .await
+            .wrap_err("failed to evict pending 2FA attempts from PostgreSQL")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        sqlx::query(
+            "INSERT INTO two_fa_codes \
+            (login_attempt_id, email, code, purpose, phone_number, created_at, last_sent_at, \
+                expires_at) \
+            VALUES ($1, $2, $3, $4, $5, $6, $6, $7)",
+        )
+        .bind(login_attempt_id.as_ref())
+        .bind(email.as_ref())
+        .bind(code.as_ref())
+        .bind(purpose.as_ref())
+        .bind(purpose.phone_number().map(AsRef::<str>::as_ref))
+        .bind(now)
+        .bind(now + CODE_TTL)
+        .execute(&mut *tx)
+        .await
+        .wrap_err("failed to add 2FA code in PostgreSQL")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        tx.commit()
+            .await
+            .map_err(|e| TwoFACodeStoreError::UnexpectedError(e.into()))
+    }
+
+    #[tracing::instrument(name = "Removing 2FA code from PostgreSQL", skip(self))]
+    async fn remove_code(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let result = sqlx::query(
+            "DELETE FROM two_fa_codes WHERE login_attempt_id = $1 AND expires_at > now()",
+        )
+        .bind(login_attempt_id.as_ref())
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to delete 2FA code from PostgreSQL")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Getting 2FA code from PostgreSQL", skip(self))]
+    async fn get_code(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(Email, TwoFACode), TwoFACodeStoreError> {
+        let record = self.get_record(login_attempt_id).await?;
+
+        Ok((
+            Email::parse(&record.email)
+                .map_err(|e| TwoFACodeStoreError::UnexpectedError(eyre!(e)))?,
+            TwoFACode::parse(record.code)
+                .map_err(|e| TwoFACodeStoreError::UnexpectedError(eyre!(e)))?,
+        ))
+    }
+
+    #[tracing::instrument(name = "Getting 2FA code purpose from PostgreSQL", skip(self))]
+    async fn get_purpose(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<TwoFAPurpose, TwoFACodeStoreError> {
+        let record = self.get_record(login_attempt_id).await?;
+
+        TwoFAPurpose::parse(&record.purpose, record.phone_number.as_deref())
+            .map_err(|e| TwoFACodeStoreError::UnexpectedError(eyre!(e)))
+    }
+
+    #[tracing::instrument(name = "Recording 2FA attempt in PostgreSQL", skip(self))]
+    async fn record_attempt(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<u32, TwoFACodeStoreError> {
+        let attempts: Option<i32> = sqlx::query_scalar(
+            "UPDATE two_fa_codes SET attempts = attempts + 1 \
+            WHERE login_attempt_id = $1 AND expires_at > now() RETURNING attempts",
+        )
+        .bind(login_attempt_id.as_ref())
+        .fetch_optional(&self.pool)
+        .await
+        .wrap_err("failed to record 2FA attempt in PostgreSQL")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        attempts
+            .map(|attempts| attempts as u32)
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    }
+
+    #[tracing::instrument(name = "Recording issued 2FA code in PostgreSQL", skip(self))]
+    async fn record_code_issued(&self, email: &Email) -> Result<u32, TwoFACodeStoreError> {
+        // An expired counter starts a new hourly window instead of being incremented.
+        let issued: i32 = sqlx::query_scalar(
+            "INSERT INTO two_fa_codes_issued (email, issued, expires_at) VALUES ($1, 1, $2) \
+            ON CONFLICT (email) DO UPDATE SET \
+                issued = CASE WHEN two_fa_codes_issued.expires_at <= now() THEN 1 \
+                    ELSE two_fa_codes_issued.issued + 1 END, \
+                expires_at = CASE WHEN two_fa_codes_issued.expires_at <= now() THEN EXCLUDED.expires_at \
+                    ELSE two_fa_codes_issued.expires_at END \
+            RETURNING issued",
+        )
+        .bind(email.as_ref())
+        .bind(Utc::now() + ISSUE_WINDOW)
+        .fetch_one(&self.pool)
+        .await
+        .wrap_err("failed to record issued 2FA code in PostgreSQL")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(issued as u32)
+    }
+
+    #[tracing::instrument(name = "Recording 2FA code resend in PostgreSQL", skip(self))]
+    async fn record_resend(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+        cooldown: Duration,
+        max_resends: u32,
+    ) -> Result<TwoFAResend, TwoFACodeStoreError> {
+        let recorded = sqlx::query(
+            "UPDATE two_fa_codes SET resends = resends + 1, last_sent_at = now() \
+            WHERE login_attempt_id = $1 AND expires_at > now() \
+                AND last_sent_at <= now() - $2 AND resends < $3",
+        )
+        .bind(login_attempt_id.as_ref())
+        .bind(cooldown)
+        .bind(max_resends as i32)
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to record 2FA code resend in PostgreSQL")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        if recorded.rows_affected() > 0 {
+            return Ok(TwoFAResend::Recorded);
+        }
+
+        // Not resent, so only says why; the update above already made the decision.
+        let delivery: PgTwoFACodeDelivery = sqlx::query_as(
+            "SELECT resends, last_sent_at FROM two_fa_codes \
+            WHERE login_attempt_id = $1 AND expires_at > now()",
+        )
+        .bind(login_attempt_id.as_ref())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| TwoFACodeStoreError::UnexpectedError(e.into()))?
+        .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        let retry_after = (delivery.last_sent_at + cooldown - Utc::now()).num_seconds();
+        if retry_after > 0 {
+            Ok(TwoFAResend::RetryAfter(retry_after))
+        } else if delivery.resends as u32 >= max_resends {
+            Ok(TwoFAResend::LimitReached)
+        } else {
+            // The cooldown ended between the two queries.
+            Ok(TwoFAResend::RetryAfter(1))
+        }
+    }
+}
+
+const CODE_TTL: Duration = Duration::minutes(10);
+const ISSUE_WINDOW: Duration = Duration::hours(1);
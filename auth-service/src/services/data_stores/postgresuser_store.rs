@@ -1,44 +1,62 @@
-use argon2::{
-    password_hash::{rand_core::OsRng, SaltString},
-    Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version,
-};
-
-use color_eyre::eyre::{eyre, Context, Result};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Result};
 
 use secrecy::ExposeSecret;
-use sqlx::{prelude::FromRow, PgPool};
+use sqlx::{prelude::FromRow, PgPool, Postgres, QueryBuilder};
+use uuid::Uuid;
 
-use crate::domain::{
-    data_stores::{UserStore, UserStoreError},
-    Email, Password, User,
+use crate::{
+    domain::{
+        data_stores::{UserStore, UserStoreError},
+        Email, NewUser, Password, PhoneNumber, StoredUser, TwoFAChannel, UserFilter, UserId,
+        UserPage, UserQuery, UserStatus, UserUpdate,
+    },
+    services::PasswordHasher,
 };
 
 pub struct PostgresUserStore {
     pool: PgPool,
+    hasher: PasswordHasher,
 }
 
 impl PostgresUserStore {
     pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            hasher: PasswordHasher::default(),
+        }
     }
 }
 
+/// Columns selected into a `PgUser`.
+pub(crate) const USER_COLUMNS: &str = "id, email, password_hash, requires_2fa, phone_number, \
+    phone_verified, two_fa_channel, status, created_at, updated_at, last_login_at";
+
 #[derive(FromRow)]
 pub struct PgUser {
+    pub id: Uuid,
     pub email: String,
     pub password_hash: String,
     pub requires_2fa: bool,
+    pub phone_number: Option<String>,
+    pub phone_verified: bool,
+    pub two_fa_channel: String,
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub last_login_at: Option<DateTime<Utc>>,
 }
 
 impl PostgresUserStore {
     #[tracing::instrument(name = "Getting postgres user from database", skip(self))]
     async fn get_pg_user(&self, email: Email) -> Result<PgUser, UserStoreError> {
-        let result: Option<PgUser> =
-            sqlx::query_as("SELECT email, password_hash, requires_2fa FROM users WHERE email = $1")
-                .bind(email.as_ref())
-                .fetch_optional(&self.pool)
-                .await
-                .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+        let result: Option<PgUser> = sqlx::query_as(&format!(
+            "SELECT {USER_COLUMNS} FROM users WHERE email = $1"
+        ))
+        .bind(email.as_ref())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
 
         match result {
             Some(pg_user) => Ok(pg_user),
@@ -47,26 +65,57 @@ impl PostgresUserStore {
     }
 }
 
+/// Appends `filter` as `AND` conditions to a query that already has a `WHERE` clause.
+fn push_user_filter(builder: &mut QueryBuilder<'_, Postgres>, filter: &UserFilter) {
+    if let Some(email_prefix) = &filter.email_prefix {
+        builder
+            .push(" AND starts_with(email, ")
+            .push_bind(email_prefix.clone())
+            .push(")");
+    }
+    if let Some(requires_2fa) = filter.requires_2fa {
+        builder.push(" AND requires_2fa = ").push_bind(requires_2fa);
+    }
+    if let Some(status) = filter.status {
+        builder
+            .push(" AND status = ")
+            .push_bind(status.as_ref().to_owned());
+    }
+    if let Some(since) = filter.created_since {
+        builder.push(" AND created_at >= ").push_bind(since);
+    }
+    if let Some(until) = filter.created_until {
+        builder.push(" AND created_at < ").push_bind(until);
+    }
+}
+
 #[async_trait::async_trait]
 impl UserStore for PostgresUserStore {
     #[tracing::instrument(name = "Adding user to PostgreSQL", skip_all)]
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
-        let owned_pwd = user.password().as_ref().to_owned();
-        let hashed_pwd = compute_password_hash(owned_pwd.expose_secret().to_owned())
+    async fn add_user(&self, user: NewUser) -> Result<StoredUser, UserStoreError> {
+        let password_hash = self
+            .hasher
+            .hash(user.password())
             .await
             .map_err(UserStoreError::UnexpectedError)?;
+        let user = StoredUser::new(user, password_hash);
 
         let result = sqlx::query(
-            "INSERT INTO users (email, password_hash, requires_2fa) VALUES ($1, $2, $3)",
+            "INSERT INTO users (id, email, password_hash, requires_2fa, status, created_at, updated_at) \
+            VALUES ($1, $2, $3, $4, $5, $6, $7)",
         )
+        .bind(user.id().as_ref())
         .bind(user.email().as_ref())
-        .bind(hashed_pwd)
+        .bind(user.password_hash().as_ref().expose_secret())
         .bind(user.requires_2fa())
+        .bind(user.status().as_ref())
+        .bind(user.created_at())
+        .bind(user.updated_at())
         .execute(&self.pool)
         .await;
 
         match result {
-            Ok(_) => Ok(()),
+            Ok(_) => Ok(user),
             Err(sqlx::Error::Database(db_err)) => {
                 if db_err.is_unique_violation() {
                     return Err(UserStoreError::UserAlreadyExists);
@@ -78,62 +127,271 @@ impl UserStore for PostgresUserStore {
     }
 
     #[tracing::instrument(name = "Retrieving user from PostgreSQL", skip(self))]
-    async fn get_user(&self, email: Email) -> Result<User, UserStoreError> {
-        Ok(User::from(self.get_pg_user(email).await?))
+    async fn get_user(&self, email: Email) -> Result<StoredUser, UserStoreError> {
+        Ok(StoredUser::from(self.get_pg_user(email).await?))
+    }
+
+    #[tracing::instrument(name = "Retrieving user by id from PostgreSQL", skip(self))]
+    async fn get_user_by_id(&self, id: &UserId) -> Result<StoredUser, UserStoreError> {
+        let result: Option<PgUser> =
+            sqlx::query_as(&format!("SELECT {USER_COLUMNS} FROM users WHERE id = $1"))
+                .bind(id.as_ref())
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        result
+            .map(StoredUser::from)
+            .ok_or(UserStoreError::UserNotFound)
     }
 
-    #[tracing::instrument(name = "Validating user credentials in PostgreSQL", skip(self, password))]
-    async fn validate_user(&self, email: Email, password: Password) -> Result<(), UserStoreError> {
-        let user = self.get_pg_user(email.clone()).await?;
+    #[tracing::instrument(
+        name = "Validating user credentials in PostgreSQL",
+        skip(self, password)
+    )]
+    async fn validate_user(
+        &self,
+        email: Email,
+        password: Password,
+    ) -> Result<StoredUser, UserStoreError> {
+        let user = StoredUser::from(self.get_pg_user(email).await?);
 
-        Ok(
-            verify_password_hash(user.password_hash, password.as_ref().expose_secret().to_owned())
+        self.hasher
+            .verify(user.password_hash(), &password)
+            .await
+            .map_err(|_| UserStoreError::InvalidCredentials)?;
+
+        Ok(user)
+    }
+
+    #[tracing::instrument(name = "Updating user 2FA setting in PostgreSQL", skip(self))]
+    async fn update_requires_2fa(
+        &self,
+        email: Email,
+        requires_2fa: bool,
+    ) -> Result<(), UserStoreError> {
+        let result =
+            sqlx::query("UPDATE users SET requires_2fa = $2, updated_at = now() WHERE email = $1")
+                .bind(email.as_ref())
+                .bind(requires_2fa)
+                .execute(&self.pool)
                 .await
-                .map_err(|_| UserStoreError::InvalidCredentials)?,
+                .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        match result.rows_affected() {
+            0 => Err(UserStoreError::UserNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Setting user phone number in PostgreSQL", skip(self))]
+    async fn set_phone_number(
+        &self,
+        email: Email,
+        phone_number: PhoneNumber,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query(
+            "UPDATE users SET phone_number = $2, phone_verified = FALSE, two_fa_channel = $3, \
+            updated_at = now() WHERE email = $1",
         )
+        .bind(email.as_ref())
+        .bind(phone_number.as_ref())
+        .bind(TwoFAChannel::Email.as_ref())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        match result.rows_affected() {
+            0 => Err(UserStoreError::UserNotFound),
+            _ => Ok(()),
+        }
     }
-}
 
-#[tracing::instrument(name = "Verify password hash", skip_all)]
-async fn verify_password_hash(
-    expected_password_hash: String,
-    password_candidate: String,
-) -> Result<()> {
-    let current_span: tracing::Span = tracing::Span::current();
-    let result = tokio::task::spawn_blocking(move || {
-        current_span.in_scope(|| {
-            let expected_password_hash: PasswordHash<'_> =
-                PasswordHash::new(&expected_password_hash)?;
-
-            Argon2::default()
-                .verify_password(password_candidate.as_bytes(), &expected_password_hash)
-                .wrap_err("failed to verify password hash")
-        })
-    })
-    .await;
-
-    result?
-}
+    #[tracing::instrument(name = "Verifying user phone number in PostgreSQL", skip(self))]
+    async fn verify_phone_number(
+        &self,
+        email: Email,
+        phone_number: PhoneNumber,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query(
+            "UPDATE users SET phone_verified = TRUE, updated_at = now() \
+            WHERE email = $1 AND phone_number = $2",
+        )
+        .bind(email.as_ref())
+        .bind(phone_number.as_ref())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        match result.rows_affected() {
+            0 => {
+                self.get_pg_user(email).await?;
+                Err(UserStoreError::InvalidCredentials)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Updating user 2FA channel in PostgreSQL", skip(self))]
+    async fn update_two_fa_channel(
+        &self,
+        email: Email,
+        two_fa_channel: TwoFAChannel,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query(
+            "UPDATE users SET two_fa_channel = $2, updated_at = now() WHERE email = $1",
+        )
+        .bind(email.as_ref())
+        .bind(two_fa_channel.as_ref())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        match result.rows_affected() {
+            0 => Err(UserStoreError::UserNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Updating user status in PostgreSQL", skip(self))]
+    async fn update_status(&self, email: Email, status: UserStatus) -> Result<(), UserStoreError> {
+        let result =
+            sqlx::query("UPDATE users SET status = $2, updated_at = now() WHERE email = $1")
+                .bind(email.as_ref())
+                .bind(status.as_ref())
+                .execute(&self.pool)
+                .await
+                .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        match result.rows_affected() {
+            0 => Err(UserStoreError::UserNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Recording user login in PostgreSQL", skip(self))]
+    async fn record_login(&self, email: Email) -> Result<(), UserStoreError> {
+        let result = sqlx::query("UPDATE users SET last_login_at = now() WHERE email = $1")
+            .bind(email.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        match result.rows_affected() {
+            0 => Err(UserStoreError::UserNotFound),
+            _ => Ok(()),
+        }
+    }
 
-#[tracing::instrument(name = "Computing password hash", skip_all)]
-async fn compute_password_hash(password: String) -> Result<String> {
-    let current_span: tracing::Span = tracing::Span::current();
-
-    let result = tokio::task::spawn_blocking(move || {
-        current_span.in_scope(|| {
-            let salt: SaltString = SaltString::generate(&mut OsRng);
-            let password_hash = Argon2::new(
-                Algorithm::Argon2id,
-                Version::V0x13,
-                Params::new(15000, 2, 1, None)?,
-            )
-            .hash_password(password.as_bytes(), &salt)?
-            .to_string();
-
-            Ok(password_hash)
-        })
-    })
-    .await;
-
-    result?
+    #[tracing::instrument(name = "Updating user in PostgreSQL", skip(self, update))]
+    async fn update_user(
+        &self,
+        id: &UserId,
+        update: UserUpdate,
+    ) -> Result<StoredUser, UserStoreError> {
+        let mut builder: QueryBuilder<Postgres> =
+            QueryBuilder::new("UPDATE users SET updated_at = now()");
+
+        if let Some(email) = &update.email {
+            builder
+                .push(", email = ")
+                .push_bind(email.as_ref().to_owned());
+        }
+        if let Some(password) = &update.password {
+            let password_hash = self
+                .hasher
+                .hash(password)
+                .await
+                .map_err(UserStoreError::UnexpectedError)?;
+            builder
+                .push(", password_hash = ")
+                .push_bind(password_hash.as_ref().expose_secret().to_owned());
+        }
+        if let Some(requires_2fa) = update.requires_2fa {
+            builder.push(", requires_2fa = ").push_bind(requires_2fa);
+        }
+        if let Some(status) = update.status {
+            builder
+                .push(", status = ")
+                .push_bind(status.as_ref().to_owned());
+        }
+        builder
+            .push(" WHERE id = ")
+            .push_bind(*id.as_ref())
+            .push(format!(" RETURNING {USER_COLUMNS}"));
+
+        let result = builder
+            .build_query_as::<PgUser>()
+            .fetch_optional(&self.pool)
+            .await;
+
+        match result {
+            Ok(Some(pg_user)) => Ok(StoredUser::from(pg_user)),
+            Ok(None) => Err(UserStoreError::UserNotFound),
+            Err(sqlx::Error::Database(db_err)) if db_err.is_unique_violation() => {
+                Err(UserStoreError::UserAlreadyExists)
+            }
+            Err(e) => Err(UserStoreError::UnexpectedError(e.into())),
+        }
+    }
+
+    #[tracing::instrument(name = "Deleting user from PostgreSQL", skip(self))]
+    async fn delete_user(&self, id: &UserId) -> Result<(), UserStoreError> {
+        let result = sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(id.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        match result.rows_affected() {
+            0 => Err(UserStoreError::UserNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Counting users in PostgreSQL", skip(self))]
+    async fn count_users(&self, filter: &UserFilter) -> Result<u64, UserStoreError> {
+        let mut builder: QueryBuilder<Postgres> =
+            QueryBuilder::new("SELECT COUNT(*) FROM users WHERE TRUE");
+        push_user_filter(&mut builder, filter);
+
+        let count: i64 = builder
+            .build_query_scalar()
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        Ok(count as u64)
+    }
+
+    #[tracing::instrument(name = "Listing users in PostgreSQL", skip(self))]
+    async fn list_users(&self, query: &UserQuery) -> Result<UserPage, UserStoreError> {
+        let mut builder: QueryBuilder<Postgres> =
+            QueryBuilder::new(format!("SELECT {USER_COLUMNS} FROM users WHERE TRUE"));
+        push_user_filter(&mut builder, &query.filter);
+
+        if let Some(after) = query.after {
+            builder
+                .push(" AND (created_at, id) > (")
+                .push_bind(after.created_at)
+                .push(", ")
+                .push_bind(*after.id.as_ref())
+                .push(")");
+        }
+        // One extra row tells whether there is a next page.
+        builder
+            .push(" ORDER BY created_at, id LIMIT ")
+            .push_bind(i64::from(query.limit) + 1);
+
+        let users = builder
+            .build_query_as::<PgUser>()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+            .into_iter()
+            .map(StoredUser::from)
+            .collect();
+
+        Ok(UserPage::from_rows(users, query.limit))
+    }
 }
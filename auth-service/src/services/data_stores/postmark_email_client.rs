@@ -2,7 +2,10 @@ use color_eyre::eyre::Result;
 use reqwest::{Client, Url};
 use secrecy::{ExposeSecret, Secret};
 
-use crate::domain::{Email, EmailClient};
+use crate::{
+    domain::{Email, EmailClient},
+    utils::{metrics::record_email_send_failure, tracing::trace_context_headers},
+};
 
 pub struct PostmarkEmailClient {
     http_client: Client,
@@ -25,12 +28,8 @@ impl PostmarkEmailClient {
             authorization_token,
         }
     }
-}
 
-#[async_trait::async_trait]
-impl EmailClient for PostmarkEmailClient {
-    #[tracing::instrument(name = "Sending email", skip_all)]
-    async fn send_email(&self, recipient: &Email, subject: &str, content: &str) -> Result<()> {
+    async fn send(&self, recipient: &Email, subject: &str, content: &str) -> Result<()> {
         let base = Url::parse(&self.base_url)?;
         let url = base.join("/email")?;
 
@@ -46,6 +45,7 @@ impl EmailClient for PostmarkEmailClient {
         let request = self
             .http_client
             .post(url)
+            .headers(trace_context_headers())
             .header(
                 POSTMARK_AUTH_HEADER,
                 self.authorization_token.expose_secret(),
@@ -58,6 +58,18 @@ impl EmailClient for PostmarkEmailClient {
     }
 }
 
+#[async_trait::async_trait]
+impl EmailClient for PostmarkEmailClient {
+    #[tracing::instrument(name = "Sending email", skip_all)]
+    async fn send_email(&self, recipient: &Email, subject: &str, content: &str) -> Result<()> {
+        let result = self.send(recipient, subject, content).await;
+        if result.is_err() {
+            record_email_send_failure("postmark");
+        }
+        result
+    }
+}
+
 const MESSAGE_STREAM: &str = "outbound";
 const POSTMARK_AUTH_HEADER: &str = "X-Postmark-Server-Token";
 
@@ -80,7 +92,11 @@ mod tests {
     use fake::faker::internet::en::SafeEmail;
     use fake::faker::lorem::en::{Paragraph, Sentence};
     use fake::{Fake, Faker};
-    use wiremock::matchers::{any, header, header_exists, method, path};
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::TracerProvider};
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+    use tracing_subscriber::layer::SubscriberExt;
+    use wiremock::matchers::{any, header, header_exists, header_regex, method, path};
     use wiremock::{Mock, MockServer, Request, ResponseTemplate};
 
     use super::PostmarkEmailClient;
@@ -146,6 +162,46 @@ mod tests {
         assert!(outcome.is_ok());
     }
 
+    #[tokio::test]
+    async fn send_email_propagates_the_current_trace() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+
+        Mock::given(header_regex(
+            "traceparent",
+            &format!("^00-{trace_id}-[0-9a-f]{{16}}-01$"),
+        ))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+        opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+        let provider = TracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        let _default = tracing::subscriber::set_default(subscriber);
+
+        let span = tracing::info_span!("send");
+        let parent = opentelemetry::global::get_text_map_propagator(|propagator| {
+            let carrier = std::collections::HashMap::from([(
+                "traceparent".to_owned(),
+                format!("00-{trace_id}-00f067aa0ba902b7-01"),
+            )]);
+            propagator.extract(&carrier)
+        });
+        span.set_parent(parent);
+
+        let outcome = tracing::Instrument::instrument(
+            email_client.send_email(&email(), &subject(), &content()),
+            span,
+        )
+        .await;
+
+        assert!(outcome.is_ok());
+    }
+
     #[tokio::test]
     async fn send_email_fails_if_the_server_returns_500() {
         let mock_server = MockServer::start().await;
@@ -182,4 +238,4 @@ mod tests {
 
         assert!(outcome.is_err());
     }
-}
\ No newline at end of file
+}
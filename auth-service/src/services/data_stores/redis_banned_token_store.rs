@@ -1,36 +1,42 @@
-use std::sync::Arc;
-
-use redis::{Commands, Connection};
+use chrono::{DateTime, Utc};
+use redis::AsyncCommands;
 use secrecy::{ExposeSecret, Secret};
-use tokio::sync::RwLock;
 
 use color_eyre::eyre::{Context, Result};
 
 use crate::{
-    domain::data_stores::{BannedTokenStore, BannedTokenStoreError},
+    domain::{
+        data_stores::{BannedTokenStore, BannedTokenStoreError},
+        UserId,
+    },
+    services::RedisPool,
     utils::auth::TOKEN_TTL_SECONDS,
 };
 
 pub struct RedisBannedTokenStore {
-    conn: Arc<RwLock<Connection>>,
+    pool: RedisPool,
 }
 
 impl RedisBannedTokenStore {
     #[tracing::instrument(name = "Creting new RedisBannedTokenStore", skip_all)]
-    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
-        Self { conn }
+    pub fn new(pool: RedisPool) -> Self {
+        Self { pool }
     }
 }
 
 #[async_trait::async_trait]
 impl BannedTokenStore for RedisBannedTokenStore {
     #[tracing::instrument(name = "Adding token to banned token store", skip(self))]
-    async fn add_token(&mut self, token: Secret<String>) -> Result<()> {
+    async fn add_token(&self, token: Secret<String>) -> Result<()> {
         Ok(self
-            .conn
-            .write()
+            .pool
+            .get()
+            .set_ex(
+                get_key(token.expose_secret()),
+                true,
+                TOKEN_TTL_SECONDS as u64,
+            )
             .await
-            .set_ex(get_key(token.expose_secret()), true, TOKEN_TTL_SECONDS as u64)
             .wrap_err("failed to set banned token in Redis")
             .map_err(BannedTokenStoreError::UnexpectedError)?)
     }
@@ -38,19 +44,63 @@ impl BannedTokenStore for RedisBannedTokenStore {
     #[tracing::instrument(name = "Checking if banned token store contains token", skip(self))]
     async fn contains_token(&self, token: Secret<String>) -> Result<bool> {
         let exists: bool = self
-            .conn
-            .write()
-            .await
+            .pool
+            .get()
             .exists(get_key(token.expose_secret()))
+            .await
             .wrap_err("failed to check if token exists in Redis")
             .map_err(BannedTokenStoreError::UnexpectedError)?;
 
         Ok(exists)
     }
+
+    /// The cutoff expires with the last token it can affect. Keeping the latest of two cutoffs
+    /// is a read followed by a write, which is fine for the rare, operator-driven revocations.
+    #[tracing::instrument(name = "Revoking user tokens in Redis", skip(self))]
+    async fn revoke_user_tokens(
+        &self,
+        user_id: &UserId,
+        issued_before: DateTime<Utc>,
+    ) -> Result<()> {
+        let cutoff = match self.user_tokens_revoked_before(user_id).await? {
+            Some(current) => current.max(issued_before),
+            None => issued_before,
+        };
+
+        Ok(self
+            .pool
+            .get()
+            .set_ex(
+                get_revoked_user_key(user_id),
+                cutoff.timestamp_millis(),
+                TOKEN_TTL_SECONDS as u64,
+            )
+            .await
+            .wrap_err("failed to set revoked user tokens in Redis")
+            .map_err(BannedTokenStoreError::UnexpectedError)?)
+    }
+
+    #[tracing::instrument(name = "Getting user tokens revocation from Redis", skip(self))]
+    async fn user_tokens_revoked_before(&self, user_id: &UserId) -> Result<Option<DateTime<Utc>>> {
+        let cutoff: Option<i64> = self
+            .pool
+            .get()
+            .get(get_revoked_user_key(user_id))
+            .await
+            .wrap_err("failed to get revoked user tokens from Redis")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(cutoff.and_then(DateTime::from_timestamp_millis))
+    }
 }
 
 const BANNED_TOKEN_KEY_PREFIX: &str = "banned_token:";
+const REVOKED_USER_KEY_PREFIX: &str = "revoked_user_tokens:";
 
 fn get_key(token: &str) -> String {
     format!("{BANNED_TOKEN_KEY_PREFIX}{token}")
 }
+
+fn get_revoked_user_key(user_id: &UserId) -> String {
+    format!("{REVOKED_USER_KEY_PREFIX}{user_id}")
+}
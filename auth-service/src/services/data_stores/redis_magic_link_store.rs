@@ -0,0 +1,89 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Context};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    domain::{Email, MagicLink, MagicLinkStore, MagicLinkStoreError, MagicLinkToken},
+    services::RedisPool,
+};
+
+pub struct RedisMagicLinkStore {
+    pool: RedisPool,
+}
+
+impl RedisMagicLinkStore {
+    pub fn new(pool: RedisPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl MagicLinkStore for RedisMagicLinkStore {
+    #[tracing::instrument(name = "Adding magic link to Redis", skip_all)]
+    async fn add_link(
+        &self,
+        token: &MagicLinkToken,
+        link: MagicLink,
+    ) -> Result<(), MagicLinkStoreError> {
+        let ttl_seconds = (link.expires_at - Utc::now()).num_seconds().max(1) as u64;
+        let record = MagicLinkRecord {
+            email: link.email.as_ref().to_owned(),
+            nonce_hash: link.nonce_hash,
+            expires_at: link.expires_at.timestamp(),
+        };
+        let serialized_record = serde_json::to_string(&record)
+            .wrap_err("failed to serialize magic link")
+            .map_err(MagicLinkStoreError::UnexpectedError)?;
+
+        Ok(self
+            .pool
+            .get()
+            .set_ex(get_key(token), serialized_record, ttl_seconds)
+            .await
+            .wrap_err("failed to set magic link in Redis")
+            .map_err(MagicLinkStoreError::UnexpectedError)?)
+    }
+
+    #[tracing::instrument(name = "Taking magic link from Redis", skip_all)]
+    async fn take_link(&self, token: &MagicLinkToken) -> Result<MagicLink, MagicLinkStoreError> {
+        let serialized_record: Option<String> = self
+            .pool
+            .get()
+            .get_del(get_key(token))
+            .await
+            .wrap_err("failed to take magic link from Redis")
+            .map_err(MagicLinkStoreError::UnexpectedError)?;
+
+        let record: MagicLinkRecord =
+            serde_json::from_str(&serialized_record.ok_or(MagicLinkStoreError::LinkNotFound)?)
+                .map_err(|e| MagicLinkStoreError::UnexpectedError(e.into()))?;
+
+        let link = MagicLink {
+            email: Email::parse(&record.email)
+                .map_err(|e| MagicLinkStoreError::UnexpectedError(eyre!(e)))?,
+            nonce_hash: record.nonce_hash,
+            expires_at: DateTime::from_timestamp(record.expires_at, 0).ok_or(
+                MagicLinkStoreError::UnexpectedError(eyre!("invalid magic link timestamp")),
+            )?,
+        };
+
+        match link.is_expired() {
+            true => Err(MagicLinkStoreError::LinkNotFound),
+            false => Ok(link),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct MagicLinkRecord {
+    email: String,
+    nonce_hash: String,
+    expires_at: i64,
+}
+
+const MAGIC_LINK_PREFIX: &str = "magic_link:";
+
+fn get_key(token: &MagicLinkToken) -> String {
+    format!("{}{}", MAGIC_LINK_PREFIX, token.hash())
+}
@@ -0,0 +1,76 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use redis::{aio::ConnectionManager, Client, RedisResult};
+
+/// A fixed set of async Redis connections handed out round-robin.
+///
+/// Each `ConnectionManager` multiplexes commands over one connection and reconnects on its own
+/// when the connection drops, so cloning one out of the pool is cheap and needs no lock.
+#[derive(Clone)]
+pub struct RedisPool {
+    managers: Arc<Vec<ConnectionManager>>,
+    next: Arc<AtomicUsize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RedisPoolConfig {
+    pub size: usize,
+    pub command_timeout: Duration,
+    pub connect_timeout: Duration,
+    pub reconnect_retries: usize,
+}
+
+impl RedisPool {
+    #[tracing::instrument(name = "Creating Redis connection pool", skip(client))]
+    pub async fn new(client: Client, config: RedisPoolConfig) -> RedisResult<Self> {
+        let mut managers = Vec::with_capacity(config.size.max(1));
+        for _ in 0..config.size.max(1) {
+            managers.push(
+                ConnectionManager::new_with_backoff_and_timeouts(
+                    client.clone(),
+                    RECONNECT_BACKOFF_EXPONENT_BASE,
+                    RECONNECT_BACKOFF_FACTOR_MS,
+                    config.reconnect_retries,
+                    config.command_timeout,
+                    config.connect_timeout,
+                )
+                .await?,
+            );
+        }
+
+        Ok(Self {
+            managers: Arc::new(managers),
+            next: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    pub fn get(&self) -> ConnectionManager {
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.managers.len();
+        self.managers[index].clone()
+    }
+
+    /// Asks the server to close every connection. The managers would reconnect on the next
+    /// command, so this is only for shutting down.
+    pub async fn close(&self) {
+        for manager in self.managers.iter() {
+            let mut connection = manager.clone();
+            if let Err(e) = redis::cmd("QUIT")
+                .query_async::<_, ()>(&mut connection)
+                .await
+            {
+                tracing::warn!(error = ?e, "Failed to close Redis connection");
+            }
+        }
+    }
+}
+
+/// The `n`th reconnect attempt waits
+/// `RECONNECT_BACKOFF_FACTOR_MS * RECONNECT_BACKOFF_EXPONENT_BASE^n` milliseconds.
+const RECONNECT_BACKOFF_EXPONENT_BASE: u64 = 2;
+const RECONNECT_BACKOFF_FACTOR_MS: u64 = 100;
@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Context};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    domain::{Email, TrustedDevice, TrustedDeviceId, TrustedDeviceStore, TrustedDeviceStoreError},
+    services::RedisPool,
+};
+
+pub struct RedisTrustedDeviceStore {
+    pool: RedisPool,
+}
+
+impl RedisTrustedDeviceStore {
+    pub fn new(pool: RedisPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl TrustedDeviceStore for RedisTrustedDeviceStore {
+    #[tracing::instrument(name = "Adding trusted device to Redis", skip(self))]
+    async fn add_device(
+        &self,
+        email: Email,
+        device: TrustedDevice,
+    ) -> Result<(), TrustedDeviceStoreError> {
+        let record = TrustedDeviceRecord {
+            user_agent: device.user_agent,
+            created_at: device.created_at.timestamp(),
+            expires_at: device.expires_at.timestamp(),
+        };
+        let serialized_record = serde_json::to_string(&record)
+            .wrap_err("failed to serialize trusted device")
+            .map_err(TrustedDeviceStoreError::UnexpectedError)?;
+
+        let mut conn = self.pool.get();
+        let key = get_key(&email);
+
+        conn.hset::<_, _, _, ()>(&key, device.id.as_ref(), serialized_record)
+            .await
+            .wrap_err("failed to add trusted device in Redis")
+            .map_err(TrustedDeviceStoreError::UnexpectedError)?;
+
+        // The newest device always expires last, so the whole hash can share its lifetime.
+        Ok(conn
+            .expire_at(&key, device.expires_at.timestamp())
+            .await
+            .wrap_err("failed to set expiry on trusted devices in Redis")
+            .map_err(TrustedDeviceStoreError::UnexpectedError)?)
+    }
+
+    #[tracing::instrument(name = "Getting trusted devices from Redis", skip(self))]
+    async fn get_devices(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<TrustedDevice>, TrustedDeviceStoreError> {
+        let records: HashMap<String, String> = self
+            .pool
+            .get()
+            .hgetall(get_key(email))
+            .await
+            .wrap_err("failed to get trusted devices from Redis")
+            .map_err(TrustedDeviceStoreError::UnexpectedError)?;
+
+        let mut devices = records
+            .into_iter()
+            .map(|(id, record)| parse_device(id, &record))
+            .collect::<Result<Vec<_>, _>>()?;
+        devices.retain(|device| !device.is_expired());
+        devices.sort_by_key(|device| device.created_at);
+
+        Ok(devices)
+    }
+
+    #[tracing::instrument(name = "Checking trusted device in Redis", skip(self))]
+    async fn contains_device(
+        &self,
+        email: &Email,
+        device_id: &TrustedDeviceId,
+    ) -> Result<bool, TrustedDeviceStoreError> {
+        let record: Option<String> = self
+            .pool
+            .get()
+            .hget(get_key(email), device_id.as_ref())
+            .await
+            .wrap_err("failed to get trusted device from Redis")
+            .map_err(TrustedDeviceStoreError::UnexpectedError)?;
+
+        match record {
+            Some(record) => Ok(!parse_device(device_id.as_ref().to_owned(), &record)?.is_expired()),
+            None => Ok(false),
+        }
+    }
+
+    #[tracing::instrument(name = "Removing trusted device from Redis", skip(self))]
+    async fn remove_device(
+        &self,
+        email: &Email,
+        device_id: &TrustedDeviceId,
+    ) -> Result<(), TrustedDeviceStoreError> {
+        let removed: u32 = self
+            .pool
+            .get()
+            .hdel(get_key(email), device_id.as_ref())
+            .await
+            .wrap_err("failed to remove trusted device from Redis")
+            .map_err(TrustedDeviceStoreError::UnexpectedError)?;
+
+        match removed {
+            0 => Err(TrustedDeviceStoreError::DeviceNotFound),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct TrustedDeviceRecord {
+    user_agent: Option<String>,
+    created_at: i64,
+    expires_at: i64,
+}
+
+fn parse_device(id: String, record: &str) -> Result<TrustedDevice, TrustedDeviceStoreError> {
+    let record: TrustedDeviceRecord = serde_json::from_str(record)
+        .map_err(|e| TrustedDeviceStoreError::UnexpectedError(e.into()))?;
+    let invalid_timestamp =
+        || TrustedDeviceStoreError::UnexpectedError(eyre!("invalid trusted device timestamp"));
+
+    Ok(TrustedDevice {
+        id: TrustedDeviceId::parse(id)
+            .map_err(|e| TrustedDeviceStoreError::UnexpectedError(eyre!(e)))?,
+        user_agent: record.user_agent,
+        created_at: DateTime::<Utc>::from_timestamp(record.created_at, 0)
+            .ok_or_else(invalid_timestamp)?,
+        expires_at: DateTime::<Utc>::from_timestamp(record.expires_at, 0)
+            .ok_or_else(invalid_timestamp)?,
+    })
+}
+
+const TRUSTED_DEVICES_PREFIX: &str = "trusted_devices:";
+
+fn get_key(email: &Email) -> String {
+    format!("{}{}", TRUSTED_DEVICES_PREFIX, email.as_ref())
+}
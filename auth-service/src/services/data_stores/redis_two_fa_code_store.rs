@@ -1,21 +1,117 @@
-use std::sync::Arc;
-use redis::{Commands, Connection};
-use serde::{Deserialize, Serialize};
-use tokio::sync::RwLock;
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
 use color_eyre::eyre::{Context, Report};
+use redis::{aio::ConnectionManager, AsyncCommands, ExistenceCheck, SetExpiry, SetOptions};
 
-use crate::domain::{
-    data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
-    Email,
+use crate::{
+    domain::{
+        data_stores::{
+            LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError, TwoFAPurpose,
+            TwoFAResend,
+        },
+        Email,
+    },
+    services::RedisPool,
 };
 
 pub struct RedisTwoFACodeStore {
-    conn: Arc<RwLock<Connection>>,
+    pool: RedisPool,
+    max_pending_attempts: usize,
 }
 
 impl RedisTwoFACodeStore {
-    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
-        Self { conn }
+    /// Keeps at most `max_pending_attempts` codes per email, dropping the oldest first.
+    pub fn new(pool: RedisPool, max_pending_attempts: usize) -> Self {
+        Self {
+            pool,
+            max_pending_attempts,
+        }
+    }
+
+    async fn get_record(
+        conn: &mut ConnectionManager,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<TwoFARecord, TwoFACodeStoreError> {
+        let fields: HashMap<String, String> = conn
+            .hgetall(get_key(login_attempt_id))
+            .await
+            .wrap_err("failed to get 2FA code from Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        TwoFARecord::from_fields(&fields).ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    }
+
+    /// Increments a counter field of a pending code. A code that expired or was removed is
+    /// recreated by HINCRBY holding only the counter, so that leftover is deleted again.
+    async fn increment_field(
+        conn: &mut ConnectionManager,
+        login_attempt_id: &LoginAttemptId,
+        field: &str,
+    ) -> Result<u32, TwoFACodeStoreError> {
+        let key = get_key(login_attempt_id);
+        let (count, code): (u32, Option<String>) = redis::pipe()
+            .atomic()
+            .hincr(&key, field, 1)
+            .hget(&key, CODE_FIELD)
+            .query_async(conn)
+            .await
+            .wrap_err("failed to update 2FA record in Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        if code.is_none() {
+            conn.del::<_, ()>(&key)
+                .await
+                .wrap_err("failed to delete incomplete 2FA record from Redis")
+                .map_err(TwoFACodeStoreError::UnexpectedError)?;
+            return Err(TwoFACodeStoreError::LoginAttemptIdNotFound);
+        }
+
+        Ok(count)
+    }
+
+    async fn evict_oldest_pending(
+        &self,
+        conn: &mut ConnectionManager,
+        email: &Email,
+    ) -> Result<(), Report> {
+        let pending_key = get_pending_key(email);
+        let now = Utc::now().timestamp_millis();
+
+        conn.zrembyscore::<_, _, _, ()>(
+            &pending_key,
+            "-inf",
+            now - TEN_MINUTES_IN_SECONDS as i64 * 1000,
+        )
+        .await
+        .wrap_err("failed to drop expired pending 2FA attempts from Redis")?;
+
+        let pending: usize = conn
+            .zcard(&pending_key)
+            .await
+            .wrap_err("failed to count pending 2FA attempts in Redis")?;
+
+        if pending >= self.max_pending_attempts {
+            let evicted: Vec<String> = conn
+                .zrange(
+                    &pending_key,
+                    0,
+                    (pending - self.max_pending_attempts) as isize,
+                )
+                .await
+                .wrap_err("failed to get oldest pending 2FA attempts from Redis")?;
+
+            for login_attempt_id in evicted {
+                conn.del::<_, ()>(format!("{TWO_FA_CODE_PREFIX}{login_attempt_id}"))
+                    .await
+                    .wrap_err("failed to delete evicted 2FA code from Redis")?;
+                conn.zrem::<_, _, ()>(&pending_key, login_attempt_id)
+                    .await
+                    .wrap_err("failed to remove evicted 2FA attempt from Redis")?;
+            }
+        }
+
+        Ok(())
     }
 }
 
@@ -23,71 +119,263 @@ impl RedisTwoFACodeStore {
 impl TwoFACodeStore for RedisTwoFACodeStore {
     #[tracing::instrument(name = "Adding 2FA code to Redis", skip(self))]
     async fn add_code(
-        &mut self,
+        &self,
         email: Email,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
+        purpose: TwoFAPurpose,
     ) -> Result<(), TwoFACodeStoreError> {
-        let two_fa_tuple = TwoFATuple(
-            login_attempt_id.as_ref().to_owned(),
-            code.as_ref().to_owned(),
-        );
+        let now = Utc::now();
+        let key = get_key(&login_attempt_id);
+        let mut conn = self.pool.get();
 
-        let serialized_tuple = serde_json::to_string(&two_fa_tuple)
-            .wrap_err("failed to serialize 2FA tuple")
+        self.evict_oldest_pending(&mut conn, &email)
+            .await
             .map_err(TwoFACodeStoreError::UnexpectedError)?;
 
-        Ok(self
-            .conn
-            .write()
+        let mut fields = vec![
+            (EMAIL_FIELD, email.as_ref().to_owned()),
+            (CODE_FIELD, code.as_ref().to_owned()),
+            (PURPOSE_FIELD, purpose.as_ref().to_owned()),
+            (ATTEMPTS_FIELD, "0".to_owned()),
+            (RESENDS_FIELD, "0".to_owned()),
+            (LAST_SENT_AT_FIELD, now.timestamp().to_string()),
+        ];
+        if let Some(phone_number) = purpose.phone_number() {
+            fields.push((PHONE_NUMBER_FIELD, phone_number.as_ref().to_owned()));
+        }
+
+        redis::pipe()
+            .atomic()
+            .hset_multiple(&key, &fields)
+            .ignore()
+            .expire(&key, TEN_MINUTES_IN_SECONDS as i64)
+            .ignore()
+            .query_async::<_, ()>(&mut conn)
             .await
-            .set_ex(get_key(&email), serialized_tuple, TEN_MINUTES_IN_SECONDS)
             .wrap_err("failed to set 2FA code in Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        let pending_key = get_pending_key(&email);
+        conn.zadd::<_, _, _, ()>(
+            &pending_key,
+            login_attempt_id.as_ref(),
+            now.timestamp_millis(),
+        )
+        .await
+        .wrap_err("failed to add pending 2FA attempt in Redis")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(conn
+            .expire(&pending_key, TEN_MINUTES_IN_SECONDS as i64)
+            .await
+            .wrap_err("failed to set expiry on pending 2FA attempts in Redis")
             .map_err(TwoFACodeStoreError::UnexpectedError)?)
     }
 
     #[tracing::instrument(name = "Remove 2FA code to Redis", skip(self))]
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
-        Ok(self
-            .conn
-            .write()
+    async fn remove_code(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let mut conn = self.pool.get();
+
+        let record = Self::get_record(&mut conn, login_attempt_id).await?;
+
+        let deleted: u32 = conn
+            .del(get_key(login_attempt_id))
             .await
-            .del(get_key(email))
             .wrap_err("failed to delete 2FA code from Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        if deleted == 0 {
+            return Err(TwoFACodeStoreError::LoginAttemptIdNotFound);
+        }
+
+        let email = Email::parse(&record.email)
+            .map_err(|e| TwoFACodeStoreError::UnexpectedError(Report::msg(e)))?;
+
+        Ok(conn
+            .zrem(get_pending_key(&email), login_attempt_id.as_ref())
+            .await
+            .wrap_err("failed to remove pending 2FA attempt from Redis")
             .map_err(TwoFACodeStoreError::UnexpectedError)?)
     }
 
     #[tracing::instrument(name = "Get 2FA code from Redis", skip(self))]
     async fn get_code(
         &self,
-        email: &Email,
-    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
-        let serialized_tuple: String = self
-            .conn
-            .write()
-            .await
-            .get(get_key(email))
-            .wrap_err("failed to get 2FA code from Redis")
-            .map_err(|_| TwoFACodeStoreError::LoginAttemptIdNotFound)?;
-
-        let deserialized_tuple: TwoFATuple = serde_json::from_str(&serialized_tuple)
-            .map_err(|e| TwoFACodeStoreError::UnexpectedError(e.into()))?;
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(Email, TwoFACode), TwoFACodeStoreError> {
+        let record = Self::get_record(&mut self.pool.get(), login_attempt_id).await?;
 
         Ok((
-            LoginAttemptId::parse(deserialized_tuple.0)
+            Email::parse(&record.email)
                 .map_err(|e| TwoFACodeStoreError::UnexpectedError(Report::msg(e)))?,
-            TwoFACode::parse(deserialized_tuple.1)
+            TwoFACode::parse(record.code)
                 .map_err(|e| TwoFACodeStoreError::UnexpectedError(Report::msg(e)))?,
         ))
     }
+
+    #[tracing::instrument(name = "Get 2FA code purpose from Redis", skip(self))]
+    async fn get_purpose(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<TwoFAPurpose, TwoFACodeStoreError> {
+        let record = Self::get_record(&mut self.pool.get(), login_attempt_id).await?;
+
+        TwoFAPurpose::parse(&record.purpose, record.phone_number.as_deref())
+            .map_err(|e| TwoFACodeStoreError::UnexpectedError(Report::msg(e)))
+    }
+
+    #[tracing::instrument(name = "Record 2FA attempt in Redis", skip(self))]
+    async fn record_attempt(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<u32, TwoFACodeStoreError> {
+        Self::increment_field(&mut self.pool.get(), login_attempt_id, ATTEMPTS_FIELD).await
+    }
+
+    #[tracing::instrument(name = "Record issued 2FA code in Redis", skip(self))]
+    async fn record_code_issued(&self, email: &Email) -> Result<u32, TwoFACodeStoreError> {
+        let key = get_issued_key(email);
+
+        // Creating the counter with its expiry and incrementing it in one transaction means no
+        // failure can leave a counter without an expiry behind.
+        let (issued,): (u32,) = redis::pipe()
+            .atomic()
+            .set_options(
+                &key,
+                0,
+                SetOptions::default()
+                    .conditional_set(ExistenceCheck::NX)
+                    .with_expiration(SetExpiry::EX(ONE_HOUR_IN_SECONDS)),
+            )
+            .ignore()
+            .incr(&key, 1)
+            .query_async(&mut self.pool.get())
+            .await
+            .wrap_err("failed to increment issued 2FA codes in Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(issued)
+    }
+
+    #[tracing::instrument(name = "Record 2FA code resend in Redis", skip(self))]
+    async fn record_resend(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+        cooldown: Duration,
+        max_resends: u32,
+    ) -> Result<TwoFAResend, TwoFACodeStoreError> {
+        let mut conn = self.pool.get();
+        let record = Self::get_record(&mut conn, login_attempt_id).await?;
+
+        let retry_after = (record.last_sent_at + cooldown - Utc::now()).num_seconds();
+        if retry_after > 0 {
+            return Ok(TwoFAResend::RetryAfter(retry_after));
+        }
+        if record.resends >= max_resends {
+            return Ok(TwoFAResend::LimitReached);
+        }
+
+        // The record was read without a lock, so concurrent requests can all get this far. Only
+        // the one that sets the cooldown key resends, and the incremented count enforces the limit.
+        if cooldown.num_seconds() > 0 {
+            let claim_key = get_resend_key(login_attempt_id);
+            let claimed: Option<String> = conn
+                .set_options(
+                    &claim_key,
+                    1,
+                    SetOptions::default()
+                        .conditional_set(ExistenceCheck::NX)
+                        .with_expiration(SetExpiry::EX(cooldown.num_seconds() as usize)),
+                )
+                .await
+                .wrap_err("failed to claim 2FA code resend in Redis")
+                .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+            if claimed.is_none() {
+                let ttl: i64 = conn
+                    .ttl(&claim_key)
+                    .await
+                    .wrap_err("failed to get 2FA code resend cooldown from Redis")
+                    .map_err(TwoFACodeStoreError::UnexpectedError)?;
+                return Ok(TwoFAResend::RetryAfter(ttl.max(1)));
+            }
+        }
+
+        let resends = Self::increment_field(&mut conn, login_attempt_id, RESENDS_FIELD).await?;
+        if resends > max_resends {
+            return Ok(TwoFAResend::LimitReached);
+        }
+
+        conn.hset::<_, _, _, ()>(
+            get_key(login_attempt_id),
+            LAST_SENT_AT_FIELD,
+            Utc::now().timestamp(),
+        )
+        .await
+        .wrap_err("failed to record 2FA code resend in Redis")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(TwoFAResend::Recorded)
+    }
 }
 
-#[derive(Serialize, Deserialize)]
-struct TwoFATuple(pub String, pub String);
+struct TwoFARecord {
+    email: String,
+    code: String,
+    purpose: String,
+    phone_number: Option<String>,
+    resends: u32,
+    last_sent_at: DateTime<Utc>,
+}
+
+impl TwoFARecord {
+    /// Returns `None` unless all fields are present, as when the code expired or was removed.
+    fn from_fields(fields: &HashMap<String, String>) -> Option<Self> {
+        Some(Self {
+            email: fields.get(EMAIL_FIELD)?.clone(),
+            code: fields.get(CODE_FIELD)?.clone(),
+            purpose: fields.get(PURPOSE_FIELD)?.clone(),
+            phone_number: fields.get(PHONE_NUMBER_FIELD).cloned(),
+            resends: fields.get(RESENDS_FIELD)?.parse().ok()?,
+            last_sent_at: DateTime::from_timestamp(
+                fields.get(LAST_SENT_AT_FIELD)?.parse().ok()?,
+                0,
+            )?,
+        })
+    }
+}
 
 const TEN_MINUTES_IN_SECONDS: u64 = 600;
+const ONE_HOUR_IN_SECONDS: usize = 3600;
 const TWO_FA_CODE_PREFIX: &str = "two_fa_code:";
+const TWO_FA_PENDING_PREFIX: &str = "two_fa_pending:";
+const TWO_FA_ISSUED_PREFIX: &str = "two_fa_issued:";
+const TWO_FA_RESEND_PREFIX: &str = "two_fa_resend:";
+
+const EMAIL_FIELD: &str = "email";
+const CODE_FIELD: &str = "code";
+const PURPOSE_FIELD: &str = "purpose";
+const PHONE_NUMBER_FIELD: &str = "phone_number";
+const ATTEMPTS_FIELD: &str = "attempts";
+const RESENDS_FIELD: &str = "resends";
+const LAST_SENT_AT_FIELD: &str = "last_sent_at";
+
+fn get_key(login_attempt_id: &LoginAttemptId) -> String {
+    format!("{}{}", TWO_FA_CODE_PREFIX, login_attempt_id.as_ref())
+}
+
+fn get_pending_key(email: &Email) -> String {
+    format!("{}{}", TWO_FA_PENDING_PREFIX, email.as_ref())
+}
+
+fn get_issued_key(email: &Email) -> String {
+    format!("{}{}", TWO_FA_ISSUED_PREFIX, email.as_ref())
+}
 
-fn get_key(email: &Email) -> String {
-    format!("{}{}", TWO_FA_CODE_PREFIX, email.as_ref())
+fn get_resend_key(login_attempt_id: &LoginAttemptId) -> String {
+    format!("{}{}", TWO_FA_RESEND_PREFIX, login_attempt_id.as_ref())
 }
@@ -0,0 +1,173 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Context, Result};
+use redis::AsyncCommands;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    domain::{
+        Email, PasswordHash, PhoneNumber, StoredUser, TwoFAChannel, UserCache, UserId, UserStatus,
+    },
+    services::RedisPool,
+};
+
+/// A user cache shared by every instance through Redis. Redis expires the entries, so the size
+/// is bounded by the TTL and the server's own memory limit.
+///
+/// Each invalidation gives the user a new version, drawn from a shared counter so that no
+/// version is ever reused, and an entry cached under an older version reads as a miss. The
+/// version key lives at least as long as the entry it guards.
+pub struct RedisUserCache {
+    pool: RedisPool,
+    ttl: Duration,
+}
+
+impl RedisUserCache {
+    pub fn new(pool: RedisPool, ttl: Duration) -> Self {
+        Self { pool, ttl }
+    }
+}
+
+#[async_trait::async_trait]
+impl UserCache for RedisUserCache {
+    #[tracing::instrument(name = "Getting cached user from Redis", skip_all)]
+    async fn get(&self, email: &Email) -> Result<Option<StoredUser>> {
+        let (record, version): (Option<String>, Option<u64>) = self
+            .pool
+            .get()
+            .mget(&[get_key(email), get_version_key(email)])
+            .await
+            .wrap_err("failed to get cached user from Redis")?;
+
+        let Some(record) = record else {
+            return Ok(None);
+        };
+        let record: UserRecord =
+            serde_json::from_str(&record).wrap_err("failed to deserialize cached user")?;
+
+        match record.version == version.unwrap_or_default() {
+            true => parse_user(record).map(Some),
+            false => Ok(None),
+        }
+    }
+
+    #[tracing::instrument(name = "Getting cached user version from Redis", skip_all)]
+    async fn version(&self, email: &Email) -> Result<u64> {
+        let version: Option<u64> = self
+            .pool
+            .get()
+            .get(get_version_key(email))
+            .await
+            .wrap_err("failed to get cached user version from Redis")?;
+
+        Ok(version.unwrap_or_default())
+    }
+
+    #[tracing::instrument(name = "Caching user in Redis", skip_all)]
+    async fn insert(&self, user: &StoredUser, version: u64) -> Result<()> {
+        let record = serde_json::to_string(&UserRecord::new(user, version))
+            .wrap_err("failed to serialize cached user")?;
+        let ttl_seconds = self.ttl.as_secs().max(1);
+
+        redis::pipe()
+            .atomic()
+            .set_ex(get_key(user.email()), record, ttl_seconds)
+            .ignore()
+            .expire(get_version_key(user.email()), ttl_seconds as i64)
+            .ignore()
+            .query_async::<_, ()>(&mut self.pool.get())
+            .await
+            .wrap_err("failed to cache user in Redis")
+    }
+
+    #[tracing::instrument(name = "Invalidating cached user in Redis", skip_all)]
+    async fn invalidate(&self, email: &Email) -> Result<()> {
+        let mut conn = self.pool.get();
+        let version: u64 = conn
+            .incr(USER_CACHE_VERSION_COUNTER_KEY, 1)
+            .await
+            .wrap_err("failed to get a new cached user version from Redis")?;
+
+        redis::pipe()
+            .atomic()
+            .set_ex(get_version_key(email), version, self.ttl.as_secs().max(1))
+            .ignore()
+            .del(get_key(email))
+            .ignore()
+            .query_async::<_, ()>(&mut conn)
+            .await
+            .wrap_err("failed to invalidate cached user in Redis")
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct UserRecord {
+    id: String,
+    email: String,
+    password_hash: String,
+    requires_2fa: bool,
+    phone_number: Option<String>,
+    phone_verified: bool,
+    two_fa_channel: String,
+    status: String,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    last_login_at: Option<DateTime<Utc>>,
+    version: u64,
+}
+
+impl UserRecord {
+    fn new(user: &StoredUser, version: u64) -> Self {
+        Self {
+            id: user.id().to_string(),
+            email: user.email().as_ref().to_owned(),
+            password_hash: user.password_hash().as_ref().expose_secret().to_owned(),
+            requires_2fa: *user.requires_2fa(),
+            phone_number: user
+                .phone_number()
+                .as_ref()
+                .map(|phone_number| phone_number.as_ref().to_owned()),
+            phone_verified: *user.phone_verified(),
+            two_fa_channel: user.two_fa_channel().as_ref().to_owned(),
+            status: user.status().as_ref().to_owned(),
+            created_at: *user.created_at(),
+            updated_at: *user.updated_at(),
+            last_login_at: *user.last_login_at(),
+            version,
+        }
+    }
+}
+
+fn parse_user(record: UserRecord) -> Result<StoredUser> {
+    Ok(StoredUser {
+        id: UserId::parse(&record.id).map_err(|e| eyre!(e))?,
+        email: Email::parse(&record.email).map_err(|e| eyre!(e))?,
+        password_hash: PasswordHash::new(Secret::new(record.password_hash)),
+        requires_2fa: record.requires_2fa,
+        phone_number: record
+            .phone_number
+            .map(|phone_number| PhoneNumber::parse(&phone_number))
+            .transpose()
+            .map_err(|e| eyre!(e))?,
+        phone_verified: record.phone_verified,
+        two_fa_channel: TwoFAChannel::parse(&record.two_fa_channel).map_err(|e| eyre!(e))?,
+        status: UserStatus::parse(&record.status).map_err(|e| eyre!(e))?,
+        created_at: record.created_at,
+        updated_at: record.updated_at,
+        last_login_at: record.last_login_at,
+    })
+}
+
+const USER_CACHE_PREFIX: &str = "user_cache:";
+const USER_CACHE_VERSION_PREFIX: &str = "user_cache_version:";
+const USER_CACHE_VERSION_COUNTER_KEY: &str = "user_cache_versions";
+
+fn get_key(email: &Email) -> String {
+    format!("{}{}", USER_CACHE_PREFIX, email.as_ref())
+}
+
+fn get_version_key(email: &Email) -> String {
+    format!("{}{}", USER_CACHE_VERSION_PREFIX, email.as_ref())
+}
@@ -0,0 +1,503 @@
+use chrono::Utc;
+use color_eyre::eyre::eyre;
+use secrecy::ExposeSecret;
+use sqlx::{QueryBuilder, Sqlite, SqlitePool};
+
+use super::postgresuser_store::{PgUser, USER_COLUMNS};
+use crate::{
+    domain::{
+        data_stores::{UserStore, UserStoreError},
+        Email, NewUser, Password, PhoneNumber, StoredUser, TwoFAChannel, UserFilter, UserId,
+        UserPage, UserQuery, UserStatus, UserUpdate,
+    },
+    services::PasswordHasher,
+};
+
+pub struct SqliteUserStore {
+    pool: SqlitePool,
+    hasher: PasswordHasher,
+}
+
+impl SqliteUserStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self {
+            pool,
+            hasher: PasswordHasher::default(),
+        }
+    }
+
+    // The users table has the same columns as in Postgres, so rows decode into `PgUser`.
+    #[tracing::instrument(name = "Getting SQLite user from database", skip(self))]
+    async fn get_sqlite_user(&self, email: Email) -> Result<PgUser, UserStoreError> {
+        let result: Option<PgUser> = sqlx::query_as(&format!(
+            "SELECT {USER_COLUMNS} FROM users WHERE email = ?1"
+        ))
+        .bind(email.as_ref())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        result.ok_or(UserStoreError::UserNotFound)
+    }
+}
+
+/// Appends `filter` as `AND` conditions to a query that already has a `WHERE` clause.
+fn push_user_filter(builder: &mut QueryBuilder<'_, Sqlite>, filter: &UserFilter) {
+    if let Some(email_prefix) = &filter.email_prefix {
+        builder
+            .push(" AND substr(email, 1, ")
+            .push_bind(email_prefix.chars().count() as i64)
+            .push(") = ")
+            .push_bind(email_prefix.clone());
+    }
+    if let Some(requires_2fa) = filter.requires_2fa {
+        builder.push(" AND requires_2fa = ").push_bind(requires_2fa);
+    }
+    if let Some(status) = filter.status {
+        builder
+            .push(" AND status = ")
+            .push_bind(status.as_ref().to_owned());
+    }
+    if let Some(since) = filter.created_since {
+        builder.push(" AND created_at >= ").push_bind(since);
+    }
+    if let Some(until) = filter.created_until {
+        builder.push(" AND created_at < ").push_bind(until);
+    }
+}
+
+#[async_trait::async_trait]
+impl UserStore for SqliteUserStore {
+    #[tracing::instrument(name = "Adding user to SQLite", skip_all)]
+    async fn add_user(&self, user: NewUser) -> Result<StoredUser, UserStoreError> {
+        let password_hash = self
+            .hasher
+            .hash(user.password())
+            .await
+            .map_err(UserStoreError::UnexpectedError)?;
+        let user = StoredUser::new(user, password_hash);
+
+        let result = sqlx::query(
+            "INSERT INTO users (id, email, password_hash, requires_2fa, status, created_at, updated_at) \
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )
+        .bind(user.id().as_ref())
+        .bind(user.email().as_ref())
+        .bind(user.password_hash().as_ref().expose_secret())
+        .bind(user.requires_2fa())
+        .bind(user.status().as_ref())
+        .bind(user.created_at())
+        .bind(user.updated_at())
+        .execute(&self.pool)
+        .await;
+
+        match result {
+            Ok(_) => Ok(user),
+            Err(sqlx::Error::Database(db_err)) => {
+                if db_err.is_unique_violation() {
+                    return Err(UserStoreError::UserAlreadyExists);
+                }
+                Err(UserStoreError::UnexpectedError(eyre!(db_err)))
+            }
+            Err(e) => Err(UserStoreError::UnexpectedError(e.into())),
+        }
+    }
+
+    #[tracing::instrument(name = "Retrieving user from SQLite", skip(self))]
+    async fn get_user(&self, email: Email) -> Result<StoredUser, UserStoreError> {
+        Ok(StoredUser::from(self.get_sqlite_user(email).await?))
+    }
+
+    #[tracing::instrument(name = "Retrieving user by id from SQLite", skip(self))]
+    async fn get_user_by_id(&self, id: &UserId) -> Result<StoredUser, UserStoreError> {
+        let result: Option<PgUser> =
+            sqlx::query_as(&format!("SELECT {USER_COLUMNS} FROM users WHERE id = ?1"))
+                .bind(id.as_ref())
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        result
+            .map(StoredUser::from)
+            .ok_or(UserStoreError::UserNotFound)
+    }
+
+    #[tracing::instrument(name = "Validating user credentials in SQLite", skip(self, password))]
+    async fn validate_user(
+        &self,
+        email: Email,
+        password: Password,
+    ) -> Result<StoredUser, UserStoreError> {
+        let user = StoredUser::from(self.get_sqlite_user(email).await?);
+
+        self.hasher
+            .verify(user.password_hash(), &password)
+            .await
+            .map_err(|_| UserStoreError::InvalidCredentials)?;
+
+        Ok(user)
+    }
+
+    #[tracing::instrument(name = "Updating user 2FA setting in SQLite", skip(self))]
+    async fn update_requires_2fa(
+        &self,
+        email: Email,
+        requires_2fa: bool,
+    ) -> Result<(), UserStoreError> {
+        let result =
+            sqlx::query("UPDATE users SET requires_2fa = ?2, updated_at = ?3 WHERE email = ?1")
+                .bind(email.as_ref())
+                .bind(requires_2fa)
+                .bind(Utc::now())
+                .execute(&self.pool)
+                .await
+                .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        match result.rows_affected() {
+            0 => Err(UserStoreError::UserNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Setting user phone number in SQLite", skip(self))]
+    async fn set_phone_number(
+        &self,
+        email: Email,
+        phone_number: PhoneNumber,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query(
+            "UPDATE users SET phone_number = ?2, phone_verified = FALSE, two_fa_channel = ?3, \
+            updated_at = ?4 WHERE email = ?1",
+        )
+        .bind(email.as_ref())
+        .bind(phone_number.as_ref())
+        .bind(TwoFAChannel::Email.as_ref())
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        match result.rows_affected() {
+            0 => Err(UserStoreError::UserNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Verifying user phone number in SQLite", skip(self))]
+    async fn verify_phone_number(
+        &self,
+        email: Email,
+        phone_number: PhoneNumber,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query(
+            "UPDATE users SET phone_verified = TRUE, updated_at = ?3 \
+            WHERE email = ?1 AND phone_number = ?2",
+        )
+        .bind(email.as_ref())
+        .bind(phone_number.as_ref())
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        match result.rows_affected() {
+            0 => {
+                self.get_sqlite_user(email).await?;
+                Err(UserStoreError::InvalidCredentials)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Updating user 2FA channel in SQLite", skip(self))]
+    async fn update_two_fa_channel(
+        &self,
+        email: Email,
+        two_fa_channel: TwoFAChannel,
+    ) -> Result<(), UserStoreError> {
+        let result =
+            sqlx::query("UPDATE users SET two_fa_channel = ?2, updated_at = ?3 WHERE email = ?1")
+                .bind(email.as_ref())
+                .bind(two_fa_channel.as_ref())
+                .bind(Utc::now())
+                .execute(&self.pool)
+                .await
+                .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        match result.rows_affected() {
+            0 => Err(UserStoreError::UserNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Updating user status in SQLite", skip(self))]
+    async fn update_status(&self, email: Email, status: UserStatus) -> Result<(), UserStoreError> {
+        let result = sqlx::query("UPDATE users SET status = ?2, updated_at = ?3 WHERE email = ?1")
+            .bind(email.as_ref())
+            .bind(status.as_ref())
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        match result.rows_affected() {
+            0 => Err(UserStoreError::UserNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Recording user login in SQLite", skip(self))]
+    async fn record_login(&self, email: Email) -> Result<(), UserStoreError> {
+        let result = sqlx::query("UPDATE users SET last_login_at = ?2 WHERE email = ?1")
+            .bind(email.as_ref())
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        match result.rows_affected() {
+            0 => Err(UserStoreError::UserNotFound),
+            _ => Ok(()),
+        }
+    }
+    #[tracing::instrument(name = "Updating user in SQLite", skip(self, update))]
+    async fn update_user(
+        &self,
+        id: &UserId,
+        update: UserUpdate,
+    ) -> Result<StoredUser, UserStoreError> {
+        let mut builder: QueryBuilder<Sqlite> = QueryBuilder::new("UPDATE users SET updated_at = ");
+        builder.push_bind(Utc::now());
+
+        if let Some(email) = &update.email {
+            builder
+                .push(", email = ")
+                .push_bind(email.as_ref().to_owned());
+        }
+        if let Some(password) = &update.password {
+            let password_hash = self
+                .hasher
+                .hash(password)
+                .await
+                .map_err(UserStoreError::UnexpectedError)?;
+            builder
+                .push(", password_hash = ")
+                .push_bind(password_hash.as_ref().expose_secret().to_owned());
+        }
+        if let Some(requires_2fa) = update.requires_2fa {
+            builder.push(", requires_2fa = ").push_bind(requires_2fa);
+        }
+        if let Some(status) = update.status {
+            builder
+                .push(", status = ")
+                .push_bind(status.as_ref().to_owned());
+        }
+        builder
+            .push(" WHERE id = ")
+            .push_bind(*id.as_ref())
+            .push(format!(" RETURNING {USER_COLUMNS}"));
+
+        let result = builder
+            .build_query_as::<PgUser>()
+            .fetch_optional(&self.pool)
+            .await;
+
+        match result {
+            Ok(Some(user)) => Ok(StoredUser::from(user)),
+            Ok(None) => Err(UserStoreError::UserNotFound),
+            Err(sqlx::Error::Database(db_err)) if db_err.is_unique_violation() => {
+                Err(UserStoreError::UserAlreadyExists)
+            }
+            Err(e) => Err(UserStoreError::UnexpectedError(e.into())),
+        }
+    }
+
+    #[tracing::instrument(name = "Deleting user from SQLite", skip(self))]
+    async fn delete_user(&self, id: &UserId) -> Result<(), UserStoreError> {
+        let result = sqlx::query("DELETE FROM users WHERE id = ?1")
+            .bind(id.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        match result.rows_affected() {
+            0 => Err(UserStoreError::UserNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Counting users in SQLite", skip(self))]
+    async fn count_users(&self, filter: &UserFilter) -> Result<u64, UserStoreError> {
+        let mut builder: QueryBuilder<Sqlite> =
+            QueryBuilder::new("SELECT COUNT(*) FROM users WHERE TRUE");
+        push_user_filter(&mut builder, filter);
+
+        let count: i64 = builder
+            .build_query_scalar()
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        Ok(count as u64)
+    }
+
+    #[tracing::instrument(name = "Listing users in SQLite", skip(self))]
+    async fn list_users(&self, query: &UserQuery) -> Result<UserPage, UserStoreError> {
+        let mut builder: QueryBuilder<Sqlite> =
+            QueryBuilder::new(format!("SELECT {USER_COLUMNS} FROM users WHERE TRUE"));
+        push_user_filter(&mut builder, &query.filter);
+
+        if let Some(after) = query.after {
+            builder
+                .push(" AND (created_at, id) > (")
+                .push_bind(after.created_at)
+                .push(", ")
+                .push_bind(*after.id.as_ref())
+                .push(")");
+        }
+        // One extra row tells whether there is a next page.
+        builder
+            .push(" ORDER BY created_at, id LIMIT ")
+            .push_bind(i64::from(query.limit) + 1);
+
+        let users = builder
+            .build_query_as::<PgUser>()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+            .into_iter()
+            .map(StoredUser::from)
+            .collect();
+
+        Ok(UserPage::from_rows(users, query.limit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+
+    async fn store() -> SqliteUserStore {
+        // A single connection keeps every query on the same in-memory database.
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations_sqlite")
+            .run(&pool)
+            .await
+            .unwrap();
+
+        SqliteUserStore::new(pool)
+    }
+
+    fn user(requires_2fa: bool) -> NewUser {
+        NewUser::new(
+            Email::parse("test@email.com").unwrap(),
+            Password::parse(Secret::new("password123".to_owned())).unwrap(),
+            requires_2fa,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_add_user() {
+        let store = store().await;
+
+        let stored = store.add_user(user(false)).await.unwrap();
+        assert_eq!(store.get_user(stored.email().clone()).await, Ok(stored));
+        assert_eq!(
+            store.add_user(user(false)).await,
+            Err(UserStoreError::UserAlreadyExists)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validate_user() {
+        let store = store().await;
+        let stored = store.add_user(user(true)).await.unwrap();
+
+        let email = Email::parse("test@email.com").unwrap();
+        assert_eq!(
+            store
+                .validate_user(
+                    email.clone(),
+                    Password::parse(Secret::new("password123".to_owned())).unwrap()
+                )
+                .await,
+            Ok(stored)
+        );
+        assert_eq!(
+            store
+                .validate_user(
+                    email.clone(),
+                    Password::parse(Secret::new("wrongpassword".to_owned())).unwrap()
+                )
+                .await,
+            Err(UserStoreError::InvalidCredentials)
+        );
+        assert!(*store.get_user(email).await.unwrap().requires_2fa());
+    }
+
+    #[tokio::test]
+    async fn test_verify_phone_number() {
+        let store = store().await;
+        store.add_user(user(true)).await.unwrap();
+
+        let email = Email::parse("test@email.com").unwrap();
+        let phone_number = PhoneNumber::parse("+447700900123").unwrap();
+        store
+            .set_phone_number(email.clone(), phone_number.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store
+                .verify_phone_number(email.clone(), PhoneNumber::parse("+447700900999").unwrap())
+                .await,
+            Err(UserStoreError::InvalidCredentials)
+        );
+        assert_eq!(
+            store.verify_phone_number(email.clone(), phone_number).await,
+            Ok(())
+        );
+        assert!(*store.get_user(email).await.unwrap().phone_verified());
+    }
+
+    #[tokio::test]
+    async fn test_update_missing_user() {
+        let store = store().await;
+
+        assert_eq!(
+            store
+                .update_requires_2fa(Email::parse("test@email.com").unwrap(), true)
+                .await,
+            Err(UserStoreError::UserNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_user_by_id_round_trips_new_columns() {
+        let store = store().await;
+        let user = store.add_user(user(false)).await.unwrap();
+
+        store
+            .update_status(user.email().clone(), UserStatus::Disabled)
+            .await
+            .unwrap();
+        store.record_login(user.email().clone()).await.unwrap();
+
+        let stored = store.get_user_by_id(user.id()).await.unwrap();
+        assert_eq!(stored.email(), user.email());
+        assert_eq!(stored.created_at(), user.created_at());
+        assert!(stored.is_disabled());
+        assert!(stored.last_login_at().is_some());
+        assert_eq!(
+            store.get_user_by_id(&UserId::default()).await,
+            Err(UserStoreError::UserNotFound)
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use color_eyre::eyre::Result;
+use reqwest::{Client, Url};
+use secrecy::{ExposeSecret, Secret};
+
+use crate::{
+    domain::{PhoneNumber, SmsClient},
+    utils::tracing::trace_context_headers,
+};
+
+pub struct TwilioSmsClient {
+    http_client: Client,
+    base_url: String,
+    sender: PhoneNumber,
+    account_sid: String,
+    authorization_token: Secret<String>,
+}
+
+impl TwilioSmsClient {
+    pub fn new(
+        base_url: String,
+        sender: PhoneNumber,
+        account_sid: String,
+        authorization_token: Secret<String>,
+        http_client: Client,
+    ) -> Self {
+        Self {
+            http_client,
+            base_url,
+            sender,
+            account_sid,
+            authorization_token,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl SmsClient for TwilioSmsClient {
+    #[tracing::instrument(name = "Sending SMS", skip_all)]
+    async fn send_sms(&self, recipient: &PhoneNumber, content: &str) -> Result<()> {
+        let base = Url::parse(&self.base_url)?;
+        let url = base.join(&format!(
+            "/2010-04-01/Accounts/{}/Messages.json",
+            self.account_sid
+        ))?;
+
+        let request_body = SendSmsRequest {
+            from: self.sender.as_ref(),
+            to: recipient.as_ref(),
+            body: content,
+        };
+
+        let request = self
+            .http_client
+            .post(url)
+            .headers(trace_context_headers())
+            .basic_auth(
+                &self.account_sid,
+                Some(self.authorization_token.expose_secret()),
+            )
+            .form(&request_body);
+
+        request.send().await?.error_for_status()?;
+
+        Ok(())
+    }
+}
+
+#[derive(serde::Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct SendSmsRequest<'a> {
+    from: &'a str,
+    to: &'a str,
+    body: &'a str,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::constants::test;
+
+    use super::*;
+    use fake::faker::lorem::en::Sentence;
+    use fake::{Fake, Faker};
+    use wiremock::matchers::{any, header, header_exists, method, path};
+    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+    const ACCOUNT_SID: &str = "AC00000000000000000000000000000000";
+
+    fn content() -> String {
+        Sentence(1..5).fake()
+    }
+
+    fn phone_number() -> PhoneNumber {
+        let digits: u64 = (10_000_000_000..99_999_999_999).fake();
+        PhoneNumber::parse(&format!("+{digits}")).unwrap()
+    }
+
+    fn sms_client(base_url: String) -> TwilioSmsClient {
+        let http_client = Client::builder()
+            .timeout(test::sms_client::TIMEOUT)
+            .build()
+            .unwrap();
+        TwilioSmsClient::new(
+            base_url,
+            phone_number(),
+            ACCOUNT_SID.to_owned(),
+            Secret::new(Faker.fake()),
+            http_client,
+        )
+    }
+
+    struct SendSmsBodyMatcher;
+
+    impl wiremock::Match for SendSmsBodyMatcher {
+        fn matches(&self, request: &Request) -> bool {
+            let body = String::from_utf8_lossy(&request.body);
+            let fields: Vec<&str> = body
+                .split('&')
+                .filter_map(|pair| pair.split('=').next())
+                .collect();
+            ["From", "To", "Body"]
+                .iter()
+                .all(|name| fields.contains(name))
+        }
+    }
+
+    #[tokio::test]
+    async fn send_sms_sends_the_expected_request() {
+        let mock_server = MockServer::start().await;
+        let sms_client = sms_client(mock_server.uri());
+
+        Mock::given(header_exists("Authorization"))
+            .and(header("Content-Type", "application/x-www-form-urlencoded"))
+            .and(path(format!(
+                "/2010-04-01/Accounts/{ACCOUNT_SID}/Messages.json"
+            )))
+            .and(method("POST"))
+            .and(SendSmsBodyMatcher)
+            .respond_with(ResponseTemplate::new(201))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = sms_client.send_sms(&phone_number(), &content()).await;
+
+        assert!(outcome.is_ok());
+    }
+
+    #[tokio::test]
+    async fn send_sms_fails_if_the_server_returns_500() {
+        let mock_server = MockServer::start().await;
+        let sms_client = sms_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = sms_client.send_sms(&phone_number(), &content()).await;
+
+        assert!(outcome.is_err());
+    }
+
+    #[tokio::test]
+    async fn send_sms_times_out_if_the_server_takes_too_long() {
+        let mock_server = MockServer::start().await;
+        let sms_client = sms_client(mock_server.uri());
+
+        let response = ResponseTemplate::new(201).set_delay(std::time::Duration::from_secs(180));
+        Mock::given(any())
+            .respond_with(response)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = sms_client.send_sms(&phone_number(), &content()).await;
+
+        assert!(outcome.is_err());
+    }
+}
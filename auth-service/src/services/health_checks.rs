@@ -0,0 +1,115 @@
+use color_eyre::eyre::{Context, Result};
+use sqlx::{Connection, Database, Pool};
+
+use crate::{domain::HealthCheck, services::RedisPool};
+
+/// Checks a connection can be taken from the pool and still talks to the database.
+pub struct DatabaseHealthCheck<DB: Database> {
+    pool: Pool<DB>,
+}
+
+impl<DB: Database> DatabaseHealthCheck<DB> {
+    pub fn new(pool: Pool<DB>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl<DB: Database> HealthCheck for DatabaseHealthCheck<DB> {
+    #[tracing::instrument(name = "Checking database health", skip_all)]
+    async fn check(&self) -> Result<()> {
+        let mut connection = self
+            .pool
+            .acquire()
+            .await
+            .wrap_err("failed to acquire a database connection")?;
+        connection
+            .ping()
+            .await
+            .wrap_err("failed to ping the database")
+    }
+}
+
+pub struct RedisHealthCheck {
+    pool: RedisPool,
+}
+
+impl RedisHealthCheck {
+    pub fn new(pool: RedisPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl HealthCheck for RedisHealthCheck {
+    #[tracing::instrument(name = "Checking Redis health", skip_all)]
+    async fn check(&self) -> Result<()> {
+        let mut connection = self.pool.get();
+        redis::cmd("PING")
+            .query_async::<_, String>(&mut connection)
+            .await
+            .wrap_err("failed to ping Redis")?;
+        Ok(())
+    }
+}
+
+/// Checks an HTTP API answers at `url`. Any response counts, since only reachability is checked.
+pub struct HttpHealthCheck {
+    url: String,
+    http_client: reqwest::Client,
+}
+
+impl HttpHealthCheck {
+    pub fn new(url: String, http_client: reqwest::Client) -> Self {
+        Self { url, http_client }
+    }
+}
+
+#[async_trait::async_trait]
+impl HealthCheck for HttpHealthCheck {
+    #[tracing::instrument(name = "Checking HTTP API health", skip_all)]
+    async fn check(&self) -> Result<()> {
+        self.http_client
+            .head(&self.url)
+            .send()
+            .await
+            .wrap_err_with(|| format!("failed to reach {}", self.url))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::sqlite::SqlitePoolOptions;
+    use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_database_health_check() {
+        let pool = SqlitePoolOptions::new()
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let check = DatabaseHealthCheck::new(pool.clone());
+        assert!(check.check().await.is_ok());
+
+        pool.close().await;
+        assert!(check.check().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_http_health_check_accepts_any_response() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("HEAD"))
+            .respond_with(ResponseTemplate::new(405))
+            .mount(&mock_server)
+            .await;
+        let check = HttpHealthCheck::new(mock_server.uri(), reqwest::Client::new());
+        assert!(check.check().await.is_ok());
+
+        let unreachable =
+            HttpHealthCheck::new("http://127.0.0.1:1".to_owned(), reqwest::Client::new());
+        assert!(unreachable.check().await.is_err());
+    }
+}
@@ -1,3 +1,7 @@
 pub mod data_stores;
+pub mod health_checks;
+pub mod password_hasher;
 
-pub use data_stores::*;
\ No newline at end of file
+pub use data_stores::*;
+pub use health_checks::*;
+pub use password_hasher::*;
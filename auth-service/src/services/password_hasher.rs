@@ -0,0 +1,128 @@
+use std::time::Instant;
+
+use argon2::{
+    password_hash::{rand_core::OsRng, SaltString},
+    Algorithm, Argon2, Params, PasswordHash as PhcHash, PasswordHasher as _, PasswordVerifier,
+    Version,
+};
+use color_eyre::eyre::{Context, Result};
+use secrecy::{ExposeSecret, Secret};
+
+use crate::{
+    domain::{Password, PasswordHash},
+    utils::metrics::record_password_hash_duration,
+};
+
+/// Hashes and verifies passwords with Argon2id. Every `UserStore` goes through this, so all
+/// backends store and check credentials the same way.
+#[derive(Clone, Debug)]
+pub struct PasswordHasher {
+    params: Params,
+}
+
+impl Default for PasswordHasher {
+    fn default() -> Self {
+        Self {
+            params: Params::new(15000, 2, 1, None).expect("valid Argon2 parameters"),
+        }
+    }
+}
+
+impl PasswordHasher {
+    #[tracing::instrument(name = "Computing password hash", skip_all)]
+    pub async fn hash(&self, password: &Password) -> Result<PasswordHash> {
+        let current_span: tracing::Span = tracing::Span::current();
+        let params = self.params.clone();
+        let password = password.as_ref().clone();
+
+        let result = tokio::task::spawn_blocking(move || {
+            current_span.in_scope(|| {
+                let _timer = HashTimer::start("hash");
+                let salt: SaltString = SaltString::generate(&mut OsRng);
+                let password_hash = Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                    .hash_password(password.expose_secret().as_bytes(), &salt)?
+                    .to_string();
+
+                Ok(PasswordHash::new(Secret::new(password_hash)))
+            })
+        })
+        .await;
+
+        result?
+    }
+
+    /// Fails if `candidate` does not match `expected`. The parameters are read from the hash, so
+    /// hashes made with older parameters still verify.
+    #[tracing::instrument(name = "Verify password hash", skip_all)]
+    pub async fn verify(&self, expected: &PasswordHash, candidate: &Password) -> Result<()> {
+        let current_span: tracing::Span = tracing::Span::current();
+        let expected = expected.as_ref().clone();
+        let candidate = candidate.as_ref().clone();
+
+        let result = tokio::task::spawn_blocking(move || {
+            current_span.in_scope(|| {
+                let _timer = HashTimer::start("verify");
+                let expected_password_hash = PhcHash::new(expected.expose_secret())?;
+
+                Argon2::default()
+                    .verify_password(
+                        candidate.expose_secret().as_bytes(),
+                        &expected_password_hash,
+                    )
+                    .wrap_err("failed to verify password hash")
+            })
+        })
+        .await;
+
+        result?
+    }
+}
+
+/// Records the duration of an Argon2 operation when dropped, so failures are timed too.
+struct HashTimer {
+    operation: &'static str,
+    started: Instant,
+}
+
+impl HashTimer {
+    fn start(operation: &'static str) -> Self {
+        Self {
+            operation,
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Drop for HashTimer {
+    fn drop(&mut self) {
+        record_password_hash_duration(self.operation, self.started.elapsed());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn password(s: &str) -> Password {
+        Password::parse(Secret::new(s.to_owned())).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_hash_and_verify() {
+        let hasher = PasswordHasher::default();
+        let hash = hasher.hash(&password("MySecretPassword")).await.unwrap();
+
+        assert_ne!(
+            hash.as_ref().expose_secret(),
+            password("MySecretPassword").as_ref().expose_secret()
+        );
+        assert!(hasher
+            .verify(&hash, &password("MySecretPassword"))
+            .await
+            .is_ok());
+        assert!(hasher
+            .verify(&hash, &password("NotMySecretPassword"))
+            .await
+            .is_err());
+    }
+}
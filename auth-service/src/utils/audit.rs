@@ -0,0 +1,92 @@
+use std::{convert::Infallible, net::SocketAddr};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header, request::Parts},
+};
+use chrono::Utc;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuditEvent, AuditEventKind, AuditOutcome, AuthAPIError, UserId},
+    utils::constants::REQUEST_ID_HEADER,
+};
+
+/// Details of the client making a request, attached to the audit events it causes.
+#[derive(Clone, Debug, Default)]
+pub struct RequestMetadata {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for RequestMetadata {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let header_value = |name: &str| {
+            parts
+                .headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned)
+        };
+
+        Ok(Self {
+            ip: parts
+                .extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(address)| address.ip().to_string()),
+            user_agent: header_value(header::USER_AGENT.as_str()),
+            request_id: header_value(REQUEST_ID_HEADER),
+        })
+    }
+}
+
+/// Records an audit event. A failure to record is logged, not returned, so an audit log outage
+/// never blocks a login.
+#[tracing::instrument(name = "Recording audit event", skip(state, metadata))]
+pub async fn record_audit_event(
+    state: &AppState,
+    metadata: &RequestMetadata,
+    kind: AuditEventKind,
+    outcome: AuditOutcome,
+    actor: Option<String>,
+    user_id: Option<&UserId>,
+    reason: Option<String>,
+) {
+    let event = AuditEvent {
+        occurred_at: Utc::now(),
+        kind,
+        outcome,
+        actor,
+        user_id: user_id.map(|user_id| user_id.to_string()),
+        ip: metadata.ip.clone(),
+        user_agent: metadata.user_agent.clone(),
+        reason,
+        request_id: metadata.request_id.clone(),
+    };
+
+    if let Err(e) = state.audit_log.record(event).await {
+        tracing::error!(error = ?e, "failed to record audit event");
+    }
+}
+
+/// Records the outcome of a handler: success, or failure with the error as the reason.
+pub async fn record_audit_result<T>(
+    state: &AppState,
+    metadata: &RequestMetadata,
+    kind: AuditEventKind,
+    actor: Option<String>,
+    user_id: Option<&UserId>,
+    result: &Result<T, AuthAPIError>,
+) {
+    let (outcome, reason) = match result {
+        Ok(_) => (AuditOutcome::Success, None),
+        Err(e) => (AuditOutcome::Failure, Some(e.to_string())),
+    };
+
+    record_audit_event(state, metadata, kind, outcome, actor, user_id, reason).await;
+}
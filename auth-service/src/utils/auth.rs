@@ -1,25 +1,42 @@
-use axum_extra::extract::cookie::{Cookie, SameSite};
-use chrono::Utc;
+use axum::http::{header, HeaderMap};
+use axum_extra::extract::{
+    cookie::{Cookie, SameSite},
+    CookieJar,
+};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Context, ContextCompat, Result};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Validation};
 use secrecy::{ExposeSecret, Secret};
 use serde::{Deserialize, Serialize};
-use color_eyre::eyre::{eyre, Context, ContextCompat, Result};
+use subtle::ConstantTimeEq;
 
-use crate::{app_state::AppState, domain::email::Email, utils::constants::JWT_SECRET};
-use super::constants::JWT_COOKIE_NAME;
+use crate::{
+    app_state::AppState,
+    domain::{email::Email, AuthAPIError, MagicLinkToken, StoredUser, TrustedDeviceId, UserId},
+    utils::{
+        constants::{JWT_COOKIE_NAME, MAGIC_LINK_NONCE_COOKIE_NAME, TRUSTED_DEVICE_COOKIE_NAME},
+        metrics::{record_banned_token_hit, record_token_validation},
+    },
+};
 
-#[tracing::instrument(name = "Generating auth cookie")]
-pub fn generate_auth_cookie(email: &Email) -> Result<Cookie<'static>> {
-    let token = generate_auth_token(email)?;
-    Ok(create_auth_cookie(token))
+/// `secure` marks the cookie as HTTPS-only, and is set whenever the service serves TLS.
+#[tracing::instrument(name = "Generating auth cookie", skip(jwt_secret))]
+pub fn generate_auth_cookie(
+    user_id: &UserId,
+    jwt_secret: &Secret<String>,
+    secure: bool,
+) -> Result<Cookie<'static>> {
+    let token = generate_auth_token(user_id, jwt_secret)?;
+    Ok(create_auth_cookie(token, secure))
 }
 
 #[tracing::instrument(name = "Creating auth cookie", skip_all)]
-fn create_auth_cookie(token: String) -> Cookie<'static> {
+fn create_auth_cookie(token: String, secure: bool) -> Cookie<'static> {
     let cookie = Cookie::build((JWT_COOKIE_NAME, token))
         .path("/")
         .http_only(true)
         .same_site(SameSite::Lax)
+        .secure(secure)
         .build();
 
     cookie
@@ -28,70 +45,253 @@ fn create_auth_cookie(token: String) -> Cookie<'static> {
 pub const TOKEN_TTL_SECONDS: i64 = 600; // 10 minutes
 
 #[tracing::instrument(name = "Generating auth token", skip_all)]
-fn generate_auth_token(email: &Email) -> Result<String> {
+fn generate_auth_token(user_id: &UserId, jwt_secret: &Secret<String>) -> Result<String> {
     let delta = chrono::Duration::try_seconds(TOKEN_TTL_SECONDS)
         .wrap_err("failed to create 10 minute time delta")?;
 
-    let exp = Utc::now()
+    let now = Utc::now();
+    let exp = now
         .checked_add_signed(delta)
         .ok_or(eyre!("failed to add 10 minutes to current time"))?
         .timestamp();
 
-    let exp: usize = exp.try_into().wrap_err(format!(
-        "failed to cast exp time to usize. exp time: {exp}"
-    ))?;
+    let exp: usize = exp
+        .try_into()
+        .wrap_err(format!("failed to cast exp time to usize. exp time: {exp}"))?;
+    let iat: usize = now
+        .timestamp()
+        .try_into()
+        .wrap_err("failed to cast iat time to usize")?;
 
-    let sub = email.as_ref().to_owned();
+    let sub = user_id.to_string();
 
-    let claims = Claims { sub, exp };
+    let claims = Claims { sub, exp, iat };
 
-    create_token(&claims)
+    create_token(&claims, jwt_secret)
 }
 
 #[tracing::instrument(name = "Validating token", skip_all)]
-pub async fn validate_token(
+pub async fn validate_token(state: &AppState, token: Secret<String>) -> Result<Claims> {
+    let (claims, _) = validate_token_for_user(state, token).await?;
+    Ok(claims)
+}
+
+/// Decodes the token and loads the user it was issued to, refusing banned tokens and disabled
+/// accounts.
+#[tracing::instrument(name = "Validating token for user", skip_all)]
+pub async fn validate_token_for_user(
     state: &AppState,
     token: Secret<String>,
-) -> Result<Claims> {
-    (!state
+) -> Result<(Claims, StoredUser)> {
+    let result = check_token_for_user(state, token).await;
+    record_token_validation(result.is_ok());
+    result
+}
+
+async fn check_token_for_user(
+    state: &AppState,
+    token: Secret<String>,
+) -> Result<(Claims, StoredUser)> {
+    let banned = state
         .banned_token_store
-        .read()
-        .await
         .contains_token(token.clone())
         .await
-        .map_err(|_| eyre!("Invalid token"))?)
-    .then_some(())
-    .ok_or(eyre!("Invalid token"))?;
-    decode::<Claims>(
+        .map_err(|_| eyre!("Invalid token"))?;
+    if banned {
+        record_banned_token_hit();
+        return Err(eyre!("Invalid token"));
+    }
+    let claims = decode::<Claims>(
         token.expose_secret(),
-        &DecodingKey::from_secret(JWT_SECRET.expose_secret().as_bytes()),
+        &DecodingKey::from_secret(state.settings.auth.jwt_secret.expose_secret().as_bytes()),
         &Validation::default(),
     )
     .map(|data| data.claims)
-    .wrap_err("failed to decode token")
+    .wrap_err("failed to decode token")?;
+
+    let user_id = UserId::parse(&claims.sub).map_err(|e| eyre!(e))?;
+    let revoked_before = state
+        .banned_token_store
+        .user_tokens_revoked_before(&user_id)
+        .await
+        .map_err(|_| eyre!("Invalid token"))?;
+    // `iat` only has second precision, so a token from the second of the cutoff counts as revoked.
+    if revoked_before.is_some_and(|cutoff| claims.iat as i64 <= cutoff.timestamp()) {
+        record_banned_token_hit();
+        return Err(eyre!("Invalid token"));
+    }
+
+    let user = state
+        .user_store
+        .get_user_by_id(&user_id)
+        .await
+        .wrap_err("failed to load token subject")?;
+
+    if user.is_disabled() {
+        return Err(eyre!("Account is disabled"));
+    }
+
+    Ok((claims, user))
+}
+
+#[tracing::instrument(name = "Authenticating request", skip_all)]
+pub async fn authenticate(state: &AppState, jar: &CookieJar) -> Result<Email, AuthAPIError> {
+    let cookie = jar.get(JWT_COOKIE_NAME).ok_or(AuthAPIError::MissingToken)?;
+
+    let (_, user) = validate_token_for_user(state, Secret::new(cookie.value().to_owned()))
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    Ok(user.email)
+}
+
+/// Checks the request's bearer token against `admin_token`. Every admin request is refused when
+/// no admin token is configured.
+#[tracing::instrument(name = "Authorizing admin request", skip_all)]
+pub fn authorize_admin(
+    headers: &HeaderMap,
+    admin_token: Option<&Secret<String>>,
+) -> Result<(), AuthAPIError> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(AuthAPIError::MissingToken)?;
+
+    let authorized = admin_token.is_some_and(|admin_token| {
+        token
+            .as_bytes()
+            .ct_eq(admin_token.expose_secret().as_bytes())
+            .into()
+    });
+
+    match authorized {
+        true => Ok(()),
+        false => Err(AuthAPIError::InvalidToken),
+    }
 }
 
 #[tracing::instrument(name = "Creating token", skip_all)]
-fn create_token(claims: &Claims) -> Result<String> {
+fn create_token<T: Serialize>(claims: &T, jwt_secret: &Secret<String>) -> Result<String> {
     encode(
         &jsonwebtoken::Header::default(),
         &claims,
-        &EncodingKey::from_secret(JWT_SECRET.expose_secret().as_bytes()),
-    ).wrap_err("failed to create token")
+        &EncodingKey::from_secret(jwt_secret.expose_secret().as_bytes()),
+    )
+    .wrap_err("failed to create token")
 }
 
+/// Claims of the auth token. `sub` is the user's id.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
     pub exp: usize,
+    /// When the token was issued, checked against the user's token revocations. Tokens issued
+    /// before the claim existed read as issued at the epoch.
+    #[serde(default)]
+    pub iat: usize,
+}
+
+/// Audience of trusted device tokens. `validate_token` rejects any token carrying an audience,
+/// so a trusted device cookie can never be used in place of the auth cookie.
+const TRUSTED_DEVICE_AUDIENCE: &str = "trusted-device";
+
+#[tracing::instrument(name = "Generating trusted device cookie", skip_all)]
+pub fn generate_trusted_device_cookie(
+    email: &Email,
+    device_id: &TrustedDeviceId,
+    expires_at: DateTime<Utc>,
+    jwt_secret: &Secret<String>,
+    secure: bool,
+) -> Result<Cookie<'static>> {
+    let exp: usize = expires_at.timestamp().try_into().wrap_err(format!(
+        "failed to cast exp time to usize. exp time: {expires_at}"
+    ))?;
+
+    let claims = TrustedDeviceClaims {
+        sub: email.as_ref().to_owned(),
+        aud: TRUSTED_DEVICE_AUDIENCE.to_owned(),
+        jti: device_id.as_ref().to_owned(),
+        exp,
+    };
+
+    let max_age = (expires_at - Utc::now()).num_seconds().max(0);
+    let cookie = Cookie::build((
+        TRUSTED_DEVICE_COOKIE_NAME,
+        create_token(&claims, jwt_secret)?,
+    ))
+    .path("/")
+    .http_only(true)
+    .same_site(SameSite::Lax)
+    .secure(secure)
+    .max_age(time::Duration::seconds(max_age))
+    .build();
+
+    Ok(cookie)
+}
+
+/// Returns the trusted device id from the request's trusted device cookie, provided the cookie
+/// was issued to `email`. Whether the device is still trusted is up to the trusted device store.
+#[tracing::instrument(name = "Reading trusted device cookie", skip_all)]
+pub fn trusted_device_id(
+    jar: &CookieJar,
+    email: &Email,
+    jwt_secret: &Secret<String>,
+) -> Option<TrustedDeviceId> {
+    let cookie = jar.get(TRUSTED_DEVICE_COOKIE_NAME)?;
+
+    let mut validation = Validation::default();
+    validation.set_audience(&[TRUSTED_DEVICE_AUDIENCE]);
+    validation.set_required_spec_claims(&["exp", "aud", "sub"]);
+
+    let claims = decode::<TrustedDeviceClaims>(
+        cookie.value(),
+        &DecodingKey::from_secret(jwt_secret.expose_secret().as_bytes()),
+        &validation,
+    )
+    .ok()?
+    .claims;
+
+    (claims.sub == email.as_ref())
+        .then(|| TrustedDeviceId::parse(claims.jti).ok())
+        .flatten()
+}
+
+#[tracing::instrument(name = "Creating magic link nonce cookie", skip_all)]
+pub fn create_magic_link_nonce_cookie(
+    nonce: &MagicLinkToken,
+    ttl_seconds: i64,
+    secure: bool,
+) -> Cookie<'static> {
+    Cookie::build((MAGIC_LINK_NONCE_COOKIE_NAME, nonce.as_ref().to_owned()))
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .secure(secure)
+        .max_age(time::Duration::seconds(ttl_seconds))
+        .build()
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrustedDeviceClaims {
+    pub sub: String,
+    pub aud: String,
+    pub jti: String,
+    pub exp: usize,
 }
 
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
 
-    use crate::services::{
-        HashmapTwoFACodeStore, HashmapUserStore, HashsetBannedTokenStore, MockEmailClient,
+    use crate::{
+        domain::{NewUser, Password, UserStatus},
+        services::{
+            HashmapMagicLinkStore, HashmapTrustedDeviceStore, HashmapTwoFACodeStore,
+            HashmapUserStore, HashsetBannedTokenStore, InMemoryAuditLog, MockEmailClient,
+            MockSmsClient,
+        },
+        utils::{constants::test, settings::Settings},
     };
 
     use super::*;
@@ -100,48 +300,67 @@ mod tests {
 
     static APP_STATE: Lazy<AppState> = Lazy::new(|| {
         AppState::new(
-            Arc::new(RwLock::new(Box::new(HashmapUserStore::default()))),
-            Arc::new(RwLock::new(Box::new(HashsetBannedTokenStore::default()))),
-            Arc::new(RwLock::new(Box::new(HashmapTwoFACodeStore::default()))),
+            Arc::new(Settings::from_toml(test::SETTINGS).unwrap()),
+            Arc::new(HashmapUserStore::default()),
+            Arc::new(HashsetBannedTokenStore::default()),
+            Arc::new(HashmapTwoFACodeStore::default()),
+            Arc::new(HashmapTrustedDeviceStore::default()),
+            Arc::new(HashmapMagicLinkStore::default()),
+            Arc::new(InMemoryAuditLog::default()),
             Arc::new(RwLock::new(Box::new(MockEmailClient))),
+            Some(Arc::new(RwLock::new(Box::new(MockSmsClient)))),
+            Arc::new(Vec::new()),
         )
     });
 
+    fn jwt_secret() -> &'static Secret<String> {
+        &APP_STATE.settings.auth.jwt_secret
+    }
+
+    async fn add_user(email: &str) -> StoredUser {
+        let user = NewUser::new(
+            Email::parse(email).unwrap(),
+            Password::parse(Secret::new("password123".to_owned())).unwrap(),
+            false,
+        );
+        APP_STATE.user_store.add_user(user).await.unwrap()
+    }
+
     #[tokio::test]
     async fn test_generate_auth_cookie() {
-        let email = Email::parse("test@example.com").unwrap();
-        let cookie = generate_auth_cookie(&email).unwrap();
+        let cookie = generate_auth_cookie(&UserId::default(), jwt_secret(), false).unwrap();
         assert_eq!(cookie.name(), JWT_COOKIE_NAME);
         assert_eq!(cookie.value().split('.').count(), 3);
         assert_eq!(cookie.path(), Some("/"));
         assert_eq!(cookie.http_only(), Some(true));
         assert_eq!(cookie.same_site(), Some(SameSite::Lax));
+        assert_eq!(cookie.secure(), Some(false));
     }
 
     #[tokio::test]
     async fn test_create_auth_cookie() {
         let token = "test_token".to_owned();
-        let cookie = create_auth_cookie(token.clone());
+        let cookie = create_auth_cookie(token.clone(), true);
         assert_eq!(cookie.name(), JWT_COOKIE_NAME);
         assert_eq!(cookie.value(), token);
         assert_eq!(cookie.path(), Some("/"));
         assert_eq!(cookie.http_only(), Some(true));
         assert_eq!(cookie.same_site(), Some(SameSite::Lax));
+        assert_eq!(cookie.secure(), Some(true));
     }
 
     #[tokio::test]
     async fn test_generate_auth_token() {
-        let email = Email::parse("test@example.com").unwrap();
-        let result = generate_auth_token(&email).unwrap();
+        let result = generate_auth_token(&UserId::default(), jwt_secret()).unwrap();
         assert_eq!(result.split('.').count(), 3);
     }
 
     #[tokio::test]
     async fn test_validate_token_with_valid_token() {
-        let email = Email::parse("test@example.com").unwrap();
-        let token = Secret::new(generate_auth_token(&email).unwrap());
+        let user = add_user("test@example.com").await;
+        let token = Secret::new(generate_auth_token(user.id(), jwt_secret()).unwrap());
         let result = validate_token(&APP_STATE, token).await.unwrap();
-        assert_eq!(result.sub, "test@example.com");
+        assert_eq!(result.sub, user.id().to_string());
 
         let exp = Utc::now()
             .checked_add_signed(chrono::Duration::try_minutes(9).expect("valid duration"))
@@ -157,4 +376,53 @@ mod tests {
         let result = validate_token(&APP_STATE, token).await;
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_validate_token_for_disabled_user() {
+        let user = add_user("disabled@example.com").await;
+        let token = Secret::new(generate_auth_token(user.id(), jwt_secret()).unwrap());
+        APP_STATE
+            .user_store
+            .update_status(user.email().clone(), UserStatus::Disabled)
+            .await
+            .unwrap();
+
+        let result = validate_token(&APP_STATE, token).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_issued_before_revocation() {
+        let user = add_user("revoked@example.com").await;
+        let token = Secret::new(generate_auth_token(user.id(), jwt_secret()).unwrap());
+        APP_STATE
+            .banned_token_store
+            .revoke_user_tokens(user.id(), Utc::now())
+            .await
+            .unwrap();
+
+        let result = validate_token(&APP_STATE, token).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_issued_after_revocation() {
+        let user = add_user("reissued@example.com").await;
+        APP_STATE
+            .banned_token_store
+            .revoke_user_tokens(user.id(), Utc::now() - chrono::Duration::seconds(5))
+            .await
+            .unwrap();
+        let token = Secret::new(generate_auth_token(user.id(), jwt_secret()).unwrap());
+
+        let result = validate_token(&APP_STATE, token).await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_for_unknown_user() {
+        let token = Secret::new(generate_auth_token(&UserId::default(), jwt_secret()).unwrap());
+        let result = validate_token(&APP_STATE, token).await;
+        assert!(result.is_err());
+    }
 }
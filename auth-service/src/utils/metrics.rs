@@ -0,0 +1,202 @@
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use color_eyre::eyre::{Context, Result};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use sqlx::{Database, Pool};
+use tokio::task::JoinHandle;
+
+use crate::domain::AuthAPIError;
+
+/// Histogram buckets, in seconds, for every `*_duration_seconds` metric.
+const DURATION_BUCKETS: &[f64] = &[
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Installs the global recorder that the `/metrics` endpoint renders.
+pub fn install_prometheus_recorder() -> Result<PrometheusHandle> {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Suffix("duration_seconds".to_owned()),
+            DURATION_BUCKETS,
+        )
+        .wrap_err("failed to set histogram buckets")?
+        .install_recorder()
+        .wrap_err("failed to install the Prometheus recorder")
+}
+
+/// Periodically drains the histograms of `handle`, which otherwise only happens on a scrape.
+pub fn spawn_metrics_upkeep(handle: PrometheusHandle, interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            handle.run_upkeep();
+        }
+    })
+}
+
+/// Counts requests and records their latency, labelled with the route template rather than the
+/// raw path so the number of series stays bounded.
+pub async fn track_http_metrics(request: Request, next: Next) -> Response {
+    let started = Instant::now();
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_owned())
+        .unwrap_or_else(|| "unmatched".to_owned());
+
+    let response = next.run(request).await;
+
+    let labels = [
+        ("method", method),
+        ("route", route),
+        ("status", response.status().as_u16().to_string()),
+    ];
+    metrics::counter!("http_requests_total", &labels).increment(1);
+    metrics::histogram!("http_request_duration_seconds", &labels).record(started.elapsed());
+
+    response
+}
+
+/// The outcome label of a handler result: `success`, or the kind of error it failed with.
+pub fn outcome_label<T>(result: &Result<T, AuthAPIError>) -> &'static str {
+    match result {
+        Ok(_) => "success",
+        Err(AuthAPIError::UserAlreadyExists) => "user_already_exists",
+        Err(AuthAPIError::InvalidCredentials) => "invalid_request",
+        Err(AuthAPIError::IncorrectCredentials) => "incorrect_credentials",
+        Err(AuthAPIError::AccountDisabled) => "account_disabled",
+        Err(AuthAPIError::MissingToken) => "missing_token",
+        Err(AuthAPIError::InvalidToken) => "invalid_token",
+        Err(AuthAPIError::TooManyRequests) => "too_many_requests",
+        Err(AuthAPIError::TrustedDeviceNotFound) => "trusted_device_not_found",
+        Err(AuthAPIError::PhoneNumberNotVerified) => "phone_number_not_verified",
+        Err(AuthAPIError::SmsNotEnabled) => "sms_not_enabled",
+        Err(AuthAPIError::ResendCooldown(_)) => "resend_cooldown",
+        Err(AuthAPIError::UnexpectedError(_)) => "error",
+    }
+}
+
+pub fn record_signup(outcome: &'static str) {
+    metrics::counter!("auth_signups_total", "outcome" => outcome).increment(1);
+}
+
+pub fn record_login_attempt(outcome: &'static str) {
+    metrics::counter!("auth_logins_total", "outcome" => outcome).increment(1);
+}
+
+pub fn record_2fa_verification(outcome: &'static str) {
+    metrics::counter!("auth_2fa_verifications_total", "outcome" => outcome).increment(1);
+}
+
+pub fn record_logout(outcome: &'static str) {
+    metrics::counter!("auth_logouts_total", "outcome" => outcome).increment(1);
+}
+
+pub fn record_token_validation(valid: bool) {
+    let outcome = match valid {
+        true => "valid",
+        false => "invalid",
+    };
+    metrics::counter!("auth_token_validations_total", "outcome" => outcome).increment(1);
+}
+
+pub fn record_banned_token_hit() {
+    metrics::counter!("auth_banned_token_hits_total").increment(1);
+}
+
+pub fn record_email_send_failure(provider: &'static str) {
+    metrics::counter!("email_send_failures_total", "provider" => provider).increment(1);
+}
+
+pub fn record_certificate_reload(loaded: bool) {
+    let outcome = match loaded {
+        true => "success",
+        false => "failure",
+    };
+    metrics::counter!("tls_certificate_reloads_total", "outcome" => outcome).increment(1);
+}
+
+/// Records how long an Argon2 `operation`, `hash` or `verify`, took.
+pub fn record_password_hash_duration(operation: &'static str, duration: Duration) {
+    metrics::histogram!("password_hash_duration_seconds", "operation" => operation)
+        .record(duration);
+}
+
+/// Periodically records the size of `pool` as gauges labelled with `name`.
+pub fn spawn_pool_metrics<DB: Database>(
+    pool: Pool<DB>,
+    name: &'static str,
+    interval: Duration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            record_pool_metrics(&pool, name);
+        }
+    })
+}
+
+pub fn record_pool_metrics<DB: Database>(pool: &Pool<DB>, name: &'static str) {
+    let size = pool.size() as f64;
+    let idle = pool.num_idle() as f64;
+
+    metrics::gauge!("db_pool_connections", "pool" => name, "state" => "idle").set(idle);
+    metrics::gauge!("db_pool_connections", "pool" => name, "state" => "in_use").set(size - idle);
+    metrics::gauge!("db_pool_max_connections", "pool" => name)
+        .set(pool.options().get_max_connections() as f64);
+}
+
+#[cfg(test)]
+mod tests {
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_record_pool_metrics() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(3)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let _connection = pool.acquire().await.unwrap();
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, || record_pool_metrics(&pool, "test"));
+
+        let gauges = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| {
+                let labels = key
+                    .key()
+                    .labels()
+                    .map(|label| label.value().to_owned())
+                    .collect::<Vec<_>>();
+                (key.key().name().to_owned(), labels, value)
+            })
+            .collect::<Vec<_>>();
+
+        assert!(gauges.contains(&(
+            "db_pool_connections".to_owned(),
+            vec!["test".to_owned(), "in_use".to_owned()],
+            DebugValue::Gauge(1.0.into())
+        )));
+        assert!(gauges.contains(&(
+            "db_pool_max_connections".to_owned(),
+            vec!["test".to_owned()],
+            DebugValue::Gauge(3.0.into())
+        )));
+    }
+}
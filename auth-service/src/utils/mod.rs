@@ -1,3 +1,8 @@
+pub mod audit;
 pub mod auth;
 pub mod constants;
+pub mod metrics;
+pub mod settings;
+pub mod shutdown;
+pub mod tls;
 pub mod tracing;
@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+/// Tells the listeners to stop accepting connections and drain the requests in flight. Every
+/// clone shares the same state, and triggering is permanent.
+#[derive(Clone, Debug)]
+pub struct ShutdownHandle {
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl Default for ShutdownHandle {
+    fn default() -> Self {
+        let (sender, _) = watch::channel(false);
+        Self {
+            sender: Arc::new(sender),
+        }
+    }
+}
+
+impl ShutdownHandle {
+    pub fn trigger(&self) {
+        self.sender.send_replace(true);
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.sender.borrow()
+    }
+
+    /// Resolves once `trigger` has been called, immediately if it already has.
+    pub async fn triggered(&self) {
+        let mut receiver = self.sender.subscribe();
+        // The sender lives as long as `self`, so this cannot fail.
+        let _ = receiver.wait_for(|triggered| *triggered).await;
+    }
+}
+
+/// Resolves on SIGINT, or SIGTERM on Unix.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to listen for SIGINT");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_clones_share_the_trigger() {
+        let shutdown = ShutdownHandle::default();
+        let waiter = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move { shutdown.triggered().await }
+        });
+        assert!(!shutdown.is_triggered());
+
+        shutdown.clone().trigger();
+
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .expect("waiter was not woken")
+            .unwrap();
+        assert!(shutdown.is_triggered());
+        // Already triggered, so this resolves at once.
+        shutdown.triggered().await;
+    }
+}
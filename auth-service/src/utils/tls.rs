@@ -0,0 +1,261 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use axum::{
+    extract::State,
+    http::{header, uri::Authority, HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Redirect, Response},
+    Router,
+};
+use axum_server::tls_rustls::RustlsConfig;
+use tokio::task::JoinHandle;
+
+use crate::utils::{
+    metrics::record_certificate_reload, settings::TlsSettings, shutdown::ShutdownHandle,
+};
+
+/// The certificate chain and private key served over HTTPS, loaded from their PEM files.
+pub struct Certificates {
+    config: RustlsConfig,
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    /// Contents of the files as last read, whether or not they could be loaded.
+    seen: PemFiles,
+}
+
+type PemFiles = (Vec<u8>, Vec<u8>);
+
+impl Certificates {
+    pub async fn load(settings: &TlsSettings) -> io::Result<Self> {
+        let cert_path = PathBuf::from(&settings.cert_path);
+        let key_path = PathBuf::from(&settings.key_path);
+        let (cert, key) = read_pem_files(&cert_path, &key_path).await?;
+        let config = RustlsConfig::from_pem(cert.clone(), key.clone())
+            .await
+            .map_err(|e| invalid_certificate(&cert_path, &key_path, e))?;
+
+        Ok(Self {
+            config,
+            cert_path,
+            key_path,
+            seen: (cert, key),
+        })
+    }
+
+    /// The config the listener serves. Reloads swap what it holds, so connections accepted
+    /// afterwards get the new certificate while open ones keep the old.
+    pub fn config(&self) -> RustlsConfig {
+        self.config.clone()
+    }
+
+    /// Reloads the certificate if either file changed since it was last read, returning whether
+    /// it did. Files that cannot be loaded, such as a certificate renewed ahead of its key, leave
+    /// the current certificate in place until they change again.
+    pub async fn reload_if_changed(&mut self) -> io::Result<bool> {
+        let files = read_pem_files(&self.cert_path, &self.key_path).await?;
+        if files == self.seen {
+            return Ok(false);
+        }
+
+        self.seen = files.clone();
+        let (cert, key) = files;
+        self.config
+            .reload_from_pem(cert, key)
+            .await
+            .map_err(|e| invalid_certificate(&self.cert_path, &self.key_path, e))?;
+
+        Ok(true)
+    }
+}
+
+async fn read_pem_files(cert_path: &Path, key_path: &Path) -> io::Result<PemFiles> {
+    let read = |path: &Path| {
+        let path = path.to_owned();
+        async move {
+            tokio::fs::read(&path).await.map_err(|e| {
+                io::Error::new(e.kind(), format!("failed to read {}: {e}", path.display()))
+            })
+        }
+    };
+
+    Ok((read(cert_path).await?, read(key_path).await?))
+}
+
+fn invalid_certificate(cert_path: &Path, key_path: &Path, e: io::Error) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "failed to load the certificate in {} with the key in {}: {e}",
+            cert_path.display(),
+            key_path.display()
+        ),
+    )
+}
+
+/// Checks the certificate files every `interval` and reloads them when they change, until
+/// `shutdown` is triggered.
+pub fn spawn_certificate_reload(
+    mut certificates: Certificates,
+    interval: Duration,
+    shutdown: ShutdownHandle,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        // The first tick completes at once, and the files were only just loaded.
+        ticker.tick().await;
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = shutdown.triggered() => return,
+            }
+
+            match certificates.reload_if_changed().await {
+                Ok(true) => {
+                    tracing::info!("Reloaded the TLS certificate");
+                    record_certificate_reload(true);
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    tracing::error!(error = ?e, "Failed to reload the TLS certificate");
+                    record_certificate_reload(false);
+                }
+            }
+        }
+    })
+}
+
+/// Routes every request to the same host and path on the HTTPS listener at `https_port`.
+pub fn redirect_to_https(https_port: u16) -> Router {
+    Router::new().fallback(redirect).with_state(https_port)
+}
+
+async fn redirect(State(https_port): State<u16>, headers: HeaderMap, uri: Uri) -> Response {
+    let host = headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .and_then(|host| host.parse::<Authority>().ok());
+
+    match host {
+        Some(host) => Redirect::permanent(&https_location(&host, https_port, &uri)).into_response(),
+        None => StatusCode::BAD_REQUEST.into_response(),
+    }
+}
+
+fn https_location(host: &Authority, https_port: u16, uri: &Uri) -> String {
+    let path = uri
+        .path_and_query()
+        .map_or("/", |path_and_query| path_and_query.as_str());
+
+    match https_port {
+        443 => format!("https://{}{path}", host.host()),
+        port => format!("https://{}:{port}{path}", host.host()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use uuid::Uuid;
+
+    use super::*;
+
+    struct TestFiles {
+        dir: PathBuf,
+        settings: TlsSettings,
+    }
+
+    impl TestFiles {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("auth-service-tls-{}", Uuid::new_v4()));
+            std::fs::create_dir_all(&dir).unwrap();
+            let settings = TlsSettings {
+                enabled: true,
+                cert_path: dir.join("cert.pem").display().to_string(),
+                key_path: dir.join("key.pem").display().to_string(),
+                reload_interval_seconds: 1,
+                redirect_address: None,
+            };
+
+            Self { dir, settings }
+        }
+
+        fn write_certificate(&self) {
+            let certified =
+                rcgen::generate_simple_self_signed(vec!["localhost".to_owned()]).unwrap();
+            std::fs::write(&self.settings.cert_path, certified.cert.pem()).unwrap();
+            std::fs::write(&self.settings.key_path, certified.key_pair.serialize_pem()).unwrap();
+        }
+    }
+
+    impl Drop for TestFiles {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reloads_only_when_the_files_change() {
+        let files = TestFiles::new();
+        files.write_certificate();
+        let mut certificates = Certificates::load(&files.settings).await.unwrap();
+        let loaded = certificates.config().get_inner();
+
+        assert!(!certificates.reload_if_changed().await.unwrap());
+        assert!(Arc::ptr_eq(&loaded, &certificates.config().get_inner()));
+
+        files.write_certificate();
+        assert!(certificates.reload_if_changed().await.unwrap());
+        assert!(!Arc::ptr_eq(&loaded, &certificates.config().get_inner()));
+    }
+
+    #[tokio::test]
+    async fn test_keeps_the_current_certificate_when_the_new_one_is_invalid() {
+        let files = TestFiles::new();
+        files.write_certificate();
+        let mut certificates = Certificates::load(&files.settings).await.unwrap();
+        let loaded = certificates.config().get_inner();
+
+        std::fs::write(&files.settings.key_path, "not a key").unwrap();
+
+        assert!(certificates.reload_if_changed().await.is_err());
+        assert!(Arc::ptr_eq(&loaded, &certificates.config().get_inner()));
+        // Unchanged since, so not retried.
+        assert!(!certificates.reload_if_changed().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_load_fails_on_missing_files() {
+        let files = TestFiles::new();
+
+        let err = Certificates::load(&files.settings).await.err().unwrap();
+
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(err.to_string().contains("cert.pem"));
+    }
+
+    #[test]
+    fn test_https_location_keeps_host_and_path() {
+        let uri = Uri::from_static("/login?next=%2Faccount");
+
+        assert_eq!(
+            https_location(&Authority::from_static("example.com:8080"), 3443, &uri),
+            "https://example.com:3443/login?next=%2Faccount"
+        );
+        assert_eq!(
+            https_location(&Authority::from_static("example.com"), 443, &uri),
+            "https://example.com/login?next=%2Faccount"
+        );
+        assert_eq!(
+            https_location(
+                &Authority::from_static("[::1]:8080"),
+                3443,
+                &Uri::from_static("/")
+            ),
+            "https://[::1]:3443/"
+        );
+    }
+}
@@ -1,37 +1,135 @@
 use std::time::Duration;
 
-use axum::{body::Body, extract::Request, response::Response};
+use axum::{body::Body, extract::Request, http::HeaderMap, middleware::Next, response::Response};
+use opentelemetry::{global, trace::TracerProvider as _, KeyValue};
+use opentelemetry_http::{HeaderExtractor, HeaderInjector};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{
+    propagation::TraceContextPropagator, runtime, trace::TracerProvider, Resource,
+};
 use tracing::{Level, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 use color_eyre::eyre::Result;
 use tracing_error::ErrorLayer;
 use tracing_subscriber::prelude::*;
 use tracing_subscriber::{fmt, EnvFilter};
+
+use crate::utils::{
+    constants::{LogFormat, REQUEST_ID_HEADER},
+    settings::TelemetrySettings,
+};
 //...
 
-pub fn init_tracing() -> Result<()> {
-    let fmt_layer = fmt::layer().compact();
+/// Installs the global subscriber, exporting spans over OTLP when telemetry is enabled. The
+/// returned provider must be shut down before exiting, to flush the spans not yet exported.
+pub fn init_tracing(settings: &TelemetrySettings) -> Result<Option<TracerProvider>> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let (compact_layer, json_layer) = match settings.log_format {
+        LogFormat::Compact => (Some(fmt::layer().compact()), None),
+        LogFormat::Json => {
+            let json_layer = fmt::layer().json().flatten_event(true).with_span_list(true);
+            (None, Some(json_layer))
+        }
+    };
     let filter_layer = EnvFilter::try_from_default_env().or_else(|_| EnvFilter::try_new("info"))?;
+    let tracer_provider = match settings.enabled {
+        true => Some(build_tracer_provider(settings)?),
+        false => None,
+    };
+    let otel_layer = tracer_provider.as_ref().map(|provider| {
+        tracing_opentelemetry::layer().with_tracer(provider.tracer(settings.service_name.clone()))
+    });
 
     tracing_subscriber::registry()
         .with(filter_layer)
-        .with(fmt_layer)
+        .with(compact_layer)
+        .with(json_layer)
+        .with(otel_layer)
         .with(ErrorLayer::default())
         .init();
 
-    Ok(())
+    Ok(tracer_provider)
+}
+
+/// Batches spans and sends them to the collector at `settings.otlp_endpoint`.
+pub fn build_tracer_provider(settings: &TelemetrySettings) -> Result<TracerProvider> {
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!(
+            "{}/v1/traces",
+            settings.otlp_endpoint.trim_end_matches('/')
+        ))
+        .with_timeout(Duration::from_millis(settings.otlp_timeout_ms))
+        .build()?;
+
+    Ok(TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_resource(Resource::new([KeyValue::new(
+            "service.name",
+            settings.service_name.clone(),
+        )]))
+        .build())
+}
+
+/// W3C trace context headers for an outgoing request, so the callee's spans join the current
+/// trace.
+pub fn trace_context_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(
+            &Span::current().context(),
+            &mut HeaderInjector(&mut headers),
+        )
+    });
+    headers
+}
+
+tokio::task_local! {
+    static REQUEST_ID: Option<String>;
+}
+
+/// Makes the id `SetRequestIdLayer` put in the request headers available to
+/// `current_request_id` while the request is handled.
+pub async fn scope_request_id(request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+    REQUEST_ID.scope(request_id, next.run(request)).await
+}
+
+/// The id of the request being handled, if called within `scope_request_id`.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok().flatten()
 }
 
+/// Opens the request span, tagged with the id `SetRequestIdLayer` put in the request headers. A
+/// `traceparent` header makes it a child of the caller's span.
 pub fn make_span_with_request_id(request: &Request<Body>) -> Span {
-    let request_id = uuid::Uuid::new_v4();
-    tracing::span!(
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let span = tracing::span!(
         Level::INFO,
         "[REQUEST]",
         method = tracing::field::display(request.method()),
         uri = tracing::field::display(request.uri()),
         version = tracing::field::debug(request.version()),
         request_id = tracing::field::display(request_id),
-    )
+    );
+
+    let parent = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(request.headers()))
+    });
+    span.set_parent(parent);
+
+    span
 }
 
 pub fn on_request(_request: &Request<Body>, _span: &Span) {
@@ -61,4 +159,88 @@ pub fn on_response(response: &Response, latency: Duration, _span: &Span) {
             )
         }
     };
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceRequest;
+    use opentelemetry_proto::tonic::trace::v1::Span as ExportedSpan;
+    use prost::Message;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const PARENT_SPAN_ID: &str = "00f067aa0ba902b7";
+
+    fn telemetry_settings(otlp_endpoint: String) -> TelemetrySettings {
+        TelemetrySettings {
+            log_format: LogFormat::Compact,
+            enabled: true,
+            otlp_endpoint,
+            otlp_timeout_ms: 1_000,
+            service_name: "auth-service-test".to_owned(),
+        }
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    fn exported_spans(requests: &[wiremock::Request]) -> Vec<ExportedSpan> {
+        requests
+            .iter()
+            .map(|request| ExportTraceServiceRequest::decode(request.body.as_slice()).unwrap())
+            .flat_map(|export| export.resource_spans)
+            .flat_map(|resource_spans| resource_spans.scope_spans)
+            .flat_map(|scope_spans| scope_spans.spans)
+            .collect()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn request_spans_join_the_callers_trace_and_are_exported() {
+        let collector = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/traces"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&collector)
+            .await;
+
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let settings = telemetry_settings(collector.uri());
+        let provider = build_tracer_provider(&settings).unwrap();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_opentelemetry::layer().with_tracer(provider.tracer(settings.service_name)),
+        );
+
+        let request = Request::builder()
+            .uri("/login")
+            .header("traceparent", format!("00-{TRACE_ID}-{PARENT_SPAN_ID}-01"))
+            .body(Body::empty())
+            .unwrap();
+        tracing::subscriber::with_default(subscriber, || {
+            let _entered = make_span_with_request_id(&request).entered();
+            let _child = tracing::info_span!("validate_credentials").entered();
+        });
+
+        let flushed = provider.clone();
+        tokio::task::spawn_blocking(move || flushed.force_flush())
+            .await
+            .unwrap()
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        let spans = exported_spans(&collector.received_requests().await.unwrap());
+        let request_span = spans.iter().find(|span| span.name == "[REQUEST]").unwrap();
+        let child_span = spans
+            .iter()
+            .find(|span| span.name == "validate_credentials")
+            .unwrap();
+
+        assert_eq!(hex(&request_span.trace_id), TRACE_ID);
+        assert_eq!(hex(&request_span.parent_span_id), PARENT_SPAN_ID);
+        assert_eq!(child_span.trace_id, request_span.trace_id);
+        assert_eq!(child_span.parent_span_id, request_span.span_id);
+    }
+}
@@ -0,0 +1,197 @@
+use auth_service::{
+    domain::{AuditEventKind, AuditOutcome},
+    routes::AuditEventsResponse,
+    utils::constants::JWT_COOKIE_NAME,
+};
+use reqwest::Url;
+use serde_json::json;
+
+use crate::helpers::{get_random_email, TestApp, ADMIN_API_TOKEN};
+
+#[tokio::test]
+async fn should_record_signup_and_login_outcomes() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let response = app
+        .post_signup(&json!({
+            "email": random_email,
+            "password": "MySecretPwd",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_login(&json!({
+            "email": random_email,
+            "password": "WrongSecretPwd"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_login(&json!({
+            "email": random_email,
+            "password": "MySecretPwd"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .get_audit_events(&[("actor", random_email.as_str())], ADMIN_API_TOKEN)
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<AuditEventsResponse>()
+        .await
+        .expect("Could not deserialize response body to AuditEventsResponse");
+
+    let events = body
+        .events
+        .iter()
+        .map(|record| (record.event.kind, record.event.outcome))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        events,
+        vec![
+            (AuditEventKind::Login, AuditOutcome::Success),
+            (AuditEventKind::Login, AuditOutcome::Failure),
+            (AuditEventKind::Signup, AuditOutcome::Success),
+        ]
+    );
+
+    let failure = &body.events[1].event;
+    assert_eq!(failure.reason.as_deref(), Some("Incorrect credentials"));
+    assert_eq!(failure.ip.as_deref(), Some("127.0.0.1"));
+    assert!(failure.request_id.is_some());
+    assert_eq!(body.next_before, None);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_page_through_filtered_events() {
+    let mut app = TestApp::new().await;
+
+    for _ in 0..3 {
+        let response = app
+            .post_verify_token(&json!({
+                "token": "invalid_token"
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app
+        .get_audit_events(
+            &[
+                ("kind", "verify_token"),
+                ("outcome", "failure"),
+                ("limit", "2"),
+            ],
+            ADMIN_API_TOKEN,
+        )
+        .await;
+    let first_page = response
+        .json::<AuditEventsResponse>()
+        .await
+        .expect("Could not deserialize response body to AuditEventsResponse");
+
+    assert_eq!(first_page.events.len(), 2);
+    let next_before = first_page.next_before.expect("No next page").to_string();
+
+    let response = app
+        .get_audit_events(
+            &[
+                ("kind", "verify_token"),
+                ("limit", "2"),
+                ("before", next_before.as_str()),
+            ],
+            ADMIN_API_TOKEN,
+        )
+        .await;
+    let second_page = response
+        .json::<AuditEventsResponse>()
+        .await
+        .expect("Could not deserialize response body to AuditEventsResponse");
+
+    assert_eq!(second_page.events.len(), 1);
+    assert_eq!(second_page.next_before, None);
+    assert!(second_page.events[0].id < first_page.events[1].id);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_admin_token_is_wrong() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_audit_events(&[], "not-the-admin-token").await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_filter_login_and_logout_by_the_same_actor() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let response = app
+        .create_user_and_login(&random_email, "MySecretPwd", false)
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{JWT_COOKIE_NAME}={}; HttpOnly; SameSite=Lax; Secure; Path=/",
+            auth_cookie.value()
+        ),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+
+    let response = app.post_logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .get_audit_events(&[("actor", random_email.as_str())], ADMIN_API_TOKEN)
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<AuditEventsResponse>()
+        .await
+        .expect("Could not deserialize response body to AuditEventsResponse");
+
+    let events = body
+        .events
+        .iter()
+        .map(|record| (record.event.kind, record.event.outcome))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        events,
+        vec![
+            (AuditEventKind::Logout, AuditOutcome::Success),
+            (AuditEventKind::Login, AuditOutcome::Success),
+            (AuditEventKind::Signup, AuditOutcome::Success),
+        ]
+    );
+
+    let user_id = body.events[0].event.user_id.clone();
+    assert!(user_id.is_some());
+    assert!(body
+        .events
+        .iter()
+        .all(|record| record.event.user_id == user_id));
+
+    app.cleanup().await;
+}
@@ -0,0 +1,186 @@
+use auth_service::{domain::LoginAttemptId, routes::TwoFactorAuthResponse};
+use serde_json::json;
+
+use crate::helpers::{get_random_email, TestApp};
+
+/// Signs up a 2FA user and completes the login, leaving the auth cookie in the jar.
+async fn login_with_2fa(app: &TestApp, email: &str) {
+    let response = app.create_user_and_login(email, "MySecretPwd", true).await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let (login_attempt_id, code) = stored_code(app, response).await;
+
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+async fn stored_code(app: &TestApp, response: reqwest::Response) -> (String, String) {
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    let (_, code) = app
+        .two_fa_code_store
+        .get_code(&LoginAttemptId::parse(json_body.login_attempt_id.clone()).unwrap())
+        .await
+        .unwrap();
+
+    (json_body.login_attempt_id, code.as_ref().to_owned())
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_disable_2fa(&json!({
+            "password": "MySecretPwd",
+            "loginAttemptId": LoginAttemptId::default().as_ref(),
+            "2FACode": "123456"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_incorrect_password() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    login_with_2fa(&app, &random_email).await;
+
+    let response = app.post_request_2fa_code().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let (login_attempt_id, code) = stored_code(&app, response).await;
+
+    let response = app
+        .post_disable_2fa(&json!({
+            "password": "WrongPassword",
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_skip_2fa_on_next_login_after_disabling() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    login_with_2fa(&app, &random_email).await;
+
+    let response = app.post_request_2fa_code().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let (login_attempt_id, code) = stored_code(&app, response).await;
+
+    let response = app
+        .post_disable_2fa(&json!({
+            "password": "MySecretPwd",
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_login(&json!({
+            "email": random_email,
+            "password": "MySecretPwd",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_code_was_issued_for_login() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    login_with_2fa(&app, &random_email).await;
+
+    let response = app
+        .post_login(&json!({
+            "email": random_email,
+            "password": "MySecretPwd",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+    let (login_attempt_id, code) = stored_code(&app, response).await;
+
+    let response = app
+        .post_disable_2fa(&json!({
+            "password": "MySecretPwd",
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_login(&json!({
+            "email": random_email,
+            "password": "MySecretPwd",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_send_security_notification_after_disabling() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    login_with_2fa(&app, &random_email).await;
+
+    let response = app.post_request_2fa_code().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let (login_attempt_id, code) = stored_code(&app, response).await;
+
+    let response = app
+        .post_disable_2fa(&json!({
+            "password": "MySecretPwd",
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let notifications: Vec<_> = app
+        .email_client
+        .emails_to(&random_email)
+        .into_iter()
+        .filter(|email| email.subject == "Auth Service: 2FA disabled")
+        .collect();
+
+    assert_eq!(notifications.len(), 1);
+    assert!(notifications[0]
+        .content
+        .contains("Two-factor authentication was turned off"));
+
+    app.cleanup().await;
+}
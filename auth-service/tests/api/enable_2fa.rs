@@ -0,0 +1,116 @@
+use auth_service::{domain::LoginAttemptId, routes::TwoFactorAuthResponse};
+use serde_json::json;
+
+use crate::helpers::{get_random_email, TestApp};
+
+async fn request_code(app: &TestApp) -> (String, String) {
+    let response = app.post_request_2fa_code().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    let (_, code) = app
+        .two_fa_code_store
+        .get_code(&LoginAttemptId::parse(json_body.login_attempt_id.clone()).unwrap())
+        .await
+        .unwrap();
+
+    (json_body.login_attempt_id, code.as_ref().to_owned())
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_enable_2fa(&json!({
+            "definitely invalid body": true
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 422);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_enable_2fa(&json!({
+            "loginAttemptId": LoginAttemptId::default().as_ref(),
+            "2FACode": "123456"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_incorrect_code() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let response = app
+        .create_user_and_login(&random_email, "MySecretPwd", false)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let (login_attempt_id, code) = request_code(&app).await;
+    let wrong_code = if code == "000000" { "111111" } else { "000000" };
+
+    let response = app
+        .post_enable_2fa(&json!({
+            "loginAttemptId": login_attempt_id,
+            "2FACode": wrong_code
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_require_2fa_on_next_login_after_enabling() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let response = app
+        .create_user_and_login(&random_email, "MySecretPwd", false)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let (login_attempt_id, code) = request_code(&app).await;
+
+    let response = app
+        .post_enable_2fa(&json!({
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_login(&json!({
+            "email": random_email,
+            "password": "MySecretPwd",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    app.cleanup().await;
+}
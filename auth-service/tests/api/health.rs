@@ -0,0 +1,239 @@
+use std::{sync::Arc, time::Duration};
+
+use auth_service::{
+    domain::{Dependency, HealthCheck},
+    routes::{HealthStatus, LivenessResponse, ReadinessResponse},
+};
+use color_eyre::eyre::{eyre, Result};
+
+use crate::helpers::TestApp;
+
+struct UnreachableCheck;
+
+#[async_trait::async_trait]
+impl HealthCheck for UnreachableCheck {
+    async fn check(&self) -> Result<()> {
+        Err(eyre!("connection refused"))
+    }
+}
+
+struct HangingCheck;
+
+#[async_trait::async_trait]
+impl HealthCheck for HangingCheck {
+    async fn check(&self) -> Result<()> {
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        Ok(())
+    }
+}
+
+fn dependency(name: &'static str, critical: bool, check: impl HealthCheck + 'static) -> Dependency {
+    Dependency {
+        name,
+        critical,
+        timeout: Duration::from_millis(100),
+        check: Arc::new(check),
+    }
+}
+
+#[tokio::test]
+async fn should_return_200_when_live() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_health_live().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response
+        .json::<LivenessResponse>()
+        .await
+        .expect("Could not deserialize response body to LivenessResponse");
+    assert_eq!(body.status, HealthStatus::Up);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_200_with_every_dependency_up() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_health_ready().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response
+        .json::<ReadinessResponse>()
+        .await
+        .expect("Could not deserialize response body to ReadinessResponse");
+    assert_eq!(body.status, HealthStatus::Up);
+    for name in ["postgres", "redis"] {
+        let dependency = &body.dependencies[name];
+        assert_eq!(dependency.status, HealthStatus::Up);
+        assert!(dependency.critical);
+        assert!(dependency.error.is_none());
+    }
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_200_if_only_a_non_critical_dependency_is_down() {
+    let mut app =
+        TestApp::with_dependencies(vec![dependency("email_provider", false, UnreachableCheck)])
+            .await;
+
+    let response = app.get_health_ready().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response.json::<ReadinessResponse>().await.unwrap();
+    assert_eq!(body.status, HealthStatus::Up);
+    let email_provider = &body.dependencies["email_provider"];
+    assert_eq!(email_provider.status, HealthStatus::Down);
+    assert_eq!(email_provider.error.as_deref(), Some("unreachable"));
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_503_if_a_critical_dependency_is_down() {
+    let mut app =
+        TestApp::with_dependencies(vec![dependency("broken", true, UnreachableCheck)]).await;
+
+    let response = app.get_health_ready().await;
+
+    assert_eq!(response.status().as_u16(), 503);
+    let body = response.json::<ReadinessResponse>().await.unwrap();
+    assert_eq!(body.status, HealthStatus::Down);
+    assert_eq!(body.dependencies["broken"].status, HealthStatus::Down);
+    assert_eq!(body.dependencies["postgres"].status, HealthStatus::Up);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_503_if_a_critical_check_times_out() {
+    let mut app = TestApp::with_dependencies(vec![dependency("hanging", true, HangingCheck)]).await;
+
+    let response = app.get_health_ready().await;
+
+    assert_eq!(response.status().as_u16(), 503);
+    let body = response.json::<ReadinessResponse>().await.unwrap();
+    let hanging = &body.dependencies["hanging"];
+    assert_eq!(hanging.status, HealthStatus::Down);
+    assert_eq!(hanging.error.as_deref(), Some("timeout"));
+
+    app.cleanup().await;
+}
+
+struct SlowCheck(Duration);
+
+#[async_trait::async_trait]
+impl HealthCheck for SlowCheck {
+    async fn check(&self) -> Result<()> {
+        tokio::time::sleep(self.0).await;
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn should_drain_in_flight_requests_and_fail_readiness_on_shutdown() {
+    let slow = Dependency {
+        timeout: Duration::from_secs(5),
+        ..dependency("slow", true, SlowCheck(Duration::from_millis(500)))
+    };
+    let mut app = TestApp::with_dependencies(vec![slow]).await;
+
+    let in_flight = tokio::spawn({
+        let http_client = app.http_client.clone();
+        let address = app.address.clone();
+        async move {
+            http_client
+                .get(format!("{address}/health/ready"))
+                .send()
+                .await
+        }
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    app.stop().await;
+
+    // The request finished, but was answered after shutdown started.
+    let response = in_flight
+        .await
+        .unwrap()
+        .expect("In-flight request was cut off");
+    assert_eq!(response.status().as_u16(), 503);
+    let body = response.json::<ReadinessResponse>().await.unwrap();
+    assert!(body.shutting_down);
+    assert_eq!(body.dependencies["slow"].status, HealthStatus::Up);
+
+    assert!(app.http_client.get(&app.address).send().await.is_err());
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_fail_readiness_during_the_shutdown_grace_period() {
+    let slow = Dependency {
+        timeout: Duration::from_secs(5),
+        ..dependency("slow", true, SlowCheck(Duration::from_millis(500)))
+    };
+    let mut app = TestApp::with_shutdown_grace(2, vec![slow]).await;
+
+    let in_flight = tokio::spawn({
+        let http_client = app.http_client.clone();
+        let address = app.address.clone();
+        async move {
+            http_client
+                .get(format!("{address}/health/ready"))
+                .send()
+                .await
+        }
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let started = std::time::Instant::now();
+    app.shutdown.trigger();
+
+    // The listener still accepts connections, but reports the app as not ready.
+    let response = app.get_health_ready().await;
+    assert_eq!(response.status().as_u16(), 503);
+    let body = response.json::<ReadinessResponse>().await.unwrap();
+    assert!(body.shutting_down);
+
+    let response = in_flight
+        .await
+        .unwrap()
+        .expect("In-flight request was cut off");
+    assert_eq!(response.status().as_u16(), 503);
+
+    app.stop().await;
+
+    assert!(started.elapsed() >= Duration::from_secs(2));
+    assert!(app.http_client.get(&app.address).send().await.is_err());
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_stop_at_the_shutdown_deadline() {
+    let stuck = Dependency {
+        timeout: Duration::from_secs(60),
+        ..dependency("stuck", true, HangingCheck)
+    };
+    let mut app = TestApp::with_dependencies(vec![stuck]).await;
+
+    #[allow(clippy::let_underscore_future)]
+    let _ = tokio::spawn(
+        app.http_client
+            .get(format!("{}/health/ready", app.address))
+            .send(),
+    );
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let started = std::time::Instant::now();
+    app.stop().await;
+
+    // The test app's deadline is one second.
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    app.cleanup().await;
+}
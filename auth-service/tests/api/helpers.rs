@@ -1,25 +1,51 @@
-use std::{str::FromStr, sync::Arc};
+use std::{str::FromStr, sync::Arc, time::Duration};
 
 use auth_service::{
-    app_state::{AppState, TwoFACodeStoreType},
-    configure_redis, get_postgres_pool,
-    services::{MockEmailClient, PostgresUserStore, RedisBannedTokenStore, RedisTwoFACodeStore},
-    utils::constants::{test, DATABASE_URL},
+    app_state::{
+        AppState, BannedTokenStoreType, MagicLinkStoreType, SmsClientType, TrustedDeviceStoreType,
+        TwoFACodeStoreType, UserStoreType,
+    },
+    configure_redis,
+    domain::{Dependency, Email, EmailClient, LoginAttemptId},
+    get_postgres_pool,
+    routes::TwoFactorAuthResponse,
+    services::{
+        DatabaseHealthCheck, MockSmsClient, PostgresAuditLog, PostgresBannedTokenStore,
+        PostgresMagicLinkStore, PostgresTrustedDeviceStore, PostgresTwoFACodeStore,
+        PostgresUserStore, RedisBannedTokenStore, RedisHealthCheck, RedisMagicLinkStore,
+        RedisTrustedDeviceStore, RedisTwoFACodeStore,
+    },
+    utils::{
+        constants::{test, StoreBackend},
+        settings::{Settings, TlsSettings, TwoFASettings},
+        shutdown::ShutdownHandle,
+    },
     Application,
 };
 use reqwest::cookie::Jar;
+use secrecy::Secret;
 use sqlx::{
     postgres::{PgConnectOptions, PgPoolOptions},
     Connection, Executor, PgConnection, PgPool,
 };
-use tokio::sync::RwLock;
+use tokio::{sync::RwLock, task::JoinHandle};
 use uuid::Uuid;
 
+/// Admin token the test apps are started with.
+pub const ADMIN_API_TOKEN: &str = "test-admin-token";
+
 pub struct TestApp {
     pub address: String,
+    pub redirect_address: Option<String>,
     pub http_client: reqwest::Client,
     pub cookie_jar: Arc<Jar>,
+    pub user_store: UserStoreType,
+    pub banned_token_store: BannedTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
+    pub email_client: RecordingEmailClient,
+    pub settings: Arc<Settings>,
+    pub shutdown: ShutdownHandle,
+    server: JoinHandle<Result<(), std::io::Error>>,
     pub db_name: String,
     pub cleanup_called: bool,
 }
@@ -34,43 +60,173 @@ impl Drop for TestApp {
 
 impl TestApp {
     pub async fn new() -> Self {
-        let (pg_pool, db_name) = configure_postgresql().await;
-        let redis_conn = Arc::new(RwLock::new(configure_redis()));
-        let two_fa_code_store: TwoFACodeStoreType = Arc::new(RwLock::new(Box::new(
-            RedisTwoFACodeStore::new(redis_conn.clone()),
-        )));
+        Self::with_store_backend(StoreBackend::Redis).await
+    }
+
+    /// Builds an app whose stores all use `backend`. With Postgres the app runs without Redis.
+    pub async fn with_store_backend(backend: StoreBackend) -> Self {
+        Self::build(backend, Vec::new(), |_| {}).await
+    }
+
+    /// Builds an app whose readiness checks cover `dependencies` as well as Postgres and Redis.
+    pub async fn with_dependencies(dependencies: Vec<Dependency>) -> Self {
+        Self::build(StoreBackend::Redis, dependencies, |_| {}).await
+    }
+
+    /// Builds an app like `with_dependencies` that keeps its listener open for `grace_seconds`
+    /// after shutdown starts.
+    pub async fn with_shutdown_grace(grace_seconds: u64, dependencies: Vec<Dependency>) -> Self {
+        Self::build(StoreBackend::Redis, dependencies, |settings| {
+            settings.application.shutdown_grace_seconds = grace_seconds
+        })
+        .await
+    }
+
+    /// Builds an app served over HTTPS with `tls`. The client trusts the certificate the app
+    /// starts with.
+    pub async fn with_tls(tls: TlsSettings) -> Self {
+        Self::build(StoreBackend::Redis, Vec::new(), |settings| {
+            settings.tls = tls
+        })
+        .await
+    }
+
+    /// Builds an app without an SMS client, as a deployment that has not opted in to SMS runs.
+    pub async fn without_sms() -> Self {
+        Self::build(StoreBackend::Redis, Vec::new(), |settings| {
+            settings.sms_client.enabled = false
+        })
+        .await
+    }
+
+    /// Builds an app that limits 2FA codes with `two_fa` instead of the test settings.
+    pub async fn with_two_fa(two_fa: TwoFASettings) -> Self {
+        Self::build(StoreBackend::Redis, Vec::new(), |settings| {
+            settings.two_fa = two_fa
+        })
+        .await
+    }
+
+    async fn build(
+        backend: StoreBackend,
+        extra_dependencies: Vec<Dependency>,
+        configure: impl FnOnce(&mut Settings),
+    ) -> Self {
+        let mut settings =
+            Settings::from_toml_and_env(test::SETTINGS).expect("Invalid test settings");
+        // Opt-in features are enabled so their routes can be tested.
+        settings.magic_link.enabled = true;
+        settings.auth.admin_api_token = Some(Secret::new(ADMIN_API_TOKEN.to_owned()));
+        settings.stores.banned_token_store = backend;
+        settings.stores.two_fa_code_store = backend;
+        settings.stores.trusted_device_store = backend;
+        settings.stores.magic_link_store = backend;
+        settings.application.shutdown_grace_seconds = 0;
+        settings.application.shutdown_deadline_seconds = 1;
+        configure(&mut settings);
+        let settings = Arc::new(settings);
+        let max_pending_attempts = settings.two_fa.max_pending_attempts;
+
+        let (pg_pool, db_name) = configure_postgresql(&settings).await;
+        let email_client = RecordingEmailClient::default();
+        let mut dependencies = vec![Dependency {
+            name: "postgres",
+            critical: true,
+            timeout: Duration::from_millis(settings.health.database_timeout_ms),
+            check: Arc::new(DatabaseHealthCheck::new(pg_pool.clone())),
+        }];
+        let (banned_token_store, two_fa_code_store, trusted_device_store, magic_link_store): (
+            BannedTokenStoreType,
+            TwoFACodeStoreType,
+            TrustedDeviceStoreType,
+            MagicLinkStoreType,
+        ) = match backend {
+            StoreBackend::Redis => {
+                let redis_pool = configure_redis(&settings.redis).await;
+                dependencies.push(Dependency {
+                    name: "redis",
+                    critical: true,
+                    timeout: Duration::from_millis(settings.health.redis_timeout_ms),
+                    check: Arc::new(RedisHealthCheck::new(redis_pool.clone())),
+                });
+                (
+                    Arc::new(RedisBannedTokenStore::new(redis_pool.clone())),
+                    Arc::new(RedisTwoFACodeStore::new(
+                        redis_pool.clone(),
+                        max_pending_attempts,
+                    )),
+                    Arc::new(RedisTrustedDeviceStore::new(redis_pool.clone())),
+                    Arc::new(RedisMagicLinkStore::new(redis_pool)),
+                )
+            }
+            StoreBackend::Postgres => (
+                Arc::new(PostgresBannedTokenStore::new(pg_pool.clone())),
+                Arc::new(PostgresTwoFACodeStore::new(
+                    pg_pool.clone(),
+                    max_pending_attempts,
+                )),
+                Arc::new(PostgresTrustedDeviceStore::new(pg_pool.clone())),
+                Arc::new(PostgresMagicLinkStore::new(pg_pool.clone())),
+            ),
+        };
+
+        let user_store: UserStoreType = Arc::new(PostgresUserStore::new(pg_pool.clone()));
+
+        dependencies.extend(extra_dependencies);
 
         let app_state = AppState::new(
-            Arc::new(RwLock::new(Box::new(PostgresUserStore::new(pg_pool)))),
-            Arc::new(RwLock::new(Box::new(RedisBannedTokenStore::new(
-                redis_conn.clone(),
-            )))),
+            settings.clone(),
+            user_store.clone(),
+            banned_token_store.clone(),
             two_fa_code_store.clone(),
-            Arc::new(RwLock::new(Box::new(MockEmailClient))),
+            trusted_device_store,
+            magic_link_store,
+            Arc::new(PostgresAuditLog::new(pg_pool)),
+            Arc::new(RwLock::new(Box::new(email_client.clone()))),
+            settings
+                .sms_client
+                .enabled
+                .then(|| -> SmsClientType { Arc::new(RwLock::new(Box::new(MockSmsClient))) }),
+            Arc::new(dependencies),
         );
 
-        let app = Application::build(app_state, test::APP_ADDRESS)
+        let app = Application::build(app_state)
             .await
             .expect("Failed to build app");
 
-        let address = format!("http://{}", app.address.clone());
+        let scheme = match settings.tls.enabled {
+            true => "https",
+            false => "http",
+        };
+        let address = format!("{scheme}://{}", app.address.clone());
+        let redirect_address = app.redirect_address.clone();
+        let shutdown = app.shutdown_handle();
 
         // Run the auth service in a separate async task
         // to avoid blocking the main test thread.
-        #[allow(clippy::let_underscore_future)]
-        let _ = tokio::spawn(app.run());
+        let server = tokio::spawn(app.run());
 
         let cookie_jar = Arc::new(Jar::default());
-        let http_client = reqwest::Client::builder()
-            .cookie_provider(cookie_jar.clone())
-            .build()
-            .unwrap();
+        let mut http_client = reqwest::Client::builder().cookie_provider(cookie_jar.clone());
+        if settings.tls.enabled {
+            let certificate = std::fs::read(&settings.tls.cert_path).unwrap();
+            http_client = http_client
+                .add_root_certificate(reqwest::Certificate::from_pem(&certificate).unwrap());
+        }
+        let http_client = http_client.build().unwrap();
 
         Self {
             address,
+            redirect_address,
             http_client,
             cookie_jar,
+            user_store,
+            banned_token_store,
             two_fa_code_store: two_fa_code_store.clone(),
+            email_client,
+            settings,
+            shutdown,
+            server,
             db_name,
             cleanup_called: false,
         }
@@ -84,6 +240,22 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn get_health_live(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/health/live", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_health_ready(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/health/ready", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_signup<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
@@ -120,6 +292,140 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_resend_2fa<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/resend-2fa", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_request_2fa_code(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/request-2fa-code", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_enable_2fa<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/enable-2fa", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_disable_2fa<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/disable-2fa", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_phone_number<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/phone-number", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_verify_phone_number<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/verify-phone-number", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_2fa_channel<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/2fa-channel", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_trusted_devices(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/trusted-devices", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_audit_events(&self, query: &[(&str, &str)], token: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/admin/audit-events", &self.address))
+            .query(query)
+            .bearer_auth(token)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_revoke_trusted_device<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/revoke-trusted-device", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_magic_link<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/magic-link", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_verify_magic_link<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/verify-magic-link", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_logout(&self) -> reqwest::Response {
         self.http_client
             .post(format!("{}/logout", &self.address))
@@ -165,18 +471,106 @@ impl TestApp {
         response
     }
 
+    /// Signs up a 2FA user and completes the login with "remember this device" checked, leaving
+    /// both the auth and trusted device cookies in the jar.
+    pub async fn create_user_and_remember_device(&self, email: &str, pwd: &str) {
+        let response = self.create_user_and_login(email, pwd, true).await;
+
+        assert_eq!(response.status().as_u16(), 206);
+
+        let json_body = response
+            .json::<TwoFactorAuthResponse>()
+            .await
+            .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+        let (_, code) = self
+            .two_fa_code_store
+            .get_code(&LoginAttemptId::parse(json_body.login_attempt_id.clone()).unwrap())
+            .await
+            .unwrap();
+
+        let response = self
+            .post_verify_2fa(&serde_json::json!({
+                "email": email,
+                "loginAttemptId": json_body.login_attempt_id,
+                "2FACode": code.as_ref(),
+                "rememberDevice": true
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    /// Triggers shutdown and waits for the app to drain its requests and stop serving.
+    pub async fn stop(&mut self) {
+        self.shutdown.trigger();
+        (&mut self.server)
+            .await
+            .expect("App task panicked")
+            .expect("App failed while shutting down");
+    }
+
     pub async fn cleanup(&mut self) {
-        delete_database(&self.db_name).await;
+        delete_database(&self.settings.database.url, &self.db_name).await;
         self.cleanup_called = true;
     }
 }
 
+/// Keeps every email the app sends, so tests can read links and codes out of them.
+#[derive(Clone, Default)]
+pub struct RecordingEmailClient {
+    emails: Arc<std::sync::Mutex<Vec<SentEmail>>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SentEmail {
+    pub recipient: String,
+    pub subject: String,
+    pub content: String,
+}
+
+impl RecordingEmailClient {
+    pub fn emails_to(&self, recipient: &str) -> Vec<SentEmail> {
+        self.emails
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|email| email.recipient == recipient)
+            .cloned()
+            .collect()
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailClient for RecordingEmailClient {
+    async fn send_email(
+        &self,
+        recipient: &Email,
+        subject: &str,
+        content: &str,
+    ) -> color_eyre::eyre::Result<()> {
+        self.emails.lock().unwrap().push(SentEmail {
+            recipient: recipient.as_ref().to_owned(),
+            subject: subject.to_owned(),
+            content: content.to_owned(),
+        });
+        Ok(())
+    }
+}
+
+/// The 2FA limits the app runs with by default, for tests to override with `with_two_fa`.
+pub fn default_two_fa_settings() -> TwoFASettings {
+    Settings::from_toml_and_env(test::SETTINGS)
+        .expect("Invalid test settings")
+        .two_fa
+}
+
 pub fn get_random_email() -> String {
     format!("{}@example.com", Uuid::new_v4())
 }
 
-async fn configure_postgresql() -> (PgPool, String) {
-    let postgresql_conn_url = DATABASE_URL.to_owned();
+async fn configure_postgresql(settings: &Settings) -> (PgPool, String) {
+    let postgresql_conn_url = settings.database.url.to_owned();
 
     let db_name = Uuid::new_v4().to_string();
 
@@ -184,9 +578,12 @@ async fn configure_postgresql() -> (PgPool, String) {
 
     let postgresql_conn_url_with_db = format!("{postgresql_conn_url}/{db_name}");
 
-    let pg_pool = get_postgres_pool(&postgresql_conn_url_with_db)
-        .await
-        .expect("Failed to create Postgres connection pool!");
+    let pg_pool = get_postgres_pool(
+        &postgresql_conn_url_with_db,
+        &settings.database.pool_config(),
+    )
+    .await
+    .expect("Failed to create Postgres connection pool!");
     (pg_pool, db_name)
 }
 
@@ -214,10 +611,8 @@ async fn configure_database(db_conn_string: &str, db_name: &str) {
         .expect("Failed to migrate the database");
 }
 
-async fn delete_database(db_name: &str) {
-    let postgresql_conn_url: String = DATABASE_URL.to_owned();
-
-    let connection_options = PgConnectOptions::from_str(&postgresql_conn_url)
+async fn delete_database(postgresql_conn_url: &str, db_name: &str) {
+    let connection_options = PgConnectOptions::from_str(postgresql_conn_url)
         .expect("Failed to parse PostgreSQL connection string");
 
     let mut connection = PgConnection::connect_with(&connection_options)
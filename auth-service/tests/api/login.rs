@@ -1,6 +1,6 @@
 use crate::helpers::{get_random_email, TestApp};
 use auth_service::{
-    domain::{Email, LoginAttemptId},
+    domain::{Email, LoginAttemptId, UserStatus},
     routes::TwoFactorAuthResponse,
     utils::constants::JWT_COOKIE_NAME,
 };
@@ -75,6 +75,65 @@ async fn should_return_200_if_valid_credentials_and_2fa_disabled() {
     app.cleanup().await;
 }
 
+#[tokio::test]
+async fn should_record_last_login_time() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let response = app
+        .create_user_and_login(&random_email, "MySecretPwd", false)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let user = app
+        .user_store
+        .get_user(Email::parse(&random_email).unwrap())
+        .await
+        .unwrap();
+
+    assert!(user.last_login_at().is_some());
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_403_if_account_is_disabled() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let response = app
+        .post_signup(&json!({
+            "email": random_email,
+            "password": "MySecretPwd",
+            "requires2FA": false
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.user_store
+        .update_status(Email::parse(&random_email).unwrap(), UserStatus::Disabled)
+        .await
+        .unwrap();
+
+    let response = app
+        .post_login(&json!({
+            "email": random_email,
+            "password": "MySecretPwd"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 403);
+    assert!(response
+        .cookies()
+        .all(|cookie| cookie.name() != JWT_COOKIE_NAME));
+
+    app.cleanup().await;
+}
+
 #[tokio::test]
 async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
     let mut app = TestApp::new().await;
@@ -96,14 +155,84 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
 
     assert_eq!(
         app.two_fa_code_store
-            .read()
-            .await
-            .get_code(&Email::parse(&random_email).unwrap())
+            .get_code(&LoginAttemptId::parse(json_body.login_attempt_id).unwrap())
             .await
             .unwrap()
             .0,
-        LoginAttemptId::parse(json_body.login_attempt_id).unwrap()
+        Email::parse(&random_email).unwrap()
     );
 
     app.cleanup().await;
 }
+
+#[tokio::test]
+async fn should_return_429_if_too_many_2fa_codes_issued() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let response = app
+        .create_user_and_login(&random_email, "MySecretPwd", true)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_body = json!({
+        "email": random_email,
+        "password": "MySecretPwd"
+    });
+
+    for _ in 1..app.settings.two_fa.max_codes_per_hour {
+        let response = app.post_login(&login_body).await;
+        assert_eq!(response.status().as_u16(), 206);
+    }
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 429);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_200_if_2fa_enabled_on_trusted_device() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    app.create_user_and_remember_device(&random_email, "MySecretPwd")
+        .await;
+
+    let response = app
+        .post_login(&json!({
+            "email": random_email,
+            "password": "MySecretPwd",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    assert!(!auth_cookie.value().is_empty());
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_206_if_trusted_device_belongs_to_another_user() {
+    let mut app = TestApp::new().await;
+
+    app.create_user_and_remember_device(&get_random_email(), "MySecretPwd")
+        .await;
+
+    let response = app
+        .create_user_and_login(&get_random_email(), "MySecretPwd", true)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    app.cleanup().await;
+}
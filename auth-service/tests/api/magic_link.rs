@@ -0,0 +1,125 @@
+use auth_service::utils::{constants::MAGIC_LINK_NONCE_COOKIE_NAME, settings::TwoFASettings};
+use serde_json::json;
+
+use crate::helpers::{default_two_fa_settings, get_random_email, TestApp};
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_magic_link(&json!({
+            "definitely invalid body": true
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 422);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_email() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_magic_link(&json!({
+            "email": "invalidemail"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_200_without_sending_email_if_unknown_user() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let response = app
+        .post_magic_link(&json!({
+            "email": random_email
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .cookies()
+        .any(|cookie| cookie.name() == MAGIC_LINK_NONCE_COOKIE_NAME));
+    assert!(app.email_client.emails_to(&random_email).is_empty());
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_200_and_send_link_if_known_user() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let response = app
+        .post_signup(&json!({
+            "email": random_email,
+            "password": "MySecretPwd",
+            "requires2FA": false
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_magic_link(&json!({
+            "email": random_email
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .cookies()
+        .any(|cookie| cookie.name() == MAGIC_LINK_NONCE_COOKIE_NAME));
+
+    let emails = app.email_client.emails_to(&random_email);
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0].subject, "Auth Service: your login link");
+    assert!(emails[0].content.contains("magicLinkToken="));
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_429_past_hourly_cap_whether_or_not_the_account_exists() {
+    let mut app = TestApp::with_two_fa(TwoFASettings {
+        max_codes_per_hour: 1,
+        ..default_two_fa_settings()
+    })
+    .await;
+
+    let known_email = get_random_email();
+
+    let response = app
+        .post_signup(&json!({
+            "email": known_email,
+            "password": "MySecretPwd",
+            "requires2FA": false
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    for email in [known_email.clone(), get_random_email()] {
+        let response = app.post_magic_link(&json!({ "email": email })).await;
+
+        assert_eq!(response.status().as_u16(), 200);
+
+        let response = app.post_magic_link(&json!({ "email": email })).await;
+
+        assert_eq!(response.status().as_u16(), 429);
+    }
+
+    assert_eq!(app.email_client.emails_to(&known_email).len(), 1);
+
+    app.cleanup().await;
+}
@@ -1,7 +1,25 @@
+mod audit_events;
+mod disable_2fa;
+mod enable_2fa;
+mod health;
 mod helpers;
 mod login;
 mod logout;
+mod magic_link;
+mod metrics;
+mod postgres_stores;
+mod request_2fa_code;
+mod request_id;
+mod resend_2fa;
+mod revoke_trusted_device;
 mod root;
+mod set_phone_number;
 mod signup;
+mod tls;
+mod trusted_devices;
+mod update_2fa_channel;
+mod user_store_conformance;
 mod verify_2fa;
+mod verify_magic_link;
+mod verify_phone_number;
 mod verify_token;
@@ -0,0 +1,90 @@
+use auth_service::{
+    utils::{metrics::install_prometheus_recorder, shutdown::ShutdownHandle},
+    AdminApplication,
+};
+use metrics_exporter_prometheus::PrometheusHandle;
+use once_cell::sync::Lazy;
+
+use crate::helpers::{get_random_email, TestApp};
+
+// The recorder is global, so every test in this binary shares it.
+static METRICS: Lazy<PrometheusHandle> =
+    Lazy::new(|| install_prometheus_recorder().expect("Failed to install metrics recorder"));
+
+async fn spawn_admin_app() -> String {
+    let admin = AdminApplication::build("127.0.0.1:0", METRICS.clone())
+        .await
+        .expect("Failed to build admin listener");
+    let address = format!("http://{}", admin.address);
+
+    #[allow(clippy::let_underscore_future)]
+    let _ = tokio::spawn(admin.run(ShutdownHandle::default()));
+
+    address
+}
+
+#[tokio::test]
+async fn should_serve_request_and_auth_metrics() {
+    let admin_address = spawn_admin_app().await;
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let response = app
+        .create_user_and_login(&random_email, "password123", false)
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": random_email,
+            "password": "wrong-password",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // No password is involved, so a failed magic link login is not counted as a bad password.
+    let response = app
+        .post_verify_magic_link(&serde_json::json!({ "token": "a".repeat(64) }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = reqwest::get(format!("{admin_address}/metrics"))
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/plain"));
+
+    let body = response.text().await.unwrap();
+    for expected in [
+        r#"http_requests_total{method="POST",route="/signup",status="201"}"#,
+        r#"http_request_duration_seconds_bucket{method="POST",route="/login",status="200""#,
+        r#"auth_signups_total{outcome="success"}"#,
+        r#"auth_logins_total{outcome="success"}"#,
+        r#"auth_logins_total{outcome="bad_password"}"#,
+        r#"auth_logins_total{outcome="incorrect_credentials"}"#,
+        r#"password_hash_duration_seconds_bucket{operation="hash""#,
+    ] {
+        assert!(body.contains(expected), "missing {expected} in:\n{body}");
+    }
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_not_serve_metrics_on_the_public_listener() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .http_client
+        .get(format!("{}/metrics", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 404);
+
+    app.cleanup().await;
+}
@@ -0,0 +1,266 @@
+use auth_service::{
+    domain::{Email, LoginAttemptId},
+    routes::{HealthStatus, ReadinessResponse, TrustedDevicesResponse, TwoFactorAuthResponse},
+    utils::constants::{StoreBackend, JWT_COOKIE_NAME},
+};
+use chrono::Utc;
+use reqwest::Url;
+use serde_json::json;
+
+use crate::helpers::{get_random_email, TestApp};
+
+#[tokio::test]
+async fn should_reject_banned_token_after_logout() {
+    let mut app = TestApp::with_store_backend(StoreBackend::Postgres).await;
+
+    let response = app
+        .create_user_and_login(&get_random_email(), "MySecretPwd", false)
+        .await;
+
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    app.cookie_jar.add_cookie_str(
+        &format!("{JWT_COOKIE_NAME}={token}; HttpOnly; SameSite=Lax; Secure; Path=/"),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+
+    let response = app.post_logout().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_verify_token(&json!({ "token": token })).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_reject_token_after_user_sessions_are_revoked() {
+    let mut app = TestApp::with_store_backend(StoreBackend::Postgres).await;
+
+    let random_email = get_random_email();
+    let response = app
+        .create_user_and_login(&random_email, "MySecretPwd", false)
+        .await;
+
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let user = app
+        .user_store
+        .get_user(Email::parse(&random_email).unwrap())
+        .await
+        .unwrap();
+    app.banned_token_store
+        .revoke_user_tokens(user.id(), Utc::now())
+        .await
+        .unwrap();
+
+    let response = app.post_verify_token(&json!({ "token": token })).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_accept_2fa_code_only_once() {
+    let mut app = TestApp::with_store_backend(StoreBackend::Postgres).await;
+
+    let random_email = get_random_email();
+
+    let response = app
+        .create_user_and_login(&random_email, "MySecretPwd", true)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    let (_, code) = app
+        .two_fa_code_store
+        .get_code(&LoginAttemptId::parse(json_body.login_attempt_id.clone()).unwrap())
+        .await
+        .unwrap();
+
+    let request = json!({
+        "email": random_email,
+        "loginAttemptId": json_body.login_attempt_id,
+        "2FACode": code.as_ref().to_owned()
+    });
+
+    let response = app.post_verify_2fa(&request).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_verify_2fa(&request).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_evict_oldest_login_attempt() {
+    let mut app = TestApp::with_store_backend(StoreBackend::Postgres).await;
+
+    let random_email = get_random_email();
+
+    let response = app
+        .create_user_and_login(&random_email, "MySecretPwd", true)
+        .await;
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+    let login_attempt_id = LoginAttemptId::parse(json_body.login_attempt_id).unwrap();
+
+    for _ in 0..app.settings.two_fa.max_pending_attempts {
+        let response = app
+            .post_login(&json!({
+                "email": random_email,
+                "password": "MySecretPwd"
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 206);
+    }
+
+    assert!(app
+        .two_fa_code_store
+        .get_code(&login_attempt_id)
+        .await
+        .is_err());
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_429_if_resent_too_soon() {
+    let mut app = TestApp::with_store_backend(StoreBackend::Postgres).await;
+
+    let random_email = get_random_email();
+
+    let response = app
+        .create_user_and_login(&random_email, "MySecretPwd", true)
+        .await;
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    let response = app
+        .post_resend_2fa(&json!({
+            "email": random_email,
+            "loginAttemptId": json_body.login_attempt_id
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 429);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_be_ready_without_redis() {
+    let mut app = TestApp::with_store_backend(StoreBackend::Postgres).await;
+
+    let response = app.get_health_ready().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response
+        .json::<ReadinessResponse>()
+        .await
+        .expect("Could not deserialize response body to ReadinessResponse");
+    assert_eq!(body.status, HealthStatus::Up);
+    assert!(body.dependencies.contains_key("postgres"));
+    assert!(!body.dependencies.contains_key("redis"));
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_list_and_revoke_remembered_device() {
+    let mut app = TestApp::with_store_backend(StoreBackend::Postgres).await;
+
+    app.create_user_and_remember_device(&get_random_email(), "MySecretPwd")
+        .await;
+
+    let response = app.get_trusted_devices().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let json_body = response
+        .json::<TrustedDevicesResponse>()
+        .await
+        .expect("Could not deserialize response body to TrustedDevicesResponse");
+
+    assert_eq!(json_body.devices.len(), 1);
+
+    let response = app
+        .post_revoke_trusted_device(&json!({ "deviceId": json_body.devices[0].id }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let json_body = app
+        .get_trusted_devices()
+        .await
+        .json::<TrustedDevicesResponse>()
+        .await
+        .expect("Could not deserialize response body to TrustedDevicesResponse");
+
+    assert!(json_body.devices.is_empty());
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_accept_magic_link_only_once() {
+    let mut app = TestApp::with_store_backend(StoreBackend::Postgres).await;
+
+    let random_email = get_random_email();
+    let response = app
+        .post_signup(&json!({
+            "email": random_email,
+            "password": "MySecretPwd",
+            "requires2FA": false
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.post_magic_link(&json!({ "email": random_email })).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let emails = app.email_client.emails_to(&random_email);
+    let content = &emails.last().expect("No magic link email sent").content;
+    let start = content.find("magicLinkToken=").expect("No token in email") + 15;
+    let token = &content[start..start + 64];
+
+    let response = app.post_verify_magic_link(&json!({ "token": token })).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_verify_magic_link(&json!({ "token": token })).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
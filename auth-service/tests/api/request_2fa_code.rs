@@ -0,0 +1,48 @@
+use auth_service::{domain::LoginAttemptId, routes::TwoFactorAuthResponse};
+
+use crate::helpers::{get_random_email, TestApp};
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_request_2fa_code().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_200_and_store_code_for_logged_in_user() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let response = app
+        .create_user_and_login(&random_email, "MySecretPwd", false)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_request_2fa_code().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    assert_eq!(json_body.message, "2FA code sent");
+
+    let (stored_email, _) = app
+        .two_fa_code_store
+        .get_code(&LoginAttemptId::parse(json_body.login_attempt_id).unwrap())
+        .await
+        .expect("2FA code was not stored");
+
+    assert_eq!(stored_email.as_ref(), random_email);
+
+    app.cleanup().await;
+}
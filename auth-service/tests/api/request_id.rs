@@ -0,0 +1,70 @@
+use auth_service::ErrorResponse;
+use serde_json::json;
+
+use crate::helpers::TestApp;
+
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+#[tokio::test]
+async fn should_echo_the_incoming_request_id() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .http_client
+        .get(format!("{}/health/live", &app.address))
+        .header(REQUEST_ID_HEADER, "support-ticket-1234")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers().get(REQUEST_ID_HEADER).unwrap(),
+        "support-ticket-1234"
+    );
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_generate_a_request_id_if_none_is_sent() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_health_live().await;
+
+    let request_id = response
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .expect("No request id in the response")
+        .to_str()
+        .unwrap();
+    assert!(uuid::Uuid::parse_str(request_id).is_ok());
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_include_the_request_id_in_error_responses() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .http_client
+        .post(format!("{}/login", &app.address))
+        .header(REQUEST_ID_HEADER, "support-ticket-5678")
+        .json(&json!({
+            "email": "invalidemail",
+            "password": "pwd"
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 400);
+    let body = response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse");
+    assert_eq!(body.request_id.as_deref(), Some("support-ticket-5678"));
+
+    app.cleanup().await;
+}
@@ -0,0 +1,248 @@
+use auth_service::{
+    domain::{LoginAttemptId, TwoFAResend},
+    routes::{Resend2FAResponse, TwoFactorAuthResponse},
+    utils::{constants::StoreBackend, settings::TwoFASettings},
+    ErrorResponse,
+};
+use chrono::Duration;
+use reqwest::header::RETRY_AFTER;
+use serde_json::json;
+use tokio::task::JoinSet;
+use uuid::Uuid;
+
+use crate::helpers::{default_two_fa_settings, get_random_email, TestApp};
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_resend_2fa(&json!({
+            "definitely invalid body": true
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 422);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_input() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_resend_2fa(&json!({
+            "email": "invalidemail",
+            "loginAttemptId": "123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_unknown_login_attempt() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let response = app
+        .create_user_and_login(&random_email, "MySecretPwd", true)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let response = app
+        .post_resend_2fa(&json!({
+            "email": random_email,
+            "loginAttemptId": Uuid::new_v4().to_string()
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_429_with_remaining_cooldown_if_resent_too_soon() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let response = app
+        .create_user_and_login(&random_email, "MySecretPwd", true)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    let response = app
+        .post_resend_2fa(&json!({
+            "email": random_email,
+            "loginAttemptId": json_body.login_attempt_id
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 429);
+
+    let retry_after: i64 = response
+        .headers()
+        .get(RETRY_AFTER)
+        .expect("No Retry-After header found")
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+
+    assert!(retry_after > 0);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Resend cooldown active".to_owned()
+    );
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_200_and_resend_code_after_cooldown() {
+    let mut app = TestApp::with_two_fa(TwoFASettings {
+        resend_cooldown_seconds: 0,
+        max_resends: 3,
+        ..default_two_fa_settings()
+    })
+    .await;
+
+    let random_email = get_random_email();
+
+    let response = app
+        .create_user_and_login(&random_email, "MySecretPwd", true)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    let response = app
+        .post_resend_2fa(&json!({
+            "email": random_email,
+            "loginAttemptId": json_body.login_attempt_id
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let json_body = response
+        .json::<Resend2FAResponse>()
+        .await
+        .expect("Could not deserialize response body to Resend2FAResponse");
+
+    assert_eq!(json_body.message, "2FA code resent".to_owned());
+    assert_eq!(json_body.retry_after, 0);
+
+    let emails = app.email_client.emails_to(&random_email);
+    assert_eq!(emails.len(), 2);
+    assert_eq!(emails[0].content, emails[1].content);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_429_once_max_resends_reached() {
+    let mut app = TestApp::with_two_fa(TwoFASettings {
+        resend_cooldown_seconds: 0,
+        max_resends: 2,
+        ..default_two_fa_settings()
+    })
+    .await;
+
+    let random_email = get_random_email();
+
+    let response = app
+        .create_user_and_login(&random_email, "MySecretPwd", true)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+    let body = json!({
+        "email": random_email,
+        "loginAttemptId": json_body.login_attempt_id
+    });
+
+    for _ in 0..2 {
+        let response = app.post_resend_2fa(&body).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    let response = app.post_resend_2fa(&body).await;
+
+    assert_eq!(response.status().as_u16(), 429);
+    assert!(response.headers().get(RETRY_AFTER).is_none());
+    assert_eq!(app.email_client.emails_to(&random_email).len(), 3);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_record_one_of_concurrent_resends() {
+    for backend in [StoreBackend::Redis, StoreBackend::Postgres] {
+        let mut app = TestApp::with_store_backend(backend).await;
+
+        let random_email = get_random_email();
+
+        let response = app
+            .create_user_and_login(&random_email, "MySecretPwd", true)
+            .await;
+
+        assert_eq!(response.status().as_u16(), 206);
+
+        let json_body = response
+            .json::<TwoFactorAuthResponse>()
+            .await
+            .expect("Could not deserialize response body to TwoFactorAuthResponse");
+        let login_attempt_id = LoginAttemptId::parse(json_body.login_attempt_id).unwrap();
+
+        let mut resends = JoinSet::new();
+        for _ in 0..10 {
+            let store = app.two_fa_code_store.clone();
+            let login_attempt_id = login_attempt_id.clone();
+            resends.spawn(async move {
+                store
+                    .record_resend(&login_attempt_id, Duration::zero(), 1)
+                    .await
+                    .unwrap()
+            });
+        }
+
+        let mut recorded = 0;
+        while let Some(resend) = resends.join_next().await {
+            match resend.unwrap() {
+                TwoFAResend::Recorded => recorded += 1,
+                resend => assert_eq!(resend, TwoFAResend::LimitReached),
+            }
+        }
+
+        assert_eq!(recorded, 1);
+
+        app.cleanup().await;
+    }
+}
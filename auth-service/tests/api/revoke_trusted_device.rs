@@ -0,0 +1,73 @@
+use auth_service::{domain::TrustedDeviceId, routes::TrustedDevicesResponse};
+use serde_json::json;
+
+use crate::helpers::{get_random_email, TestApp};
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_revoke_trusted_device(&json!({
+            "deviceId": TrustedDeviceId::default().as_ref()
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_404_if_unknown_device() {
+    let mut app = TestApp::new().await;
+
+    app.create_user_and_remember_device(&get_random_email(), "MySecretPwd")
+        .await;
+
+    let response = app
+        .post_revoke_trusted_device(&json!({
+            "deviceId": TrustedDeviceId::default().as_ref()
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 404);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_require_2fa_again_after_revoking_device() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    app.create_user_and_remember_device(&random_email, "MySecretPwd")
+        .await;
+
+    let devices = app
+        .get_trusted_devices()
+        .await
+        .json::<TrustedDevicesResponse>()
+        .await
+        .expect("Could not deserialize response body to TrustedDevicesResponse")
+        .devices;
+
+    let response = app
+        .post_revoke_trusted_device(&json!({
+            "deviceId": devices[0].id
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_login(&json!({
+            "email": random_email,
+            "password": "MySecretPwd",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    app.cleanup().await;
+}
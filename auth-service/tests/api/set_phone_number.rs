@@ -0,0 +1,140 @@
+use auth_service::{
+    domain::{Email, LoginAttemptId, PhoneNumber, TwoFAChannel},
+    routes::TwoFactorAuthResponse,
+};
+use serde_json::json;
+
+use crate::helpers::{get_random_email, TestApp};
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_phone_number(&json!({
+            "definitely invalid body": true
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 422);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_phone_number(&json!({
+            "phoneNumber": "+40712345678"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_phone_number() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .create_user_and_login(&get_random_email(), "MySecretPwd", false)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_phone_number(&json!({
+            "phoneNumber": "0712 345 678"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_200_and_store_verification_code() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let response = app
+        .create_user_and_login(&random_email, "MySecretPwd", false)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_phone_number(&json!({
+            "phoneNumber": "+40712345678"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    assert_eq!(json_body.message, "Verification code sent");
+
+    let (stored_email, _) = app
+        .two_fa_code_store
+        .get_code(&LoginAttemptId::parse(json_body.login_attempt_id).unwrap())
+        .await
+        .expect("Verification code was not stored");
+
+    assert_eq!(stored_email.as_ref(), random_email);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_leave_user_unchanged_if_sms_not_enabled() {
+    let mut app = TestApp::without_sms().await;
+
+    let random_email = get_random_email();
+
+    let response = app
+        .create_user_and_login(&random_email, "MySecretPwd", false)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let email = Email::parse(&random_email).unwrap();
+    let phone_number = PhoneNumber::parse("+40712345678").unwrap();
+    app.user_store
+        .set_phone_number(email.clone(), phone_number.clone())
+        .await
+        .unwrap();
+    app.user_store
+        .verify_phone_number(email.clone(), phone_number.clone())
+        .await
+        .unwrap();
+    app.user_store
+        .update_two_fa_channel(email.clone(), TwoFAChannel::Sms)
+        .await
+        .unwrap();
+
+    let response = app
+        .post_phone_number(&json!({
+            "phoneNumber": "+40798765432"
+        }))
+        .await;
+
+    // The phone routes are not served, so the request falls through to the static assets.
+    assert_eq!(response.status().as_u16(), 405);
+
+    let user = app.user_store.get_user(email).await.unwrap();
+    assert_eq!(user.phone_number(), &Some(phone_number));
+    assert!(user.phone_verified());
+    assert_eq!(user.two_fa_channel(), &TwoFAChannel::Sms);
+
+    app.cleanup().await;
+}
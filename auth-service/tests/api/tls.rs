@@ -0,0 +1,205 @@
+use std::{path::PathBuf, time::Duration};
+
+use auth_service::{
+    domain::LoginAttemptId,
+    routes::TwoFactorAuthResponse,
+    utils::{
+        constants::{JWT_COOKIE_NAME, MAGIC_LINK_NONCE_COOKIE_NAME, TRUSTED_DEVICE_COOKIE_NAME},
+        settings::TlsSettings,
+    },
+};
+use reqwest::{redirect::Policy, Certificate, StatusCode};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::helpers::{get_random_email, TestApp};
+
+/// Certificate files for the test app, self-signed for its address.
+struct CertificateFiles {
+    dir: PathBuf,
+}
+
+impl CertificateFiles {
+    fn new() -> Self {
+        let dir = std::env::temp_dir().join(format!("auth-service-tls-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        Self { dir }
+    }
+
+    fn settings(&self, redirect_address: Option<&str>) -> TlsSettings {
+        TlsSettings {
+            enabled: true,
+            cert_path: self.dir.join("cert.pem").display().to_string(),
+            key_path: self.dir.join("key.pem").display().to_string(),
+            reload_interval_seconds: 1,
+            redirect_address: redirect_address.map(str::to_owned),
+        }
+    }
+
+    /// Writes a new certificate and key over the current ones, as a renewal would, and returns
+    /// the certificate.
+    fn renew(&self) -> Certificate {
+        let certified = rcgen::generate_simple_self_signed(vec![
+            "127.0.0.1".to_owned(),
+            "localhost".to_owned(),
+        ])
+        .unwrap();
+        let settings = self.settings(None);
+        std::fs::write(settings.key_path, certified.key_pair.serialize_pem()).unwrap();
+        std::fs::write(settings.cert_path, certified.cert.pem()).unwrap();
+
+        Certificate::from_pem(certified.cert.pem().as_bytes()).unwrap()
+    }
+}
+
+impl Drop for CertificateFiles {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn client_trusting(certificate: Certificate) -> reqwest::Client {
+    reqwest::Client::builder()
+        .tls_built_in_root_certs(false)
+        .add_root_certificate(certificate)
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn should_serve_over_https() {
+    let certificates = CertificateFiles::new();
+    certificates.renew();
+    let mut app = TestApp::with_tls(certificates.settings(None)).await;
+
+    let response = app.get_health_live().await;
+
+    assert!(app.address.starts_with("https://"));
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let plain_http = reqwest::get(format!(
+        "{}/health/live",
+        app.address.replacen("https://", "http://", 1)
+    ))
+    .await;
+    assert!(plain_http.is_err());
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_serve_a_renewed_certificate_without_restarting() {
+    let certificates = CertificateFiles::new();
+    let old_certificate = certificates.renew();
+    let mut app = TestApp::with_tls(certificates.settings(None)).await;
+    let url = format!("{}/health/live", app.address);
+
+    let new_certificate = certificates.renew();
+
+    let mut served = false;
+    for _ in 0..50 {
+        let response = client_trusting(new_certificate.clone())
+            .get(&url)
+            .send()
+            .await;
+        if response.is_ok() {
+            served = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(served, "the renewed certificate was not served");
+
+    let response = client_trusting(old_certificate).get(&url).send().await;
+    assert!(response.is_err());
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_redirect_plain_http_to_https() {
+    let certificates = CertificateFiles::new();
+    certificates.renew();
+    let mut app = TestApp::with_tls(certificates.settings(Some("127.0.0.1:0"))).await;
+    let redirect_address = app.redirect_address.clone().expect("No redirect listener");
+
+    let response = reqwest::Client::builder()
+        .redirect(Policy::none())
+        .build()
+        .unwrap()
+        .post(format!("http://{redirect_address}/login?next=%2Faccount"))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(
+        response.headers()["location"],
+        format!("{}/login?next=%2Faccount", app.address).as_str()
+    );
+
+    let response = app
+        .http_client
+        .get(format!("http://{redirect_address}/health/live"))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.url().as_str().starts_with(&app.address));
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_set_secure_cookies() {
+    let certificates = CertificateFiles::new();
+    certificates.renew();
+    let mut app = TestApp::with_tls(certificates.settings(None)).await;
+
+    let random_email = get_random_email();
+    let response = app
+        .create_user_and_login(&random_email, "MySecretPwd", true)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+    let (_, code) = app
+        .two_fa_code_store
+        .get_code(&LoginAttemptId::parse(json_body.login_attempt_id.clone()).unwrap())
+        .await
+        .unwrap();
+
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": random_email,
+            "loginAttemptId": json_body.login_attempt_id,
+            "2FACode": code.as_ref(),
+            "rememberDevice": true
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    for name in [JWT_COOKIE_NAME, TRUSTED_DEVICE_COOKIE_NAME] {
+        let cookie = response
+            .cookies()
+            .find(|cookie| cookie.name() == name)
+            .unwrap_or_else(|| panic!("No {name} cookie found"));
+        assert!(cookie.secure(), "{name} cookie is not secure");
+    }
+
+    let response = app.post_magic_link(&json!({ "email": random_email })).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let nonce_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == MAGIC_LINK_NONCE_COOKIE_NAME)
+        .expect("No magic link nonce cookie found");
+    assert!(nonce_cookie.secure());
+
+    app.cleanup().await;
+}
@@ -0,0 +1,60 @@
+use auth_service::routes::TrustedDevicesResponse;
+
+use crate::helpers::{get_random_email, TestApp};
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_trusted_devices().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_empty_list_if_no_trusted_devices() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .create_user_and_login(&get_random_email(), "MySecretPwd", false)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.get_trusted_devices().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let json_body = response
+        .json::<TrustedDevicesResponse>()
+        .await
+        .expect("Could not deserialize response body to TrustedDevicesResponse");
+
+    assert!(json_body.devices.is_empty());
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_list_remembered_device() {
+    let mut app = TestApp::new().await;
+
+    app.create_user_and_remember_device(&get_random_email(), "MySecretPwd")
+        .await;
+
+    let response = app.get_trusted_devices().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let json_body = response
+        .json::<TrustedDevicesResponse>()
+        .await
+        .expect("Could not deserialize response body to TrustedDevicesResponse");
+
+    assert_eq!(json_body.devices.len(), 1);
+    assert!(json_body.devices[0].expires_at > json_body.devices[0].created_at);
+
+    app.cleanup().await;
+}
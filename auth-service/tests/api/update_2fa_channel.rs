@@ -0,0 +1,73 @@
+use serde_json::json;
+
+use crate::helpers::{get_random_email, TestApp};
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_2fa_channel(&json!({ "channel": "email" })).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_channel() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .create_user_and_login(&get_random_email(), "MySecretPwd", false)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_2fa_channel(&json!({ "channel": "pigeon" })).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_sms_without_verified_phone_number() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .create_user_and_login(&get_random_email(), "MySecretPwd", false)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_phone_number(&json!({
+            "phoneNumber": "+40712345678"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_2fa_channel(&json!({ "channel": "sms" })).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_200_if_email_channel() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .create_user_and_login(&get_random_email(), "MySecretPwd", false)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_2fa_channel(&json!({ "channel": "email" })).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup().await;
+}
@@ -0,0 +1,331 @@
+//! Checks every `UserStore` implementation has to pass. Each check uses its own email prefix, so
+//! they can share a store.
+
+use std::{sync::Arc, time::Duration};
+
+use auth_service::{
+    configure_redis,
+    domain::{
+        Email, NewUser, Password, UserCache, UserCursor, UserFilter, UserId, UserQuery, UserStatus,
+        UserStore, UserStoreError, UserUpdate,
+    },
+    services::{
+        CachedUserStore, HashmapUserStore, InMemoryUserCache, RedisUserCache, SqliteUserStore,
+    },
+    utils::{constants::test, settings::Settings},
+};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::sqlite::SqlitePoolOptions;
+use uuid::Uuid;
+
+use crate::helpers::TestApp;
+
+#[tokio::test]
+async fn hashmap_user_store_conforms() {
+    run_conformance_suite(&HashmapUserStore::default()).await;
+}
+
+#[tokio::test]
+async fn sqlite_user_store_conforms() {
+    // A single connection keeps every query on the same in-memory database.
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    sqlx::migrate!("./migrations_sqlite")
+        .run(&pool)
+        .await
+        .unwrap();
+
+    run_conformance_suite(&SqliteUserStore::new(pool)).await;
+}
+
+#[tokio::test]
+async fn postgres_user_store_conforms() {
+    let mut app = TestApp::new().await;
+
+    run_conformance_suite(app.user_store.as_ref()).await;
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn in_memory_cached_user_store_conforms() {
+    let cache = InMemoryUserCache::new(100, Duration::from_secs(60));
+
+    run_conformance_suite(&CachedUserStore::new(
+        HashmapUserStore::default(),
+        Arc::new(cache),
+    ))
+    .await;
+}
+
+#[tokio::test]
+async fn redis_cached_user_store_conforms() {
+    let settings = Settings::from_toml(test::SETTINGS).unwrap();
+    let cache = RedisUserCache::new(
+        configure_redis(&settings.redis).await,
+        Duration::from_secs(60),
+    );
+
+    run_conformance_suite(&CachedUserStore::new(
+        HashmapUserStore::default(),
+        Arc::new(cache),
+    ))
+    .await;
+}
+
+#[tokio::test]
+async fn redis_user_cache_drops_user_read_before_invalidation() {
+    let settings = Settings::from_toml(test::SETTINGS).unwrap();
+    let cache = RedisUserCache::new(
+        configure_redis(&settings.redis).await,
+        Duration::from_secs(60),
+    );
+    let store = HashmapUserStore::default();
+    let user = store
+        .add_user(new_user(&email_prefix(), 0, false))
+        .await
+        .unwrap();
+
+    let version = cache.version(user.email()).await.unwrap();
+    cache.invalidate(user.email()).await.unwrap();
+    cache.insert(&user, version).await.unwrap();
+    assert_eq!(cache.get(user.email()).await.unwrap(), None);
+
+    let version = cache.version(user.email()).await.unwrap();
+    cache.insert(&user, version).await.unwrap();
+    assert_eq!(cache.get(user.email()).await.unwrap(), Some(user));
+}
+
+async fn run_conformance_suite(store: &dyn UserStore) {
+    add_and_validate_user(store).await;
+    update_user(store).await;
+    delete_user(store).await;
+    list_and_count_users(store).await;
+}
+
+fn email_prefix() -> String {
+    format!("{}-", Uuid::new_v4())
+}
+
+fn email(prefix: &str, n: usize) -> Email {
+    Email::parse(&format!("{prefix}{n}@example.com")).unwrap()
+}
+
+fn password(password: &str) -> Password {
+    Password::parse(Secret::new(password.to_owned())).unwrap()
+}
+
+fn new_user(prefix: &str, n: usize, requires_2fa: bool) -> NewUser {
+    NewUser::new(email(prefix, n), password("password123"), requires_2fa)
+}
+
+async fn add_and_validate_user(store: &dyn UserStore) {
+    let prefix = email_prefix();
+
+    let stored = store.add_user(new_user(&prefix, 0, true)).await.unwrap();
+    assert!(*stored.requires_2fa());
+    assert_eq!(stored.status(), &UserStatus::Active);
+    assert_ne!(
+        stored.password_hash().as_ref().expose_secret(),
+        "password123"
+    );
+
+    assert_eq!(store.get_user(email(&prefix, 0)).await, Ok(stored.clone()));
+    assert_eq!(
+        store.get_cached_user(email(&prefix, 0)).await,
+        Ok(stored.clone())
+    );
+    assert_eq!(store.get_user_by_id(stored.id()).await, Ok(stored.clone()));
+    assert_eq!(
+        store
+            .validate_user(email(&prefix, 0), password("password123"))
+            .await,
+        Ok(stored.clone())
+    );
+    assert_eq!(
+        store
+            .validate_user(email(&prefix, 0), password("wrongpassword"))
+            .await,
+        Err(UserStoreError::InvalidCredentials)
+    );
+    assert_eq!(
+        store.add_user(new_user(&prefix, 0, false)).await,
+        Err(UserStoreError::UserAlreadyExists)
+    );
+}
+
+async fn update_user(store: &dyn UserStore) {
+    let prefix = email_prefix();
+    let stored = store.add_user(new_user(&prefix, 0, false)).await.unwrap();
+    store.add_user(new_user(&prefix, 1, false)).await.unwrap();
+
+    // Cached, so the update has to drop it.
+    store.get_cached_user(email(&prefix, 0)).await.unwrap();
+
+    let updated = store
+        .update_user(
+            stored.id(),
+            UserUpdate {
+                email: Some(email(&prefix, 2)),
+                password: Some(password("newpassword123")),
+                requires_2fa: Some(true),
+                status: Some(UserStatus::Disabled),
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(updated.id(), stored.id());
+    assert_eq!(updated.email(), &email(&prefix, 2));
+    assert!(*updated.requires_2fa());
+    assert!(updated.is_disabled());
+    assert!(updated.updated_at() >= stored.updated_at());
+    assert_eq!(store.get_user_by_id(stored.id()).await, Ok(updated.clone()));
+
+    assert_eq!(
+        store.get_user(email(&prefix, 0)).await,
+        Err(UserStoreError::UserNotFound)
+    );
+    assert_eq!(
+        store.get_cached_user(email(&prefix, 0)).await,
+        Err(UserStoreError::UserNotFound)
+    );
+    assert_eq!(
+        store
+            .validate_user(email(&prefix, 2), password("password123"))
+            .await,
+        Err(UserStoreError::InvalidCredentials)
+    );
+    assert_eq!(
+        store
+            .validate_user(email(&prefix, 2), password("newpassword123"))
+            .await,
+        Ok(updated)
+    );
+
+    let unchanged = store
+        .update_user(stored.id(), UserUpdate::default())
+        .await
+        .unwrap();
+    assert_eq!(unchanged.email(), &email(&prefix, 2));
+    assert!(unchanged.is_disabled());
+
+    assert_eq!(
+        store
+            .update_user(
+                stored.id(),
+                UserUpdate {
+                    email: Some(email(&prefix, 1)),
+                    ..UserUpdate::default()
+                }
+            )
+            .await,
+        Err(UserStoreError::UserAlreadyExists)
+    );
+    assert_eq!(
+        store
+            .update_user(&UserId::default(), UserUpdate::default())
+            .await,
+        Err(UserStoreError::UserNotFound)
+    );
+}
+
+async fn delete_user(store: &dyn UserStore) {
+    let prefix = email_prefix();
+    let stored = store.add_user(new_user(&prefix, 0, false)).await.unwrap();
+
+    assert_eq!(store.delete_user(stored.id()).await, Ok(()));
+    assert_eq!(
+        store.get_user_by_id(stored.id()).await,
+        Err(UserStoreError::UserNotFound)
+    );
+    assert_eq!(
+        store.delete_user(stored.id()).await,
+        Err(UserStoreError::UserNotFound)
+    );
+}
+
+async fn list_and_count_users(store: &dyn UserStore) {
+    let prefix = email_prefix();
+    let mut users = Vec::new();
+    for n in 0..5 {
+        users.push(store.add_user(new_user(&prefix, n, n < 2)).await.unwrap());
+    }
+    users[4] = store
+        .update_user(
+            users[4].id(),
+            UserUpdate {
+                status: Some(UserStatus::Disabled),
+                ..UserUpdate::default()
+            },
+        )
+        .await
+        .unwrap();
+
+    let filter = UserFilter {
+        email_prefix: Some(prefix.clone()),
+        ..UserFilter::default()
+    };
+    assert_eq!(store.count_users(&filter).await, Ok(5));
+    assert_eq!(
+        store
+            .count_users(&UserFilter {
+                requires_2fa: Some(true),
+                ..filter.clone()
+            })
+            .await,
+        Ok(2)
+    );
+    assert_eq!(
+        store
+            .count_users(&UserFilter {
+                status: Some(UserStatus::Disabled),
+                ..filter.clone()
+            })
+            .await,
+        Ok(1)
+    );
+    assert_eq!(
+        store
+            .count_users(&UserFilter {
+                created_since: Some(*users[1].created_at()),
+                created_until: Some(*users[3].created_at()),
+                ..filter.clone()
+            })
+            .await,
+        Ok(2)
+    );
+
+    let mut listed = Vec::new();
+    let mut query = UserQuery {
+        filter: filter.clone(),
+        ..UserQuery::with_limit(2)
+    };
+    loop {
+        let page = store.list_users(&query).await.unwrap();
+        assert!(page.users.len() <= 2);
+        listed.extend(page.users);
+        match page.next_cursor {
+            // Cursors travel as strings, so round-trip them like a client would.
+            Some(cursor) => query.after = Some(UserCursor::parse(&cursor.to_string()).unwrap()),
+            None => break,
+        }
+    }
+    assert_eq!(listed, users);
+
+    let page = store
+        .list_users(&UserQuery {
+            filter: UserFilter {
+                requires_2fa: Some(false),
+                status: Some(UserStatus::Active),
+                ..filter
+            },
+            ..UserQuery::with_limit(10)
+        })
+        .await
+        .unwrap();
+    assert_eq!(page.users, users[2..4]);
+    assert_eq!(page.next_cursor, None);
+}
@@ -1,7 +1,10 @@
 use auth_service::{
-    domain::Email, routes::TwoFactorAuthResponse, utils::constants::JWT_COOKIE_NAME,
+    domain::LoginAttemptId,
+    routes::TwoFactorAuthResponse,
+    utils::constants::{StoreBackend, JWT_COOKIE_NAME, TRUSTED_DEVICE_COOKIE_NAME},
 };
 use serde_json::json;
+use tokio::task::JoinSet;
 
 use crate::helpers::{get_random_email, TestApp};
 
@@ -77,11 +80,14 @@ async fn should_return_401_if_old_code() {
 
     assert_eq!(response.status().as_u16(), 206);
 
+    let old_json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
     let (_, old_2fa_code) = app
         .two_fa_code_store
-        .read()
-        .await
-        .get_code(&Email::parse(&random_email).unwrap())
+        .get_code(&LoginAttemptId::parse(old_json_body.login_attempt_id).unwrap())
         .await
         .unwrap();
 
@@ -131,9 +137,7 @@ async fn should_return_401_if_same_code_twice() {
 
     let (_, code) = app
         .two_fa_code_store
-        .read()
-        .await
-        .get_code(&Email::parse(&random_email).unwrap())
+        .get_code(&LoginAttemptId::parse(json_body.login_attempt_id.clone()).unwrap())
         .await
         .unwrap();
 
@@ -179,9 +183,7 @@ async fn should_return_200_if_correct_code() {
 
     let (_, code) = app
         .two_fa_code_store
-        .read()
-        .await
-        .get_code(&Email::parse(&random_email).unwrap())
+        .get_code(&LoginAttemptId::parse(json_body.login_attempt_id.clone()).unwrap())
         .await
         .unwrap();
 
@@ -203,3 +205,340 @@ async fn should_return_200_if_correct_code() {
 
     app.cleanup().await;
 }
+
+fn wrong_code_for(code: &str) -> String {
+    match code {
+        "000000" => "111111".to_owned(),
+        _ => "000000".to_owned(),
+    }
+}
+
+#[tokio::test]
+async fn should_return_200_if_correct_code_after_failed_attempt() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let response = app
+        .create_user_and_login(&random_email, "MySecretPwd", true)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    let (_, code) = app
+        .two_fa_code_store
+        .get_code(&LoginAttemptId::parse(json_body.login_attempt_id.clone()).unwrap())
+        .await
+        .unwrap();
+
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": random_email,
+            "loginAttemptId": json_body.login_attempt_id,
+            "2FACode": wrong_code_for(code.as_ref())
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": random_email,
+            "loginAttemptId": json_body.login_attempt_id,
+            "2FACode": code.as_ref().to_owned()
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_code_invalidated_after_max_attempts() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let response = app
+        .create_user_and_login(&random_email, "MySecretPwd", true)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    let (_, code) = app
+        .two_fa_code_store
+        .get_code(&LoginAttemptId::parse(json_body.login_attempt_id.clone()).unwrap())
+        .await
+        .unwrap();
+
+    for _ in 0..app.settings.two_fa.max_attempts {
+        let response = app
+            .post_verify_2fa(&json!({
+                "email": random_email,
+                "loginAttemptId": json_body.login_attempt_id,
+                "2FACode": wrong_code_for(code.as_ref())
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": random_email,
+            "loginAttemptId": json_body.login_attempt_id,
+            "2FACode": code.as_ref().to_owned()
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_for_correct_code_once_attempts_are_used_up() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let response = app
+        .create_user_and_login(&random_email, "MySecretPwd", true)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+    let login_attempt_id = LoginAttemptId::parse(json_body.login_attempt_id.clone()).unwrap();
+
+    let (_, code) = app
+        .two_fa_code_store
+        .get_code(&login_attempt_id)
+        .await
+        .unwrap();
+
+    // As if other requests were still comparing their guesses.
+    for _ in 0..app.settings.two_fa.max_attempts {
+        app.two_fa_code_store
+            .record_attempt(&login_attempt_id)
+            .await
+            .unwrap();
+    }
+
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": random_email,
+            "loginAttemptId": json_body.login_attempt_id,
+            "2FACode": code.as_ref().to_owned()
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_count_each_concurrent_attempt() {
+    for backend in [StoreBackend::Redis, StoreBackend::Postgres] {
+        let mut app = TestApp::with_store_backend(backend).await;
+
+        let random_email = get_random_email();
+
+        let response = app
+            .create_user_and_login(&random_email, "MySecretPwd", true)
+            .await;
+
+        assert_eq!(response.status().as_u16(), 206);
+
+        let json_body = response
+            .json::<TwoFactorAuthResponse>()
+            .await
+            .expect("Could not deserialize response body to TwoFactorAuthResponse");
+        let login_attempt_id = LoginAttemptId::parse(json_body.login_attempt_id).unwrap();
+
+        let mut attempts = JoinSet::new();
+        for _ in 0..20 {
+            let store = app.two_fa_code_store.clone();
+            let login_attempt_id = login_attempt_id.clone();
+            attempts.spawn(async move { store.record_attempt(&login_attempt_id).await.unwrap() });
+        }
+
+        let mut counts = Vec::new();
+        while let Some(count) = attempts.join_next().await {
+            counts.push(count.unwrap());
+        }
+        counts.sort_unstable();
+
+        assert_eq!(counts, (1..=20).collect::<Vec<u32>>());
+
+        app.cleanup().await;
+    }
+}
+
+#[tokio::test]
+async fn should_return_200_for_concurrent_login_attempts() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let response = app
+        .create_user_and_login(&random_email, "MySecretPwd", true)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let laptop_json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    let response = app
+        .post_login(&json!({
+            "email": random_email,
+            "password": "MySecretPwd"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let phone_json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    for json_body in [laptop_json_body, phone_json_body] {
+        let (_, code) = app
+            .two_fa_code_store
+            .get_code(&LoginAttemptId::parse(json_body.login_attempt_id.clone()).unwrap())
+            .await
+            .unwrap();
+
+        let response = app
+            .post_verify_2fa(&json!({
+                "email": random_email,
+                "loginAttemptId": json_body.login_attempt_id,
+                "2FACode": code.as_ref().to_owned()
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_login_attempt_evicted() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let response = app
+        .create_user_and_login(&random_email, "MySecretPwd", true)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    let (_, code) = app
+        .two_fa_code_store
+        .get_code(&LoginAttemptId::parse(json_body.login_attempt_id.clone()).unwrap())
+        .await
+        .unwrap();
+
+    for _ in 0..app.settings.two_fa.max_pending_attempts {
+        let response = app
+            .post_login(&json!({
+                "email": random_email,
+                "password": "MySecretPwd"
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 206);
+    }
+
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": random_email,
+            "loginAttemptId": json_body.login_attempt_id,
+            "2FACode": code.as_ref().to_owned()
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_set_trusted_device_cookie_only_if_remember_device() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let mut response = app
+        .create_user_and_login(&random_email, "MySecretPwd", true)
+        .await;
+
+    for remember_device in [false, true] {
+        assert_eq!(response.status().as_u16(), 206);
+
+        let json_body = response
+            .json::<TwoFactorAuthResponse>()
+            .await
+            .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+        let (_, code) = app
+            .two_fa_code_store
+            .get_code(&LoginAttemptId::parse(json_body.login_attempt_id.clone()).unwrap())
+            .await
+            .unwrap();
+
+        let verify_response = app
+            .post_verify_2fa(&json!({
+                "email": random_email,
+                "loginAttemptId": json_body.login_attempt_id,
+                "2FACode": code.as_ref(),
+                "rememberDevice": remember_device
+            }))
+            .await;
+
+        assert_eq!(verify_response.status().as_u16(), 200);
+        assert_eq!(
+            verify_response
+                .cookies()
+                .any(|cookie| cookie.name() == TRUSTED_DEVICE_COOKIE_NAME),
+            remember_device
+        );
+
+        response = app
+            .post_login(&json!({
+                "email": random_email,
+                "password": "MySecretPwd",
+            }))
+            .await;
+    }
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup().await;
+}
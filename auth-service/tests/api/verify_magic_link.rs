@@ -0,0 +1,240 @@
+use auth_service::{
+    domain::{AuditEventKind, AuditOutcome},
+    routes::AuditEventsResponse,
+    utils::constants::JWT_COOKIE_NAME,
+};
+use serde_json::json;
+
+use crate::helpers::{get_random_email, TestApp, ADMIN_API_TOKEN};
+
+/// Signs up a user, requests a magic link from the app's browser and returns the link token.
+async fn request_link(app: &TestApp, email: &str, requires_2fa: bool) -> String {
+    let response = app
+        .post_signup(&json!({
+            "email": email,
+            "password": "MySecretPwd",
+            "requires2FA": requires_2fa
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    request_another_link(app, email).await
+}
+
+/// Requests a magic link for an existing user from the app's browser and returns the link token.
+async fn request_another_link(app: &TestApp, email: &str) -> String {
+    let response = app.post_magic_link(&json!({ "email": email })).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let emails = app.email_client.emails_to(email);
+    let content = &emails.last().expect("No magic link email sent").content;
+    let start = content.find("magicLinkToken=").expect("No token in email") + 15;
+
+    content[start..start + 64].to_owned()
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_token() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_verify_magic_link(&json!({
+            "token": "not-a-token"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_200_and_set_auth_cookie() {
+    let mut app = TestApp::new().await;
+
+    let token = request_link(&app, &get_random_email(), false).await;
+
+    let response = app
+        .post_verify_magic_link(&json!({
+            "token": token
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    assert!(!auth_cookie.value().is_empty());
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_link_used_twice() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let token = request_link(&app, &random_email, false).await;
+
+    let response = app.post_verify_magic_link(&json!({ "token": token })).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Get a fresh nonce cookie, so only the reused token can make the request fail.
+    let response = app.post_magic_link(&json!({ "email": random_email })).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_verify_magic_link(&json!({ "token": token })).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_opened_in_another_browser() {
+    let mut app = TestApp::new().await;
+
+    let token = request_link(&app, &get_random_email(), false).await;
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/verify-magic-link", &app.address))
+        .json(&json!({ "token": token }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The link was not burnt by the other browser.
+    let response = app.post_verify_magic_link(&json!({ "token": token })).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_burn_link_if_opened_with_another_nonce() {
+    let mut app = TestApp::new().await;
+
+    let token = request_link(&app, &get_random_email(), false).await;
+
+    // Another browser holding a nonce from its own request.
+    let other_browser = reqwest::Client::builder()
+        .cookie_store(true)
+        .build()
+        .unwrap();
+    let response = other_browser
+        .post(format!("{}/magic-link", &app.address))
+        .json(&json!({ "email": get_random_email() }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = other_browser
+        .post(format!("{}/verify-magic-link", &app.address))
+        .json(&json!({ "token": token }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_verify_magic_link(&json!({ "token": token })).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_206_if_2fa_enabled() {
+    let mut app = TestApp::new().await;
+
+    let token = request_link(&app, &get_random_email(), true).await;
+
+    let response = app.post_verify_magic_link(&json!({ "token": token })).await;
+
+    assert_eq!(response.status().as_u16(), 206);
+    assert!(response
+        .cookies()
+        .all(|cookie| cookie.name() != JWT_COOKIE_NAME));
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_record_login_events() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let token = request_link(&app, &random_email, false).await;
+
+    // Burnt by a browser holding the nonce of another request.
+    let other_browser = reqwest::Client::builder()
+        .cookie_store(true)
+        .build()
+        .unwrap();
+    other_browser
+        .post(format!("{}/magic-link", &app.address))
+        .json(&json!({ "email": get_random_email() }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    let response = other_browser
+        .post(format!("{}/verify-magic-link", &app.address))
+        .json(&json!({ "token": token }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let token = request_another_link(&app, &random_email).await;
+    let response = app.post_verify_magic_link(&json!({ "token": token })).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .get_audit_events(
+            &[("actor", random_email.as_str()), ("kind", "login")],
+            ADMIN_API_TOKEN,
+        )
+        .await;
+    let body = response
+        .json::<AuditEventsResponse>()
+        .await
+        .expect("Could not deserialize response body to AuditEventsResponse");
+
+    let events = body
+        .events
+        .iter()
+        .map(|record| (record.event.outcome, record.event.user_id.is_some()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        events,
+        vec![
+            (AuditOutcome::Success, true),
+            (AuditOutcome::Failure, false)
+        ]
+    );
+    assert!(body
+        .events
+        .iter()
+        .all(|record| record.event.kind == AuditEventKind::Login));
+    assert_eq!(
+        body.events[1].event.reason.as_deref(),
+        Some("Incorrect credentials")
+    );
+
+    app.cleanup().await;
+}
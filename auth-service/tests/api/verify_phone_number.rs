@@ -0,0 +1,221 @@
+use auth_service::{domain::LoginAttemptId, routes::TwoFactorAuthResponse};
+use serde_json::json;
+
+use crate::helpers::{get_random_email, TestApp};
+
+async fn set_phone_number(app: &TestApp, phone_number: &str) -> (String, String) {
+    let response = app
+        .post_phone_number(&json!({
+            "phoneNumber": phone_number
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    let (_, code) = app
+        .two_fa_code_store
+        .get_code(&LoginAttemptId::parse(json_body.login_attempt_id.clone()).unwrap())
+        .await
+        .unwrap();
+
+    (json_body.login_attempt_id, code.as_ref().to_owned())
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_verify_phone_number(&json!({
+            "phoneNumber": "+40712345678",
+            "loginAttemptId": LoginAttemptId::default().as_ref(),
+            "2FACode": "123456"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_incorrect_code() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .create_user_and_login(&get_random_email(), "MySecretPwd", false)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let (login_attempt_id, code) = set_phone_number(&app, "+40712345678").await;
+    let wrong_code = if code == "000000" { "111111" } else { "000000" };
+
+    let response = app
+        .post_verify_phone_number(&json!({
+            "phoneNumber": "+40712345678",
+            "loginAttemptId": login_attempt_id,
+            "2FACode": wrong_code
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_phone_number_was_replaced() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .create_user_and_login(&get_random_email(), "MySecretPwd", false)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let (login_attempt_id, code) = set_phone_number(&app, "+40712345678").await;
+    set_phone_number(&app, "+40787654321").await;
+
+    let response = app
+        .post_verify_phone_number(&json!({
+            "phoneNumber": "+40712345678",
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_200_if_correct_code() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .create_user_and_login(&get_random_email(), "MySecretPwd", false)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let (login_attempt_id, code) = set_phone_number(&app, "+40712345678").await;
+
+    let response = app
+        .post_verify_phone_number(&json!({
+            "phoneNumber": "+40712345678",
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_2fa_channel(&json!({ "channel": "sms" })).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_code_was_sent_to_another_number() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .create_user_and_login(&get_random_email(), "MySecretPwd", false)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let (login_attempt_id, code) = set_phone_number(&app, "+40712345678").await;
+    set_phone_number(&app, "+40787654321").await;
+
+    let response = app
+        .post_verify_phone_number(&json!({
+            "phoneNumber": "+40787654321",
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_code_was_emailed_for_login() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let response = app
+        .create_user_and_login(&random_email, "MySecretPwd", true)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+    let login_attempt_id = LoginAttemptId::parse(json_body.login_attempt_id.clone()).unwrap();
+    let (_, code) = app
+        .two_fa_code_store
+        .get_code(&login_attempt_id)
+        .await
+        .unwrap();
+
+    let emails = app.email_client.emails_to(&random_email);
+    assert_eq!(emails.last().unwrap().content, code.as_ref());
+
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": random_email,
+            "loginAttemptId": json_body.login_attempt_id,
+            "2FACode": code.as_ref()
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_login(&json!({
+            "email": random_email,
+            "password": "MySecretPwd",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+    let (_, login_code) = app
+        .two_fa_code_store
+        .get_code(&LoginAttemptId::parse(json_body.login_attempt_id.clone()).unwrap())
+        .await
+        .unwrap();
+
+    set_phone_number(&app, "+40712345678").await;
+
+    let response = app
+        .post_verify_phone_number(&json!({
+            "phoneNumber": "+40712345678",
+            "loginAttemptId": json_body.login_attempt_id,
+            "2FACode": login_code.as_ref()
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
@@ -1,4 +1,10 @@
-use auth_service::utils::constants::JWT_COOKIE_NAME;
+use std::time::Duration;
+
+use auth_service::{
+    domain::{Email, UserStatus},
+    utils::constants::JWT_COOKIE_NAME,
+};
+use chrono::Utc;
 use reqwest::Url;
 
 use crate::helpers::{get_random_email, TestApp};
@@ -100,3 +106,98 @@ async fn should_return_401_if_banned_token() {
 
     app.cleanup().await;
 }
+
+#[tokio::test]
+async fn should_return_401_if_account_is_disabled() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let response = app
+        .create_user_and_login(&random_email, "MySecretPwd", false)
+        .await;
+
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    app.user_store
+        .update_status(Email::parse(&random_email).unwrap(), UserStatus::Disabled)
+        .await
+        .unwrap();
+
+    let response = app
+        .post_verify_token(&serde_json::json!({
+            "token": token
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_user_sessions_were_revoked() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let response = app
+        .create_user_and_login(&random_email, "MySecretPwd", false)
+        .await;
+
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let user = app
+        .user_store
+        .get_user(Email::parse(&random_email).unwrap())
+        .await
+        .unwrap();
+    app.banned_token_store
+        .revoke_user_tokens(user.id(), Utc::now())
+        .await
+        .unwrap();
+
+    let response = app
+        .post_verify_token(&serde_json::json!({
+            "token": token
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Tokens only carry whole seconds, so a new login has to wait for the next one.
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": random_email,
+            "password": "MySecretPwd"
+        }))
+        .await;
+
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let response = app
+        .post_verify_token(&serde_json::json!({
+            "token": token
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup().await;
+}